@@ -0,0 +1,430 @@
+use tantivy::schema::IndexRecordOption;
+use tantivy::tokenizer::{
+    AsciiFoldingFilter,
+    BoxTokenStream,
+    Language,
+    LowerCaser,
+    NgramTokenizer,
+    RawTokenizer,
+    RemoveLongFilter,
+    SimpleTokenizer,
+    Stemmer,
+    StopWordFilter,
+    TextAnalyzer,
+    Token,
+    TokenStream,
+    WhitespaceTokenizer,
+};
+
+/// The maximum token length used by the default analyzer.
+const DEFAULT_MAX_TOKEN_LENGTH: usize = 40;
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+/// The analyzer used to process the values of a text field.
+///
+/// By default this splits text on any non alpha-numeric characters,
+/// removes tokens longer than 40 bytes and lowercases them.
+pub struct AnalyzerOptions {
+    #[serde(default)]
+    /// The tokenizer used to split text into tokens.
+    pub tokenizer: Tokenizer,
+
+    #[serde(default = "default_filters")]
+    /// The filters applied to each token in order.
+    pub filters: Vec<TokenFilter>,
+
+    #[serde(default)]
+    /// The information recorded in the index for each token.
+    pub record: IndexRecord,
+}
+
+impl Default for AnalyzerOptions {
+    fn default() -> Self {
+        Self {
+            tokenizer: Tokenizer::default(),
+            filters: default_filters(),
+            record: IndexRecord::default(),
+        }
+    }
+}
+
+impl AnalyzerOptions {
+    /// Builds the tantivy text analyzer.
+    ///
+    /// Returns the reason the analyzer is invalid if it cannot be built.
+    pub fn build(&self) -> Result<TextAnalyzer, &'static str> {
+        let mut analyzer = self.tokenizer.build()?;
+        for filter in self.filters.iter() {
+            analyzer = filter.apply(analyzer)?;
+        }
+        Ok(analyzer)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Default,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(tag = "type", rename_all = "lowercase")]
+/// Splits text into individual tokens.
+pub enum Tokenizer {
+    #[default]
+    /// Splits text on any non alpha-numeric characters.
+    Simple,
+
+    /// Splits text on whitespace.
+    Whitespace,
+
+    /// Splits text into ngrams of `min_gram` to `max_gram` characters.
+    ///
+    /// i.e. "hello" with a `min_gram` of `2` and `max_gram` of `3` becomes
+    /// `["he", "hel", "el", "ell", "ll", "llo", "lo"]`.
+    Ngram {
+        /// The minimum length of each ngram.
+        min_gram: usize,
+        /// The maximum length of each ngram.
+        max_gram: usize,
+        #[serde(default)]
+        /// Only produce ngrams from the start of the text.
+        prefix_only: bool,
+    },
+
+    /// Keeps the entire text as a single token.
+    Raw,
+}
+
+impl Tokenizer {
+    fn build(&self) -> Result<TextAnalyzer, &'static str> {
+        let analyzer = match *self {
+            Tokenizer::Simple => TextAnalyzer::from(SimpleTokenizer),
+            Tokenizer::Whitespace => TextAnalyzer::from(WhitespaceTokenizer),
+            Tokenizer::Ngram {
+                min_gram,
+                max_gram,
+                prefix_only,
+            } => {
+                if min_gram == 0 {
+                    return Err("The ngram `min_gram` must be greater than `0`");
+                }
+                if min_gram > max_gram {
+                    return Err(
+                        "The ngram `min_gram` must be less than or equal to `max_gram`",
+                    );
+                }
+                TextAnalyzer::from(NgramTokenizer::new(min_gram, max_gram, prefix_only))
+            },
+            Tokenizer::Raw => TextAnalyzer::from(RawTokenizer),
+        };
+
+        Ok(analyzer)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// A filter which modifies or removes tokens.
+pub enum TokenFilter {
+    /// Lowercases each token.
+    Lowercase,
+
+    /// Converts any non-ASCII characters to their ASCII equivalent if one exists.
+    ///
+    /// i.e. "Café" becomes "Cafe".
+    AsciiFolding,
+
+    /// Reduces each token to its stem in the given language.
+    ///
+    /// i.e. "running" becomes "run".
+    Stemmer {
+        /// The language of the text.
+        language: StemmerLanguage,
+    },
+
+    /// Removes any tokens outside of the given length in bytes.
+    Length {
+        #[serde(default)]
+        /// The minimum length of a token.
+        min: Option<usize>,
+        #[serde(default)]
+        /// The maximum length of a token.
+        max: Option<usize>,
+    },
+
+    /// Removes any tokens matching the given words.
+    ///
+    /// Tokens are matched after any previous filters have been applied.
+    StopWords {
+        /// The words to remove.
+        words: Vec<String>,
+    },
+}
+
+impl TokenFilter {
+    fn apply(&self, analyzer: TextAnalyzer) -> Result<TextAnalyzer, &'static str> {
+        let analyzer = match self {
+            TokenFilter::Lowercase => analyzer.filter(LowerCaser),
+            TokenFilter::AsciiFolding => analyzer.filter(AsciiFoldingFilter),
+            TokenFilter::Stemmer { language } => {
+                analyzer.filter(Stemmer::new(Language::from(*language)))
+            },
+            TokenFilter::Length { min, max } => {
+                if let (Some(min), Some(max)) = (min, max) {
+                    if min > max {
+                        return Err(
+                            "The length filter `min` must be less than or equal to `max`",
+                        );
+                    }
+                }
+
+                let mut analyzer = analyzer;
+                if let Some(min) = *min {
+                    analyzer = analyzer.filter(RemoveShortFilter { min });
+                }
+                if let Some(max) = *max {
+                    // The filter removes tokens with a length greater than or
+                    // equal to the limit.
+                    analyzer =
+                        analyzer.filter(RemoveLongFilter::limit(max.saturating_add(1)));
+                }
+                analyzer
+            },
+            TokenFilter::StopWords { words } => {
+                analyzer.filter(StopWordFilter::remove(words.clone()))
+            },
+        };
+
+        Ok(analyzer)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+/// The languages supported by the stemmer filter.
+pub enum StemmerLanguage {
+    Arabic,
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Tamil,
+    Turkish,
+}
+
+impl From<StemmerLanguage> for Language {
+    fn from(language: StemmerLanguage) -> Self {
+        match language {
+            StemmerLanguage::Arabic => Language::Arabic,
+            StemmerLanguage::Danish => Language::Danish,
+            StemmerLanguage::Dutch => Language::Dutch,
+            StemmerLanguage::English => Language::English,
+            StemmerLanguage::Finnish => Language::Finnish,
+            StemmerLanguage::French => Language::French,
+            StemmerLanguage::German => Language::German,
+            StemmerLanguage::Greek => Language::Greek,
+            StemmerLanguage::Hungarian => Language::Hungarian,
+            StemmerLanguage::Italian => Language::Italian,
+            StemmerLanguage::Norwegian => Language::Norwegian,
+            StemmerLanguage::Portuguese => Language::Portuguese,
+            StemmerLanguage::Romanian => Language::Romanian,
+            StemmerLanguage::Russian => Language::Russian,
+            StemmerLanguage::Spanish => Language::Spanish,
+            StemmerLanguage::Swedish => Language::Swedish,
+            StemmerLanguage::Tamil => Language::Tamil,
+            StemmerLanguage::Turkish => Language::Turkish,
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Default,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+/// The information recorded in the index for each token.
+pub enum IndexRecord {
+    /// Only records which documents contain the token.
+    ///
+    /// Phrase queries and term frequency based scoring are not supported.
+    Basic,
+
+    /// Records the documents and the number of times the token occurs in each.
+    ///
+    /// Phrase queries are not supported.
+    Freqs,
+
+    #[default]
+    /// Records the documents, frequencies and positions of each token.
+    Positions,
+}
+
+impl From<IndexRecord> for IndexRecordOption {
+    fn from(record: IndexRecord) -> Self {
+        match record {
+            IndexRecord::Basic => IndexRecordOption::Basic,
+            IndexRecord::Freqs => IndexRecordOption::WithFreqs,
+            IndexRecord::Positions => IndexRecordOption::WithFreqsAndPositions,
+        }
+    }
+}
+
+fn default_filters() -> Vec<TokenFilter> {
+    vec![
+        TokenFilter::Length {
+            min: None,
+            max: Some(DEFAULT_MAX_TOKEN_LENGTH - 1),
+        },
+        TokenFilter::Lowercase,
+    ]
+}
+
+#[derive(Clone)]
+/// Removes tokens shorter than the given number of bytes.
+struct RemoveShortFilter {
+    min: usize,
+}
+
+impl tantivy::tokenizer::TokenFilter for RemoveShortFilter {
+    fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(RemoveShortFilterStream {
+            min: self.min,
+            tail: token_stream,
+        })
+    }
+}
+
+struct RemoveShortFilterStream<'a> {
+    min: usize,
+    tail: BoxTokenStream<'a>,
+}
+
+impl<'a> TokenStream for RemoveShortFilterStream<'a> {
+    fn advance(&mut self) -> bool {
+        while self.tail.advance() {
+            if self.tail.token().text.len() >= self.min {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn tokens(analyzer: &TextAnalyzer, text: &str) -> Vec<String> {
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
+        tokens
+    }
+
+    #[test]
+    fn test_default_analyzer() {
+        let analyzer = AnalyzerOptions::default().build().expect("Build analyzer");
+        assert_eq!(tokens(&analyzer, "Hello, World"), ["hello", "world"]);
+
+        let long_token = "a".repeat(DEFAULT_MAX_TOKEN_LENGTH);
+        assert!(tokens(&analyzer, &long_token).is_empty());
+    }
+
+    #[test]
+    fn test_custom_analyzer() {
+        let options: AnalyzerOptions = serde_json::from_value(json!({
+            "tokenizer": { "type": "whitespace" },
+            "filters": [
+                { "type": "lowercase" },
+                { "type": "ascii_folding" },
+                { "type": "length", "min": 2 },
+                { "type": "stop_words", "words": ["the"] },
+                { "type": "stemmer", "language": "english" },
+            ],
+            "record": "basic",
+        }))
+        .expect("Deserialize analyzer");
+
+        let analyzer = options.build().expect("Build analyzer");
+        assert_eq!(tokens(&analyzer, "The Cafés a running"), ["cafe", "run"],);
+    }
+
+    #[test]
+    fn test_invalid_analyzer() {
+        let options: AnalyzerOptions = serde_json::from_value(json!({
+            "tokenizer": { "type": "ngram", "min_gram": 3, "max_gram": 2 },
+        }))
+        .expect("Deserialize analyzer");
+        assert!(options.build().is_err());
+
+        let options: AnalyzerOptions = serde_json::from_value(json!({
+            "filters": [{ "type": "length", "min": 3, "max": 2 }],
+        }))
+        .expect("Deserialize analyzer");
+        assert!(options.build().is_err());
+    }
+}
@@ -0,0 +1,595 @@
+use std::collections::BTreeSet;
+
+use tantivy::schema::{
+    BytesOptions,
+    Cardinality,
+    DateOptions,
+    FacetOptions,
+    IndexRecordOption,
+    IpAddrOptions,
+    JsonObjectOptions,
+    NumericOptions,
+    Schema as TantivySchema,
+    SchemaBuilder,
+    TextFieldIndexing,
+    TextOptions as TantivyTextOptions,
+    FAST,
+    INDEXED,
+    STORED,
+};
+use tantivy::tokenizer::TokenizerManager;
+
+use super::{
+    BaseOptions,
+    DateFieldOptions,
+    DateFormat,
+    Field,
+    NumericFieldOptions,
+    Schema,
+    TextOptions,
+    VectorFieldOptions,
+    MAX_VECTOR_DIMS,
+    RESERVED_DOCUMENT_ID_FIELD,
+};
+
+/// The tokenizer used by JSON fields.
+const DEFAULT_TOKENIZER: &str = "default";
+/// The tokenizer used by raw string fields.
+const RAW_TOKENIZER: &str = "raw";
+
+#[derive(Debug, thiserror::Error)]
+/// An error preventing a schema from being converted into a tantivy schema.
+pub enum SchemaBuildError {
+    #[error("Schema-less indexes do not define any fields to build a schema from")]
+    SchemaLess,
+    #[error("The field name {0:?} is reserved for internal use")]
+    ReservedField(String),
+    #[error("The field {0:?} has already been added to the schema")]
+    DuplicateField(String),
+    #[error("The field {field:?} has an unsupported combination of options: {reason}")]
+    UnsupportedOptions {
+        /// The name of the field.
+        field: String,
+        /// Why the options are not supported.
+        reason: &'static str,
+    },
+}
+
+impl Schema {
+    /// Builds the tantivy schema for the index.
+    ///
+    /// This fails if the schema is schema-less or any of the fields
+    /// have options which are not supported together.
+    pub fn tantivy_schema(&self) -> Result<TantivySchema, SchemaBuildError> {
+        let fields = self.fields.as_ref().ok_or(SchemaBuildError::SchemaLess)?;
+
+        let mut builder = TantivySchemaBuilder::new();
+        for (name, field) in fields {
+            builder.add_field(name, field)?;
+        }
+
+        Ok(builder.build())
+    }
+
+    /// Registers the analyzers of each text field with the given tokenizer manager.
+    ///
+    /// This must be called with the index's tokenizer manager when the index
+    /// is created or opened, each analyzer is registered as [tokenizer_name].
+    pub fn register_tokenizers(
+        &self,
+        manager: &TokenizerManager,
+    ) -> Result<(), SchemaBuildError> {
+        let fields = match self.fields.as_ref() {
+            Some(fields) => fields,
+            None => return Ok(()),
+        };
+
+        for (name, field) in fields {
+            if let Field::Text(opts) = field {
+                let analyzer = opts.analyzer.build().map_err(|reason| {
+                    SchemaBuildError::UnsupportedOptions {
+                        field: name.clone(),
+                        reason,
+                    }
+                })?;
+                manager.register(&tokenizer_name(name), analyzer);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The name of the tokenizer registered for the given text field.
+pub fn tokenizer_name(field: &str) -> String {
+    format!("lnx-text-{field}")
+}
+
+/// A builder which converts lnx fields into a tantivy schema.
+///
+/// The [RESERVED_DOCUMENT_ID_FIELD] is always added to the schema.
+pub struct TantivySchemaBuilder {
+    inner: SchemaBuilder,
+    field_names: BTreeSet<String>,
+}
+
+impl Default for TantivySchemaBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TantivySchemaBuilder {
+    /// Creates a new builder containing only the reserved fields.
+    pub fn new() -> Self {
+        let mut inner = TantivySchema::builder();
+        inner.add_u64_field(RESERVED_DOCUMENT_ID_FIELD, INDEXED | STORED | FAST);
+
+        Self {
+            inner,
+            field_names: BTreeSet::new(),
+        }
+    }
+
+    /// Adds a field to the schema, returning the tantivy field handle.
+    pub fn add_field(
+        &mut self,
+        name: &str,
+        field: &Field,
+    ) -> Result<tantivy::schema::Field, SchemaBuildError> {
+        if name == RESERVED_DOCUMENT_ID_FIELD {
+            return Err(SchemaBuildError::ReservedField(name.to_string()));
+        }
+
+        if self.field_names.contains(name) {
+            return Err(SchemaBuildError::DuplicateField(name.to_string()));
+        }
+
+        let unsupported = |reason| SchemaBuildError::UnsupportedOptions {
+            field: name.to_string(),
+            reason,
+        };
+
+        let field = match field {
+            Field::Text(opts) => self
+                .inner
+                .add_text_field(name, text_options(name, opts).map_err(unsupported)?),
+            Field::RawStr(opts) => {
+                self.inner.add_text_field(name, raw_str_options(opts))
+            },
+            Field::Facet(opts) => self.inner.add_facet_field(name, facet_options(opts)),
+            Field::U64(opts) => self
+                .inner
+                .add_u64_field(name, numeric_options(opts).map_err(unsupported)?),
+            Field::I64(opts) => self
+                .inner
+                .add_i64_field(name, numeric_options(opts).map_err(unsupported)?),
+            Field::F64(opts) => self
+                .inner
+                .add_f64_field(name, numeric_options(opts).map_err(unsupported)?),
+            Field::Json(opts) => self.inner.add_json_field(name, json_options(opts)),
+            Field::Bytes(opts) => self
+                .inner
+                .add_bytes_field(name, bytes_options(opts).map_err(unsupported)?),
+            Field::Date(opts) => self
+                .inner
+                .add_date_field(name, date_options(opts).map_err(unsupported)?),
+            Field::IpAddr(opts) => self
+                .inner
+                .add_ip_addr_field(name, ip_addr_options(opts).map_err(unsupported)?),
+            Field::Bool(opts) => self
+                .inner
+                .add_bool_field(name, numeric_options(opts).map_err(unsupported)?),
+            Field::GeoPoint(opts) => {
+                self.inner.add_u64_field(name, geo_point_options(opts))
+            },
+            Field::Vector(opts) => self
+                .inner
+                .add_bytes_field(name, vector_options(opts).map_err(unsupported)?),
+        };
+
+        self.field_names.insert(name.to_string());
+
+        Ok(field)
+    }
+
+    /// Builds the tantivy schema.
+    pub fn build(self) -> TantivySchema {
+        self.inner.build()
+    }
+}
+
+fn text_options(
+    name: &str,
+    opts: &TextOptions,
+) -> Result<TantivyTextOptions, &'static str> {
+    // The analyzer is registered separately, but is validated here so
+    // invalid analyzers are rejected when the schema is created.
+    opts.analyzer.build()?;
+
+    let indexing = TextFieldIndexing::default()
+        .set_tokenizer(&tokenizer_name(name))
+        .set_index_option(opts.analyzer.record.into());
+
+    let options = TantivyTextOptions::default().set_indexing_options(indexing);
+    if opts.base.stored {
+        Ok(options.set_stored())
+    } else {
+        Ok(options)
+    }
+}
+
+fn raw_str_options(opts: &BaseOptions) -> TantivyTextOptions {
+    let indexing = TextFieldIndexing::default()
+        .set_tokenizer(RAW_TOKENIZER)
+        .set_index_option(IndexRecordOption::Basic);
+
+    let options = TantivyTextOptions::default().set_indexing_options(indexing);
+    if opts.stored {
+        options.set_stored()
+    } else {
+        options
+    }
+}
+
+fn facet_options(opts: &BaseOptions) -> FacetOptions {
+    // Facets are always indexed.
+    let options = FacetOptions::default();
+    if opts.stored {
+        options.set_stored()
+    } else {
+        options
+    }
+}
+
+fn json_options(opts: &BaseOptions) -> JsonObjectOptions {
+    let indexing = TextFieldIndexing::default()
+        .set_tokenizer(DEFAULT_TOKENIZER)
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+
+    let options = JsonObjectOptions::default().set_indexing_options(indexing);
+    if opts.stored {
+        options.set_stored()
+    } else {
+        options
+    }
+}
+
+fn numeric_options(opts: &NumericFieldOptions) -> Result<NumericOptions, &'static str> {
+    validate_numeric_options(opts)?;
+
+    let mut options = NumericOptions::default();
+    if opts.base.stored {
+        options = options.set_stored();
+    }
+    if opts.indexed {
+        options = options.set_indexed();
+    }
+    if opts.field_norms {
+        options = options.set_fieldnorm();
+    }
+    if opts.fast {
+        options = options.set_fast(fast_cardinality(&opts.base));
+    }
+
+    Ok(options)
+}
+
+fn date_options(opts: &DateFieldOptions) -> Result<DateOptions, &'static str> {
+    validate_numeric_options(&opts.numeric)?;
+
+    if opts.formats.is_empty() {
+        return Err("Date fields must accept at least one of the `formats`");
+    }
+
+    for format in opts.formats.iter() {
+        if let DateFormat::Custom(description) = format {
+            time::format_description::parse(description)
+                .map_err(|_| "A custom date format is not a valid format description")?;
+        }
+    }
+
+    let numeric = &opts.numeric;
+    let mut options = DateOptions::default().set_precision(opts.precision.into());
+    if numeric.base.stored {
+        options = options.set_stored();
+    }
+    if numeric.indexed {
+        options = options.set_indexed();
+    }
+    if numeric.field_norms {
+        options = options.set_fieldnorm();
+    }
+    if numeric.fast {
+        options = options.set_fast(fast_cardinality(&numeric.base));
+    }
+
+    Ok(options)
+}
+
+fn ip_addr_options(opts: &NumericFieldOptions) -> Result<IpAddrOptions, &'static str> {
+    validate_numeric_options(opts)?;
+
+    // Ip address fields have no field norms, `field_norms` defaults to `indexed`
+    // so it is ignored rather than rejected.
+    let mut options = IpAddrOptions::default();
+    if opts.base.stored {
+        options = options.set_stored();
+    }
+    if opts.indexed {
+        options = options.set_indexed();
+    }
+    if opts.fast {
+        options = options.set_fast(fast_cardinality(&opts.base));
+    }
+
+    Ok(options)
+}
+
+/// Points are stored as a single encoded value, see `GeoPoint::encode`.
+fn geo_point_options(opts: &BaseOptions) -> NumericOptions {
+    // The latitude is in the high bits so it can be filtered with range queries.
+    let options = NumericOptions::default()
+        .set_indexed()
+        .set_fast(fast_cardinality(opts));
+    if opts.stored {
+        options.set_stored()
+    } else {
+        options
+    }
+}
+
+/// Vectors are stored as little endian `f32`s, see `encode_vector`.
+fn vector_options(opts: &VectorFieldOptions) -> Result<BytesOptions, &'static str> {
+    if opts.dims == 0 || opts.dims > MAX_VECTOR_DIMS {
+        return Err("Vector fields must have between 1 and 4096 `dims`");
+    }
+
+    if opts.base.multi {
+        return Err(
+            "Vector fields only support a single value, `multi` must be `false`",
+        );
+    }
+
+    // The fast field is read when building the nearest neighbour index
+    // and re-scoring the results of hybrid searches.
+    let options = BytesOptions::default().set_fast();
+    if opts.base.stored {
+        Ok(options.set_stored())
+    } else {
+        Ok(options)
+    }
+}
+
+fn fast_cardinality(opts: &BaseOptions) -> Cardinality {
+    if opts.multi {
+        Cardinality::MultiValues
+    } else {
+        Cardinality::SingleValue
+    }
+}
+
+fn bytes_options(opts: &NumericFieldOptions) -> Result<BytesOptions, &'static str> {
+    validate_numeric_options(opts)?;
+
+    if opts.fast && opts.base.multi {
+        return Err(
+            "`fast` bytes fields only support a single value, `multi` must be `false`",
+        );
+    }
+
+    let mut options = BytesOptions::default();
+    if opts.base.stored {
+        options = options.set_stored();
+    }
+    if opts.indexed {
+        options = options.set_indexed();
+    }
+    if opts.field_norms {
+        options = options.set_fieldnorms();
+    }
+    if opts.fast {
+        options = options.set_fast();
+    }
+
+    Ok(options)
+}
+
+fn validate_numeric_options(opts: &NumericFieldOptions) -> Result<(), &'static str> {
+    if opts.field_norms && !opts.indexed {
+        return Err("`field_norms` requires the field to be `indexed`");
+    }
+
+    if !(opts.base.stored || opts.indexed || opts.fast) {
+        return Err("The field must be at least one of `stored`, `indexed` or `fast`");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema_from_fields(fields: serde_json::Value) -> Schema {
+        serde_json::from_value(json!({
+            "name": "test-index",
+            "fields": fields,
+        }))
+        .expect("Deserialize schema")
+    }
+
+    #[test]
+    fn test_build_tantivy_schema() {
+        let schema = schema_from_fields(json!({
+            "title": { "type": "text" },
+            "tag": { "type": "rawstr", "stored": false },
+            "category": { "type": "facet" },
+            "count": { "type": "u64", "indexed": true, "fast": true, "multi": true },
+            "score": { "type": "f64", "fast": true },
+            "data": { "type": "json" },
+            "blob": { "type": "bytes", "stored": true },
+            "created": { "type": "date", "fast": true, "precision": "milliseconds" },
+            "client": { "type": "ipaddr", "indexed": true },
+            "active": { "type": "bool", "indexed": true, "fast": true },
+            "location": { "type": "geopoint", "multi": true },
+            "embedding": { "type": "vector", "dims": 3, "metric": "dot" },
+        }));
+
+        let tantivy_schema = schema.tantivy_schema().expect("Build schema");
+
+        let field = tantivy_schema
+            .get_field(RESERVED_DOCUMENT_ID_FIELD)
+            .expect("Reserved field should be added");
+        let entry = tantivy_schema.get_field_entry(field);
+        assert!(entry.is_indexed() && entry.is_fast() && entry.is_stored());
+
+        let title = tantivy_schema.get_field("title").expect("Field exists");
+        let entry = tantivy_schema.get_field_entry(title);
+        assert!(entry.is_indexed() && entry.is_stored());
+
+        let tag = tantivy_schema.get_field("tag").expect("Field exists");
+        assert!(!tantivy_schema.get_field_entry(tag).is_stored());
+
+        let count = tantivy_schema.get_field("count").expect("Field exists");
+        let entry = tantivy_schema.get_field_entry(count);
+        assert!(entry.is_indexed() && entry.is_fast());
+
+        let score = tantivy_schema.get_field("score").expect("Field exists");
+        let entry = tantivy_schema.get_field_entry(score);
+        assert!(!entry.is_indexed() && entry.is_fast());
+
+        let created = tantivy_schema.get_field("created").expect("Field exists");
+        let entry = tantivy_schema.get_field_entry(created);
+        assert!(entry.is_fast() && entry.is_stored());
+
+        let client = tantivy_schema.get_field("client").expect("Field exists");
+        assert!(tantivy_schema.get_field_entry(client).is_indexed());
+
+        let active = tantivy_schema.get_field("active").expect("Field exists");
+        let entry = tantivy_schema.get_field_entry(active);
+        assert!(entry.is_indexed() && entry.is_fast());
+
+        let location = tantivy_schema.get_field("location").expect("Field exists");
+        let entry = tantivy_schema.get_field_entry(location);
+        assert!(entry.is_indexed() && entry.is_fast() && entry.is_stored());
+
+        let embedding = tantivy_schema.get_field("embedding").expect("Field exists");
+        let entry = tantivy_schema.get_field_entry(embedding);
+        assert!(!entry.is_indexed() && entry.is_fast() && entry.is_stored());
+
+        for name in ["category", "data", "blob"] {
+            assert!(
+                tantivy_schema.get_field(name).is_some(),
+                "{name} should exist"
+            );
+        }
+    }
+
+    #[test]
+    fn test_register_tokenizers() {
+        let schema = schema_from_fields(json!({
+            "title": {
+                "type": "text",
+                "analyzer": { "tokenizer": { "type": "whitespace" }, "filters": [] },
+            },
+            "tag": { "type": "rawstr" },
+        }));
+
+        let manager = TokenizerManager::default();
+        schema
+            .register_tokenizers(&manager)
+            .expect("Register tokenizers");
+        assert!(manager.get(&tokenizer_name("title")).is_some());
+        assert!(manager.get(&tokenizer_name("tag")).is_none());
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        let schema = schema_from_fields(json!({
+            "count": { "type": "u64", "field_norms": true },
+        }));
+        assert!(matches!(
+            schema.tantivy_schema(),
+            Err(SchemaBuildError::UnsupportedOptions { field, .. }) if field == "count",
+        ));
+
+        let schema = schema_from_fields(json!({
+            "blob": { "type": "bytes", "fast": true, "multi": true },
+        }));
+        assert!(matches!(
+            schema.tantivy_schema(),
+            Err(SchemaBuildError::UnsupportedOptions { field, .. }) if field == "blob",
+        ));
+
+        let schema = schema_from_fields(json!({
+            "title": {
+                "type": "text",
+                "analyzer": {
+                    "tokenizer": { "type": "ngram", "min_gram": 0, "max_gram": 2 },
+                },
+            },
+        }));
+        assert!(matches!(
+            schema.tantivy_schema(),
+            Err(SchemaBuildError::UnsupportedOptions { field, .. }) if field == "title",
+        ));
+
+        let schema = schema_from_fields(json!({
+            "created": { "type": "date", "formats": [{ "custom": "[year" }] },
+        }));
+        assert!(matches!(
+            schema.tantivy_schema(),
+            Err(SchemaBuildError::UnsupportedOptions { field, .. })
+                if field == "created",
+        ));
+
+        let schema = schema_from_fields(json!({
+            "client": { "type": "ipaddr", "field_norms": true },
+        }));
+        assert!(matches!(
+            schema.tantivy_schema(),
+            Err(SchemaBuildError::UnsupportedOptions { field, .. }) if field == "client",
+        ));
+
+        for embedding in [
+            json!({ "type": "vector", "dims": 0 }),
+            json!({ "type": "vector", "dims": 4097 }),
+            json!({ "type": "vector", "dims": 3, "multi": true }),
+        ] {
+            let schema = schema_from_fields(json!({ "embedding": embedding }));
+            assert!(matches!(
+                schema.tantivy_schema(),
+                Err(SchemaBuildError::UnsupportedOptions { field, .. })
+                    if field == "embedding",
+            ));
+        }
+
+        let schema = schema_from_fields(json!(null));
+        assert!(matches!(
+            schema.tantivy_schema(),
+            Err(SchemaBuildError::SchemaLess)
+        ));
+    }
+
+    #[test]
+    fn test_reserved_field() {
+        let mut builder = TantivySchemaBuilder::new();
+        let field = Field::U64(
+            serde_json::from_value(json!({ "indexed": true })).expect("Deserialize"),
+        );
+
+        assert!(matches!(
+            builder.add_field(RESERVED_DOCUMENT_ID_FIELD, &field),
+            Err(SchemaBuildError::ReservedField(_)),
+        ));
+
+        builder.add_field("count", &field).expect("Add field");
+        assert!(matches!(
+            builder.add_field("count", &field),
+            Err(SchemaBuildError::DuplicateField(_)),
+        ));
+    }
+}
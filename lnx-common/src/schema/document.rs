@@ -0,0 +1,1004 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv6Addr};
+
+use serde_json::{Map, Number, Value};
+use tantivy::schema::Facet;
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+
+use super::vector::parse_vector;
+use super::{
+    ComputedValue,
+    DateFieldOptions,
+    DateFormat,
+    DynamicMapping,
+    Field,
+    GeoPoint,
+    MappedDocument,
+    Schema,
+    RESERVED_DOCUMENT_ID_FIELD,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single value of a validated document field.
+pub enum FieldValue {
+    /// A value of a `text` or `rawstr` field.
+    Text(String),
+    /// A value of a `facet` field.
+    Facet(String),
+    /// A value of a `u64` field.
+    U64(u64),
+    /// A value of a `i64` field.
+    I64(i64),
+    /// A value of a `f64` field.
+    F64(f64),
+    /// A value of a `json` field.
+    Json(Map<String, Value>),
+    /// A decoded value of a `bytes` field.
+    Bytes(Vec<u8>),
+    /// A value of a `date` field in UTC.
+    Date(OffsetDateTime),
+    /// A value of a `ipaddr` field.
+    ///
+    /// IPv4 addresses are converted to IPv4-mapped IPv6 addresses.
+    IpAddr(Ipv6Addr),
+    /// A value of a `bool` field.
+    Bool(bool),
+    /// A value of a `geopoint` field.
+    GeoPoint(GeoPoint),
+    /// A value of a `vector` field.
+    Vector(Vec<f32>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// A document which has been validated and coerced to match the schema.
+pub struct ValidatedDocument {
+    /// The values of each field.
+    ///
+    /// Fields which are not required and were not provided have no values.
+    pub fields: BTreeMap<String, Vec<FieldValue>>,
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// The reason a field of a document is invalid.
+pub enum FieldError {
+    #[error("The field is required but no value was provided")]
+    Missing,
+    #[error("The field is not defined in the schema")]
+    Unknown,
+    #[error("The field name is reserved for internal use")]
+    Reserved,
+    #[error("The field is computed from other fields and cannot be provided")]
+    Computed,
+    #[error("The field only supports a single value but {count} values were provided")]
+    MultipleValues {
+        /// The number of values provided.
+        count: usize,
+    },
+    #[error("Expected {expected} but got {found}")]
+    InvalidType {
+        /// The type of value the field expects.
+        expected: &'static str,
+        /// The type of value that was provided.
+        found: &'static str,
+    },
+    #[error("Invalid {expected}: {reason}")]
+    InvalidValue {
+        /// The type of value the field expects.
+        expected: &'static str,
+        /// Why the value could not be used.
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, thiserror::Error, serde::Serialize)]
+#[error("The document has {} invalid field(s)", .fields.len())]
+/// A report of every invalid field in a document.
+pub struct DocumentValidationError {
+    /// The errors of each invalid field.
+    pub fields: BTreeMap<String, FieldError>,
+}
+
+#[derive(Debug, Clone, thiserror::Error, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// The reason a document could not be validated against the schema.
+pub enum DocumentError {
+    #[error(
+        "The index is schema-less, documents must be mapped to the index's \
+         dynamic mapping"
+    )]
+    SchemaLess,
+    #[error(transparent)]
+    Invalid(#[from] DocumentValidationError),
+}
+
+impl Schema {
+    /// Validates the given document against the schema.
+    ///
+    /// Missing values are replaced with the field's default and computed fields
+    /// are evaluated before the document is validated.
+    ///
+    /// Values are coerced to the type of their field where possible, for example,
+    /// numeric strings are accepted by numeric fields and `bytes` fields
+    /// expect base64 encoded strings. Unknown fields are rejected.
+    ///
+    /// Schema-less indexes are rejected with [DocumentError::SchemaLess], their
+    /// documents must be mapped to the index's persisted mapping with
+    /// [Schema::map_document].
+    pub fn validate_document(
+        &self,
+        document: Map<String, Value>,
+    ) -> Result<ValidatedDocument, DocumentError> {
+        if self.fields.is_none() {
+            return Err(DocumentError::SchemaLess);
+        }
+
+        let mapped = self.map_document(&mut DynamicMapping::new(), document)?;
+        Ok(mapped.document)
+    }
+
+    /// Validates the given document against the schema, or maps it to the
+    /// `mapping` if the index is schema-less.
+    ///
+    /// Any fields inferred from the document are added to the `mapping`,
+    /// which must then be persisted with the index. The `mapping` is left
+    /// unchanged for indexes with defined fields.
+    pub fn map_document(
+        &self,
+        mapping: &mut DynamicMapping,
+        mut document: Map<String, Value>,
+    ) -> Result<MappedDocument, DocumentValidationError> {
+        let fields = match self.fields.as_ref() {
+            Some(fields) => fields,
+            None => return mapping.map_document(document),
+        };
+
+        let mut validated = ValidatedDocument::default();
+        let mut errors = BTreeMap::new();
+
+        apply_defaults_and_computed(fields, &mut document, &mut errors);
+
+        for (name, field) in fields {
+            if errors.contains_key(name) {
+                continue;
+            }
+
+            let value = document.remove(name).unwrap_or(Value::Null);
+
+            match validate_field(field, value) {
+                Ok(values) => {
+                    validated.fields.insert(name.clone(), values);
+                },
+                Err(e) => {
+                    errors.insert(name.clone(), e);
+                },
+            }
+        }
+
+        for (name, _) in document {
+            // Computed fields provided in the document were already reported.
+            if errors.contains_key(&name) {
+                continue;
+            }
+
+            let error = if name == RESERVED_DOCUMENT_ID_FIELD {
+                FieldError::Reserved
+            } else {
+                FieldError::Unknown
+            };
+            errors.insert(name, error);
+        }
+
+        finish(validated, errors).map(|document| MappedDocument {
+            document,
+            new_fields: Vec::new(),
+        })
+    }
+}
+
+/// Fills in the default values and evaluates the computed fields of the document.
+///
+/// Computed fields provided in the document are reported as errors.
+fn apply_defaults_and_computed(
+    fields: &BTreeMap<String, Field>,
+    document: &mut Map<String, Value>,
+    errors: &mut BTreeMap<String, FieldError>,
+) {
+    for (name, field) in fields {
+        let opts = field.base_options();
+        if opts.compute.is_some() {
+            if document.get(name).is_some_and(|value| !value.is_null()) {
+                errors.insert(name.clone(), FieldError::Computed);
+            }
+            continue;
+        }
+
+        if let Some(default) = opts.default.as_ref() {
+            if is_missing(document.get(name)) {
+                document.insert(name.clone(), Value::from(default));
+            }
+        }
+    }
+
+    // Computed fields can only reference fields which are not computed, so
+    // the order they are evaluated in does not matter.
+    let mut computed = Vec::new();
+    for (name, field) in fields {
+        let opts = field.base_options();
+        let compute = match opts.compute.as_ref() {
+            Some(compute) => compute,
+            None => continue,
+        };
+
+        let mut value = compute_value(compute, document);
+        if is_missing(Some(&value)) {
+            if let Some(default) = opts.default.as_ref() {
+                value = Value::from(default);
+            }
+        }
+        computed.push((name.clone(), value));
+    }
+
+    document.extend(computed);
+}
+
+/// Evaluates the expression against the uploaded values of the document.
+fn compute_value(compute: &ComputedValue, document: &Map<String, Value>) -> Value {
+    match compute {
+        ComputedValue::CopyFrom { fields } => {
+            let mut values = fields
+                .iter()
+                .flat_map(|name| field_values(document, name))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if values.len() > 1 {
+                Value::Array(values)
+            } else {
+                values.pop().unwrap_or(Value::Null)
+            }
+        },
+        ComputedValue::Concat { fields, separator } => {
+            let parts = fields
+                .iter()
+                .flat_map(|name| field_values(document, name))
+                .map(|value| match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>();
+
+            if parts.is_empty() {
+                Value::Null
+            } else {
+                Value::String(parts.join(separator))
+            }
+        },
+        ComputedValue::Lowercase { field } => {
+            let lowercase = |value: &Value| match value {
+                Value::String(s) => Value::String(s.to_lowercase()),
+                other => other.clone(),
+            };
+
+            match document.get(field) {
+                None => Value::Null,
+                Some(Value::Array(values)) => {
+                    Value::Array(values.iter().map(lowercase).collect())
+                },
+                Some(value) => lowercase(value),
+            }
+        },
+    }
+}
+
+/// Every non-null value of the field, arrays are flattened.
+fn field_values<'a>(document: &'a Map<String, Value>, name: &str) -> Vec<&'a Value> {
+    match document.get(name) {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(values)) => {
+            values.iter().filter(|value| !value.is_null()).collect()
+        },
+        Some(value) => vec![value],
+    }
+}
+
+/// Is the value missing, `null` or an empty array.
+fn is_missing(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => true,
+        Some(Value::Array(values)) => values.is_empty(),
+        Some(_) => false,
+    }
+}
+
+fn finish(
+    validated: ValidatedDocument,
+    errors: BTreeMap<String, FieldError>,
+) -> Result<ValidatedDocument, DocumentValidationError> {
+    if errors.is_empty() {
+        Ok(validated)
+    } else {
+        Err(DocumentValidationError { fields: errors })
+    }
+}
+
+fn validate_field(field: &Field, value: Value) -> Result<Vec<FieldValue>, FieldError> {
+    let values = match value {
+        Value::Null => Vec::new(),
+        // Points and vectors are arrays themselves rather than multiple values.
+        Value::Array(values) if is_array_value(field, &values) => {
+            vec![Value::Array(values)]
+        },
+        Value::Array(values) => {
+            // A single value wrapped in an array is still a single value.
+            if !field.is_multi() && values.len() > 1 {
+                return Err(FieldError::MultipleValues {
+                    count: values.len(),
+                });
+            }
+            values
+        },
+        value => vec![value],
+    };
+
+    if values.is_empty() && field.is_required() {
+        return Err(FieldError::Missing);
+    }
+
+    values
+        .into_iter()
+        .map(|value| coerce_value(field, value))
+        .collect()
+}
+
+pub(super) fn coerce_value(
+    field: &Field,
+    value: Value,
+) -> Result<FieldValue, FieldError> {
+    match field {
+        Field::Text(_) | Field::RawStr(_) => coerce_string(value).map(FieldValue::Text),
+        Field::Facet(_) => {
+            let facet = coerce_string(value)?;
+            Facet::from_text(&facet).map_err(|e| FieldError::InvalidValue {
+                expected: "facet",
+                reason: e.to_string(),
+            })?;
+            Ok(FieldValue::Facet(facet))
+        },
+        Field::U64(_) => coerce_u64(value).map(FieldValue::U64),
+        Field::I64(_) => coerce_i64(value).map(FieldValue::I64),
+        Field::F64(_) => coerce_f64(value).map(FieldValue::F64),
+        Field::Json(_) => match value {
+            Value::Object(object) => Ok(FieldValue::Json(object)),
+            other => Err(invalid_type("object", &other)),
+        },
+        Field::Bytes(_) => match value {
+            Value::String(s) => base64::decode(s).map(FieldValue::Bytes).map_err(|e| {
+                FieldError::InvalidValue {
+                    expected: "base64 string",
+                    reason: e.to_string(),
+                }
+            }),
+            other => Err(invalid_type("base64 string", &other)),
+        },
+        Field::Date(opts) => coerce_date(opts, value).map(FieldValue::Date),
+        Field::IpAddr(_) => coerce_ip_addr(value).map(FieldValue::IpAddr),
+        Field::Bool(_) => coerce_bool(value).map(FieldValue::Bool),
+        Field::GeoPoint(_) => GeoPoint::from_json(&value)
+            .map(FieldValue::GeoPoint)
+            .map_err(|reason| FieldError::InvalidValue {
+                expected: "geo point",
+                reason,
+            }),
+        Field::Vector(opts) => parse_vector(&value, opts.dims, opts.metric)
+            .map(FieldValue::Vector)
+            .map_err(|reason| FieldError::InvalidValue {
+                expected: "vector",
+                reason,
+            }),
+    }
+}
+
+/// Is the array a single value of the field rather than multiple values.
+///
+/// This is the case for `[lon, lat]` pairs of geo points and every vector.
+fn is_array_value(field: &Field, values: &[Value]) -> bool {
+    match field {
+        Field::GeoPoint(_) => values.len() == 2 && values.iter().all(Value::is_number),
+        Field::Vector(_) => true,
+        _ => false,
+    }
+}
+
+fn coerce_string(value: Value) -> Result<String, FieldError> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(invalid_type("string", &other)),
+    }
+}
+
+fn coerce_u64(value: Value) -> Result<u64, FieldError> {
+    const EXPECTED: &str = "unsigned integer";
+
+    match value {
+        Value::Number(n) => number_as_u64(&n).ok_or_else(|| out_of_range(EXPECTED, &n)),
+        Value::String(s) => {
+            if let Ok(n) = s.trim().parse::<u64>() {
+                return Ok(n);
+            }
+
+            let timestamp =
+                parse_timestamp(&s).ok_or_else(|| not_a_number(EXPECTED, &s))?;
+            u64::try_from(timestamp).map_err(|_| FieldError::InvalidValue {
+                expected: EXPECTED,
+                reason: format!("The date {s:?} is before the unix epoch"),
+            })
+        },
+        other => Err(invalid_type(EXPECTED, &other)),
+    }
+}
+
+fn coerce_i64(value: Value) -> Result<i64, FieldError> {
+    const EXPECTED: &str = "integer";
+
+    match value {
+        Value::Number(n) => number_as_i64(&n).ok_or_else(|| out_of_range(EXPECTED, &n)),
+        Value::String(s) => {
+            if let Ok(n) = s.trim().parse::<i64>() {
+                return Ok(n);
+            }
+
+            parse_timestamp(&s).ok_or_else(|| not_a_number(EXPECTED, &s))
+        },
+        other => Err(invalid_type(EXPECTED, &other)),
+    }
+}
+
+fn coerce_f64(value: Value) -> Result<f64, FieldError> {
+    const EXPECTED: &str = "number";
+
+    let n = match value {
+        Value::Number(n) => n.as_f64().ok_or_else(|| out_of_range(EXPECTED, &n))?,
+        Value::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| not_a_number(EXPECTED, &s))?,
+        other => return Err(invalid_type(EXPECTED, &other)),
+    };
+
+    if !n.is_finite() {
+        return Err(FieldError::InvalidValue {
+            expected: EXPECTED,
+            reason: "The number must be finite".to_string(),
+        });
+    }
+
+    Ok(n)
+}
+
+fn coerce_date(
+    opts: &DateFieldOptions,
+    value: Value,
+) -> Result<OffsetDateTime, FieldError> {
+    const EXPECTED: &str = "date";
+
+    if !matches!(value, Value::String(_) | Value::Number(_)) {
+        return Err(invalid_type(EXPECTED, &value));
+    }
+
+    opts.formats
+        .iter()
+        .find_map(|format| parse_date(format, &value))
+        .ok_or_else(|| FieldError::InvalidValue {
+            expected: EXPECTED,
+            reason: format!(
+                "The value {value} does not match any of the accepted formats"
+            ),
+        })
+}
+
+/// Parses the value as a date in the given format.
+fn parse_date(format: &DateFormat, value: &Value) -> Option<OffsetDateTime> {
+    let timestamp = || match value {
+        Value::Number(n) => number_as_i64(n).map(i128::from),
+        Value::String(s) => s.trim().parse::<i128>().ok(),
+        _ => None,
+    };
+
+    match format {
+        DateFormat::Rfc3339 => {
+            OffsetDateTime::parse(value.as_str()?.trim(), &Rfc3339).ok()
+        },
+        DateFormat::Rfc2822 => {
+            OffsetDateTime::parse(value.as_str()?.trim(), &Rfc2822).ok()
+        },
+        DateFormat::Timestamp => {
+            let secs = i64::try_from(timestamp()?).ok()?;
+            OffsetDateTime::from_unix_timestamp(secs).ok()
+        },
+        DateFormat::TimestampMillis => {
+            let nanos = timestamp()?.checked_mul(1_000_000)?;
+            OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+        },
+        DateFormat::TimestampMicros => {
+            let nanos = timestamp()?.checked_mul(1_000)?;
+            OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+        },
+        DateFormat::Custom(description) => {
+            let s = value.as_str()?.trim();
+            let items = time::format_description::parse(description).ok()?;
+
+            OffsetDateTime::parse(s, &items)
+                .or_else(|_| {
+                    PrimitiveDateTime::parse(s, &items).map(|dt| dt.assume_utc())
+                })
+                .or_else(|_| Date::parse(s, &items).map(|d| d.midnight().assume_utc()))
+                .ok()
+        },
+    }
+}
+
+fn coerce_ip_addr(value: Value) -> Result<Ipv6Addr, FieldError> {
+    const EXPECTED: &str = "ip address";
+
+    let s = match value {
+        Value::String(s) => s,
+        other => return Err(invalid_type(EXPECTED, &other)),
+    };
+
+    match s.trim().parse::<IpAddr>() {
+        Ok(IpAddr::V4(addr)) => Ok(addr.to_ipv6_mapped()),
+        Ok(IpAddr::V6(addr)) => Ok(addr),
+        Err(e) => Err(FieldError::InvalidValue {
+            expected: EXPECTED,
+            reason: e.to_string(),
+        }),
+    }
+}
+
+fn coerce_bool(value: Value) -> Result<bool, FieldError> {
+    const EXPECTED: &str = "boolean";
+
+    let invalid = |value: &dyn std::fmt::Display| FieldError::InvalidValue {
+        expected: EXPECTED,
+        reason: format!("The value {value} is not `true`, `false`, `1` or `0`"),
+    };
+
+    match value {
+        Value::Bool(b) => Ok(b),
+        Value::Number(n) => match n.as_u64() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(invalid(&n)),
+        },
+        Value::String(s) => match s.trim() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(invalid(&format_args!("{s:?}"))),
+        },
+        other => Err(invalid_type(EXPECTED, &other)),
+    }
+}
+
+/// Converts the number to a `u64` if it is a whole number within range.
+fn number_as_u64(n: &Number) -> Option<u64> {
+    if let Some(n) = n.as_u64() {
+        return Some(n);
+    }
+
+    let n = n.as_f64()?;
+    // `u64::MAX as f64` rounds up to 2^64, which is already out of range.
+    (n.fract() == 0.0 && n >= 0.0 && n < u64::MAX as f64).then_some(n as u64)
+}
+
+/// Converts the number to a `i64` if it is a whole number within range.
+fn number_as_i64(n: &Number) -> Option<i64> {
+    if let Some(n) = n.as_i64() {
+        return Some(n);
+    }
+
+    let n = n.as_f64()?;
+    // `i64::MAX as f64` rounds up to 2^63, which is already out of range.
+    (n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64).then_some(n as i64)
+}
+
+/// Parses a RFC3339 formatted date into a unix timestamp in seconds.
+fn parse_timestamp(s: &str) -> Option<i64> {
+    OffsetDateTime::parse(s.trim(), &Rfc3339)
+        .ok()
+        .map(|dt| dt.unix_timestamp())
+}
+
+fn invalid_type(expected: &'static str, value: &Value) -> FieldError {
+    let found = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    };
+
+    FieldError::InvalidType { expected, found }
+}
+
+fn out_of_range(expected: &'static str, n: &Number) -> FieldError {
+    FieldError::InvalidValue {
+        expected,
+        reason: format!("The number {n} is out of range"),
+    }
+}
+
+fn not_a_number(expected: &'static str, s: &str) -> FieldError {
+    FieldError::InvalidValue {
+        expected,
+        reason: format!("The string {s:?} is not a valid number or RFC3339 date"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schema::DYNAMIC_CATCH_ALL_FIELD;
+
+    fn test_schema(fields: Value) -> Schema {
+        serde_json::from_value(json!({
+            "name": "test-index",
+            "fields": fields,
+        }))
+        .expect("Deserialize schema")
+    }
+
+    fn document(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(object) => object,
+            _ => panic!("Document must be an object"),
+        }
+    }
+
+    fn invalid(
+        result: Result<ValidatedDocument, DocumentError>,
+    ) -> DocumentValidationError {
+        match result {
+            Err(DocumentError::Invalid(err)) => err,
+            other => panic!("Document should be invalid, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_validate_and_coerce() {
+        let schema = test_schema(json!({
+            "title": { "type": "text", "required": true },
+            "tags": { "type": "rawstr", "multi": true },
+            "count": { "type": "u64" },
+            "created": { "type": "i64" },
+            "score": { "type": "f64" },
+            "data": { "type": "bytes" },
+            "category": { "type": "facet" },
+        }));
+
+        let validated = schema
+            .validate_document(document(json!({
+                "title": "Hello, world",
+                "tags": "single",
+                "count": "42",
+                "created": "1970-01-01T00:01:00Z",
+                "score": 1,
+                "data": "aGVsbG8=",
+            })))
+            .expect("Document should be valid");
+
+        let expected = [
+            ("title", vec![FieldValue::Text("Hello, world".to_string())]),
+            ("tags", vec![FieldValue::Text("single".to_string())]),
+            ("count", vec![FieldValue::U64(42)]),
+            ("created", vec![FieldValue::I64(60)]),
+            ("score", vec![FieldValue::F64(1.0)]),
+            ("data", vec![FieldValue::Bytes(b"hello".to_vec())]),
+            ("category", vec![]),
+        ];
+        for (name, values) in expected {
+            assert_eq!(validated.fields[name], values, "Field {name} should match");
+        }
+    }
+
+    #[test]
+    fn test_field_errors() {
+        let schema = test_schema(json!({
+            "title": { "type": "text", "required": true },
+            "count": { "type": "u64" },
+            "score": { "type": "f64" },
+            "data": { "type": "json" },
+            "category": { "type": "facet" },
+        }));
+
+        let err = invalid(schema.validate_document(document(json!({
+            "count": [1, 2],
+            "score": "abc",
+            "data": "not an object",
+            "category": "no-leading-slash",
+            "other": 1,
+            RESERVED_DOCUMENT_ID_FIELD: 1,
+        }))));
+
+        assert_eq!(err.fields["title"], FieldError::Missing);
+        assert_eq!(err.fields["count"], FieldError::MultipleValues { count: 2 });
+        assert!(matches!(
+            err.fields["score"],
+            FieldError::InvalidValue { .. }
+        ));
+        assert_eq!(
+            err.fields["data"],
+            FieldError::InvalidType {
+                expected: "object",
+                found: "string",
+            },
+        );
+        assert!(matches!(
+            err.fields["category"],
+            FieldError::InvalidValue { .. }
+        ));
+        assert_eq!(err.fields["other"], FieldError::Unknown);
+        assert_eq!(err.fields[RESERVED_DOCUMENT_ID_FIELD], FieldError::Reserved);
+    }
+
+    #[test]
+    fn test_number_ranges() {
+        let float = |n: f64| Number::from_f64(n).expect("Finite number");
+
+        assert_eq!(number_as_u64(&float(12.0)), Some(12));
+        assert_eq!(number_as_u64(&float(1.5)), None);
+        assert_eq!(number_as_u64(&float(-1.0)), None);
+        assert_eq!(number_as_u64(&float(18446744073709551616.0)), None);
+
+        assert_eq!(number_as_i64(&float(-12.0)), Some(-12));
+        assert_eq!(number_as_i64(&float(9223372036854775808.0)), None);
+        assert_eq!(
+            number_as_i64(&float(-9223372036854775808.0)),
+            Some(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn test_date_ip_and_bool_fields() {
+        let schema = test_schema(json!({
+            "created": { "type": "date", "multi": true },
+            "updated": {
+                "type": "date",
+                "formats": ["timestamp_millis", { "custom": "[year]-[month]-[day]" }],
+            },
+            "client": { "type": "ipaddr", "multi": true },
+            "active": { "type": "bool", "multi": true },
+        }));
+
+        let validated = schema
+            .validate_document(document(json!({
+                "created": ["1970-01-01T00:01:00Z", 120, "180"],
+                "updated": [1500],
+                "client": ["127.0.0.1", "::1"],
+                "active": [true, "false", 1],
+            })))
+            .expect("Document should be valid");
+
+        let at = |secs| OffsetDateTime::from_unix_timestamp(secs).unwrap();
+        assert_eq!(
+            validated.fields["created"],
+            vec![
+                FieldValue::Date(at(60)),
+                FieldValue::Date(at(120)),
+                FieldValue::Date(at(180)),
+            ],
+        );
+        assert_eq!(
+            validated.fields["updated"],
+            vec![FieldValue::Date(
+                OffsetDateTime::from_unix_timestamp_nanos(1_500_000_000).unwrap()
+            )],
+        );
+        assert_eq!(
+            validated.fields["client"],
+            vec![
+                FieldValue::IpAddr("::ffff:127.0.0.1".parse().unwrap()),
+                FieldValue::IpAddr(Ipv6Addr::LOCALHOST),
+            ],
+        );
+        assert_eq!(
+            validated.fields["active"],
+            vec![
+                FieldValue::Bool(true),
+                FieldValue::Bool(false),
+                FieldValue::Bool(true),
+            ],
+        );
+
+        let validated = schema
+            .validate_document(document(json!({ "updated": "2022-01-01" })))
+            .expect("Document should be valid");
+        assert_eq!(
+            validated.fields["updated"],
+            vec![FieldValue::Date(at(1_640_995_200))],
+        );
+
+        let err = invalid(schema.validate_document(document(json!({
+            "created": "yesterday",
+            "updated": "2022-01-01T00:00:00Z",
+            "client": "localhost",
+            "active": 2,
+        }))));
+        for name in ["created", "updated", "client", "active"] {
+            assert!(
+                matches!(err.fields[name], FieldError::InvalidValue { .. }),
+                "Field {name} should be invalid",
+            );
+        }
+    }
+
+    #[test]
+    fn test_defaults_and_computed_fields() {
+        let schema = test_schema(json!({
+            "title": { "type": "text", "default": "Untitled" },
+            "body": { "type": "text" },
+            "count": { "type": "u64", "required": true, "default": 0 },
+            "tag": { "type": "rawstr", "multi": true },
+            "all": {
+                "type": "text",
+                "multi": true,
+                "compute": { "type": "copy_from", "fields": ["title", "body"] },
+            },
+            "summary": {
+                "type": "text",
+                "compute": {
+                    "type": "concat",
+                    "fields": ["title", "count"],
+                    "separator": " - ",
+                },
+            },
+            "tag_lower": {
+                "type": "rawstr",
+                "multi": true,
+                "compute": { "type": "lowercase", "field": "tag" },
+                "default": "none",
+            },
+        }));
+
+        let validated = schema
+            .validate_document(document(json!({
+                "body": "Hello",
+                "tag": ["Rust", "Search"],
+            })))
+            .expect("Document should be valid");
+
+        let text = |s: &str| FieldValue::Text(s.to_string());
+        assert_eq!(validated.fields["title"], vec![text("Untitled")]);
+        assert_eq!(validated.fields["count"], vec![FieldValue::U64(0)]);
+        assert_eq!(
+            validated.fields["all"],
+            vec![text("Untitled"), text("Hello")]
+        );
+        assert_eq!(validated.fields["summary"], vec![text("Untitled - 0")]);
+        assert_eq!(
+            validated.fields["tag_lower"],
+            vec![text("rust"), text("search")],
+        );
+
+        let validated = schema
+            .validate_document(document(json!({ "title": "Title", "count": 2 })))
+            .expect("Document should be valid");
+        assert_eq!(validated.fields["all"], vec![text("Title")]);
+        assert_eq!(validated.fields["tag_lower"], vec![text("none")]);
+
+        let err =
+            invalid(schema.validate_document(document(json!({ "all": "provided" }))));
+        assert_eq!(err.fields["all"], FieldError::Computed);
+    }
+
+    #[test]
+    fn test_geo_point_fields() {
+        let schema = test_schema(json!({
+            "location": { "type": "geopoint" },
+            "stores": { "type": "geopoint", "multi": true },
+        }));
+
+        let validated = schema
+            .validate_document(document(json!({
+                "location": [-0.12, 51.5],
+                "stores": [{ "lat": 51.5, "lon": -0.12 }, "51.5,-0.12", [-0.12, 51.5]],
+            })))
+            .expect("Document should be valid");
+
+        let point = FieldValue::GeoPoint(GeoPoint::new(51.5, -0.12).unwrap());
+        assert_eq!(validated.fields["location"], vec![point.clone()]);
+        assert_eq!(validated.fields["stores"], vec![point; 3]);
+
+        let err = invalid(schema.validate_document(document(
+            json!({ "location": { "lat": 100.0, "lon": 0.0 } }),
+        )));
+        assert!(matches!(
+            err.fields["location"],
+            FieldError::InvalidValue { .. }
+        ));
+    }
+
+    #[test]
+    fn test_vector_fields() {
+        let schema = test_schema(json!({
+            "embedding": { "type": "vector", "dims": 3 },
+        }));
+
+        let validated = schema
+            .validate_document(document(json!({ "embedding": [0.5, 1, -2] })))
+            .expect("Document should be valid");
+        assert_eq!(
+            validated.fields["embedding"],
+            vec![FieldValue::Vector(vec![0.5, 1.0, -2.0])],
+        );
+
+        for embedding in [json!([1, 2]), json!([0, 0, 0]), json!(["a", 1, 2])] {
+            let err = invalid(
+                schema.validate_document(document(json!({ "embedding": embedding }))),
+            );
+            assert!(matches!(
+                err.fields["embedding"],
+                FieldError::InvalidValue { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_schema_less() {
+        let schema = test_schema(json!(null));
+        let mut mapping = DynamicMapping::new();
+
+        let mapped = schema
+            .map_document(
+                &mut mapping,
+                document(json!({
+                    "anything": "goes",
+                    "numbers": [1, 2],
+                })),
+            )
+            .expect("Document should be valid");
+
+        assert_eq!(mapped.new_fields, ["anything", "numbers"]);
+        assert_eq!(
+            mapped.document.fields["numbers"],
+            vec![FieldValue::I64(1), FieldValue::I64(2)],
+        );
+
+        let mapped = schema
+            .map_document(&mut mapping, document(json!({ "numbers": "three" })))
+            .expect("Document should be valid");
+        assert!(mapped.new_fields.is_empty());
+        assert!(mapped.document.fields.contains_key(DYNAMIC_CATCH_ALL_FIELD));
+
+        let err = schema
+            .map_document(
+                &mut mapping,
+                document(json!({ RESERVED_DOCUMENT_ID_FIELD: 1 })),
+            )
+            .expect_err("Document should be invalid");
+        assert_eq!(err.fields[RESERVED_DOCUMENT_ID_FIELD], FieldError::Reserved);
+
+        let err = schema
+            .validate_document(document(json!({ "anything": "goes" })))
+            .expect_err("Schema-less documents should require a mapping");
+        assert!(matches!(err, DocumentError::SchemaLess));
+    }
+
+    #[test]
+    fn test_typed_schema_ignores_mapping() {
+        let schema = test_schema(json!({ "title": { "type": "text" } }));
+        let mut mapping = DynamicMapping::new();
+
+        let mapped = schema
+            .map_document(&mut mapping, document(json!({ "title": "Hello" })))
+            .expect("Document should be valid");
+        assert!(mapped.new_fields.is_empty());
+        assert!(mapping.fields().is_empty());
+    }
+}
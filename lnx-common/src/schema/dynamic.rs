@@ -0,0 +1,454 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+use tantivy::schema::Schema as TantivySchema;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use super::document::coerce_value;
+use super::validators::is_valid_field_name;
+use super::{
+    AnalyzerOptions,
+    BaseOptions,
+    DateFieldOptions,
+    DateFormat,
+    DatePrecision,
+    DocumentValidationError,
+    Field,
+    FieldError,
+    FieldValue,
+    NumericFieldOptions,
+    SchemaBuildError,
+    TantivySchemaBuilder,
+    TextOptions,
+    ValidatedDocument,
+    DYNAMIC_CATCH_ALL_FIELD,
+    RESERVED_DOCUMENT_ID_FIELD,
+};
+use crate::CorruptedData;
+
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+/// The fields of a schema-less index, inferred from the uploaded documents.
+///
+/// The mapping only ever grows, once a field has been inferred its type is fixed
+/// and any later values which do not match the type are stored in the
+/// [DYNAMIC_CATCH_ALL_FIELD] instead.
+///
+/// The mapping must be persisted as replicated index metadata under
+/// [DynamicMapping::METADATA_KEY] whenever a document adds new fields. Nodes
+/// can infer fields concurrently, so the stored mapping must be merged in with
+/// [DynamicMapping::merge] before the mapping is persisted.
+pub struct DynamicMapping {
+    fields: BTreeMap<String, Field>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A document which has been mapped to the fields of a [DynamicMapping].
+pub struct MappedDocument {
+    /// The document values of each field.
+    ///
+    /// Values which conflict with their field's type or could not be mapped
+    /// are stored as an object in the [DYNAMIC_CATCH_ALL_FIELD].
+    pub document: ValidatedDocument,
+    /// The fields added to the mapping by this document.
+    pub new_fields: Vec<String>,
+}
+
+impl DynamicMapping {
+    /// The index metadata key the mapping is persisted under.
+    pub const METADATA_KEY: &'static str = "dynamic-mapping";
+
+    /// Deserializes the mapping from a given buffer.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CorruptedData> {
+        serde_json::from_slice(bytes).map_err(|_| CorruptedData)
+    }
+
+    /// Serializes the mapping to a buffer.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CorruptedData> {
+        serde_json::to_vec(self).map_err(|_| CorruptedData)
+    }
+
+    /// Creates a new mapping without any fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The fields which have been inferred so far.
+    pub fn fields(&self) -> &BTreeMap<String, Field> {
+        &self.fields
+    }
+
+    /// Merges the fields inferred by another node into the mapping.
+    ///
+    /// If both mappings inferred a different type for the same field, the type
+    /// with the lowest serialized form is kept, so every node converges on the
+    /// same mapping regardless of the order mappings are merged in. Documents
+    /// which were already mapped to the replaced type must be re-mapped.
+    ///
+    /// Returns the fields whose type was replaced by the other mapping.
+    pub fn merge(&mut self, other: &DynamicMapping) -> Vec<String> {
+        let mut replaced = Vec::new();
+        for (name, field) in other.fields.iter() {
+            match self.fields.get(name) {
+                None => {
+                    self.fields.insert(name.clone(), field.clone());
+                },
+                Some(existing) if sort_key(field) < sort_key(existing) => {
+                    self.fields.insert(name.clone(), field.clone());
+                    replaced.push(name.clone());
+                },
+                Some(_) => {},
+            }
+        }
+
+        replaced
+    }
+
+    /// Maps the document to the inferred fields, adding a field for any
+    /// unseen values with an inferable type.
+    ///
+    /// Values of `null` and empty arrays are ignored, and only the reserved
+    /// field names are rejected.
+    pub fn map_document(
+        &mut self,
+        document: Map<String, Value>,
+    ) -> Result<MappedDocument, DocumentValidationError> {
+        // Documents are checked before being mapped so rejected documents never
+        // add fields to the mapping.
+        let errors = document
+            .keys()
+            .filter(|name| {
+                *name == RESERVED_DOCUMENT_ID_FIELD || *name == DYNAMIC_CATCH_ALL_FIELD
+            })
+            .map(|name| (name.clone(), FieldError::Reserved))
+            .collect::<BTreeMap<_, _>>();
+        if !errors.is_empty() {
+            return Err(DocumentValidationError { fields: errors });
+        }
+
+        let mut mapped = ValidatedDocument::default();
+        let mut new_fields = Vec::new();
+        let mut catch_all = Map::new();
+
+        for (name, value) in document {
+            let values = match value {
+                Value::Null => continue,
+                Value::Array(values) => values,
+                value => vec![value],
+            };
+
+            let first = match values.iter().find(|value| !value.is_null()) {
+                Some(first) => first,
+                None => continue,
+            };
+
+            if !self.fields.contains_key(&name) {
+                match infer_field(first) {
+                    Some(field) if is_valid_field_name(&name) => {
+                        self.fields.insert(name.clone(), field);
+                        new_fields.push(name.clone());
+                    },
+                    _ => {
+                        catch_all.insert(name, Value::Array(values));
+                        continue;
+                    },
+                }
+            }
+
+            let field = &self.fields[&name];
+            let mut field_values = Vec::with_capacity(values.len());
+            let mut conflicts = Vec::new();
+            for value in values {
+                if value.is_null() {
+                    continue;
+                }
+
+                match map_value(field, &value) {
+                    Some(value) => field_values.push(value),
+                    None => conflicts.push(value),
+                }
+            }
+
+            if !conflicts.is_empty() {
+                catch_all.insert(name.clone(), Value::Array(conflicts));
+            }
+            if !field_values.is_empty() {
+                mapped.fields.insert(name, field_values);
+            }
+        }
+
+        if !catch_all.is_empty() {
+            mapped.fields.insert(
+                DYNAMIC_CATCH_ALL_FIELD.to_string(),
+                vec![FieldValue::Json(catch_all)],
+            );
+        }
+
+        Ok(MappedDocument {
+            document: mapped,
+            new_fields,
+        })
+    }
+
+    /// Builds the tantivy schema containing the inferred fields and
+    /// the [DYNAMIC_CATCH_ALL_FIELD].
+    pub fn tantivy_schema(&self) -> Result<TantivySchema, SchemaBuildError> {
+        let mut builder = TantivySchemaBuilder::new();
+        for (name, field) in self.fields.iter() {
+            builder.add_field(name, field)?;
+        }
+
+        builder.add_field(
+            DYNAMIC_CATCH_ALL_FIELD,
+            &Field::Json(dynamic_base_options()),
+        )?;
+
+        Ok(builder.build())
+    }
+}
+
+/// The key used to pick between conflicting types of a field when
+/// merging mappings.
+fn sort_key(field: &Field) -> Vec<u8> {
+    serde_json::to_vec(field).expect("Serializing a field cannot fail")
+}
+
+/// Infers the type of field from the value.
+///
+/// Returns `None` if the type of the value cannot be mapped to a field.
+fn infer_field(value: &Value) -> Option<Field> {
+    let field = match value {
+        Value::String(s) => {
+            if OffsetDateTime::parse(s, &Rfc3339).is_ok() {
+                Field::Date(DateFieldOptions {
+                    formats: vec![DateFormat::Rfc3339, DateFormat::Timestamp],
+                    precision: DatePrecision::default(),
+                    numeric: dynamic_numeric_options(),
+                })
+            } else {
+                Field::Text(TextOptions {
+                    base: dynamic_base_options(),
+                    analyzer: AnalyzerOptions::default(),
+                })
+            }
+        },
+        Value::Number(n) if n.is_i64() => Field::I64(dynamic_numeric_options()),
+        Value::Number(n) if n.is_u64() => Field::U64(dynamic_numeric_options()),
+        Value::Number(_) => Field::F64(dynamic_numeric_options()),
+        Value::Bool(_) => Field::Bool(dynamic_numeric_options()),
+        Value::Object(_) => Field::Json(dynamic_base_options()),
+        Value::Null | Value::Array(_) => return None,
+    };
+
+    Some(field)
+}
+
+/// Maps the value to the type of the field.
+///
+/// Returns `None` if the value conflicts with the type of the field.
+fn map_value(field: &Field, value: &Value) -> Option<FieldValue> {
+    // Values are only coerced between compatible JSON types so, for example,
+    // numbers are not silently converted to text.
+    let compatible = match field {
+        Field::Text(_) => value.is_string(),
+        Field::Date(_) => value.is_string() || value.is_number(),
+        Field::U64(_) | Field::I64(_) | Field::F64(_) => value.is_number(),
+        Field::Bool(_) => value.is_boolean(),
+        Field::Json(_) => value.is_object(),
+        _ => false,
+    };
+
+    if !compatible {
+        return None;
+    }
+
+    coerce_value(field, value.clone()).ok()
+}
+
+/// Inferred fields support multiple values so both single values and
+/// arrays can be uploaded.
+fn dynamic_base_options() -> BaseOptions {
+    BaseOptions {
+        stored: true,
+        multi: true,
+        ..BaseOptions::default()
+    }
+}
+
+fn dynamic_numeric_options() -> NumericFieldOptions {
+    NumericFieldOptions {
+        indexed: true,
+        field_norms: false,
+        fast: false,
+        base: dynamic_base_options(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn document(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(object) => object,
+            _ => panic!("Document must be an object"),
+        }
+    }
+
+    #[test]
+    fn test_infer_fields() {
+        let mut mapping = DynamicMapping::new();
+
+        let mapped = mapping
+            .map_document(document(json!({
+                "title": "Hello, world",
+                "count": 5,
+                "score": 1.5,
+                "created": "2022-01-01T00:00:00Z",
+                "active": true,
+                "data": { "nested": [1, 2] },
+                "tags": ["a", "b"],
+                "empty": null,
+            })))
+            .expect("Map document");
+
+        assert_eq!(
+            mapped.new_fields,
+            ["active", "count", "created", "data", "score", "tags", "title"],
+        );
+        assert!(matches!(mapping.fields()["title"], Field::Text(_)));
+        assert!(matches!(mapping.fields()["count"], Field::I64(_)));
+        assert!(matches!(mapping.fields()["score"], Field::F64(_)));
+        assert!(matches!(mapping.fields()["created"], Field::Date(_)));
+        assert!(matches!(mapping.fields()["active"], Field::Bool(_)));
+        assert!(matches!(mapping.fields()["data"], Field::Json(_)));
+        assert!(!mapping.fields().contains_key("empty"));
+
+        assert_eq!(
+            mapped.document.fields["tags"],
+            vec![
+                FieldValue::Text("a".to_string()),
+                FieldValue::Text("b".to_string()),
+            ],
+        );
+        assert!(!mapped.document.fields.contains_key(DYNAMIC_CATCH_ALL_FIELD));
+
+        let schema = mapping.tantivy_schema().expect("Build schema");
+        for name in ["title", "count", "created", "data", DYNAMIC_CATCH_ALL_FIELD] {
+            assert!(schema.get_field(name).is_some(), "{name} should exist");
+        }
+    }
+
+    #[test]
+    fn test_conflicting_values() {
+        let mut mapping = DynamicMapping::new();
+        mapping
+            .map_document(document(json!({ "count": 5, "title": "Hello" })))
+            .expect("Map document");
+
+        let mapped = mapping
+            .map_document(document(json!({
+                "count": [1, "two", 3.5],
+                "title": 12,
+                "mixed": ["a", 1],
+                "nested": [[1]],
+                "bad name": "value",
+            })))
+            .expect("Map document");
+
+        assert_eq!(mapped.new_fields, ["mixed"]);
+        assert!(matches!(mapping.fields()["count"], Field::I64(_)));
+        assert_eq!(mapped.document.fields["count"], vec![FieldValue::I64(1)]);
+        assert!(!mapped.document.fields.contains_key("title"));
+
+        let expected = json!({
+            "count": ["two", 3.5],
+            "title": [12],
+            "mixed": [1],
+            "nested": [[1]],
+            "bad name": ["value"],
+        });
+        assert_eq!(
+            mapped.document.fields[DYNAMIC_CATCH_ALL_FIELD],
+            vec![FieldValue::Json(document(expected))],
+        );
+    }
+
+    #[test]
+    fn test_reserved_fields() {
+        let mut mapping = DynamicMapping::new();
+        let err = mapping
+            .map_document(document(json!({
+                RESERVED_DOCUMENT_ID_FIELD: 1,
+                DYNAMIC_CATCH_ALL_FIELD: {},
+                "title": "Hello",
+            })))
+            .expect_err("Document should be invalid");
+
+        assert_eq!(err.fields[RESERVED_DOCUMENT_ID_FIELD], FieldError::Reserved);
+        assert_eq!(err.fields[DYNAMIC_CATCH_ALL_FIELD], FieldError::Reserved);
+        assert!(mapping.fields().is_empty());
+    }
+
+    #[test]
+    fn test_mapping_bytes() {
+        let mut mapping = DynamicMapping::new();
+        mapping
+            .map_document(document(json!({ "count": 5, "title": "Hello" })))
+            .expect("Map document");
+
+        let bytes = mapping.to_bytes().expect("Serialize mapping");
+        let mut loaded =
+            DynamicMapping::from_bytes(&bytes).expect("Deserialize mapping");
+        assert_eq!(loaded, mapping);
+
+        // Types inferred before the mapping was persisted are kept.
+        let mapped = loaded
+            .map_document(document(json!({ "count": "five" })))
+            .expect("Map document");
+        assert!(mapped.new_fields.is_empty());
+        assert!(mapped.document.fields.contains_key(DYNAMIC_CATCH_ALL_FIELD));
+
+        assert!(DynamicMapping::from_bytes(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_merge_mappings() {
+        let mut first = DynamicMapping::new();
+        first
+            .map_document(document(json!({ "count": 5, "title": "Hello" })))
+            .expect("Map document");
+        let mut second = DynamicMapping::new();
+        second
+            .map_document(document(json!({ "count": "five", "active": true })))
+            .expect("Map document");
+
+        let mut merged_first = first.clone();
+        let replaced_first = merged_first.merge(&second);
+        let mut merged_second = second.clone();
+        let replaced_second = merged_second.merge(&first);
+
+        assert_eq!(merged_first, merged_second, "Mappings should converge");
+        assert_eq!(
+            merged_first.fields().keys().collect::<Vec<_>>(),
+            ["active", "count", "title"],
+        );
+        assert_eq!(replaced_first.len() + replaced_second.len(), 1);
+
+        let mut unchanged = merged_first.clone();
+        assert!(unchanged.merge(&merged_second).is_empty());
+        assert_eq!(unchanged, merged_first);
+    }
+}
@@ -1,6 +1,11 @@
+use tantivy::DatePrecision as TantivyDatePrecision;
+
+use super::{AnalyzerOptions, DistanceMetric};
+
 #[derive(
     Debug,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     rkyv::Archive,
@@ -26,10 +31,12 @@ pub enum Field {
     /// A hierarchal facet field.
     ///
     /// They are typically represented similarly to a filepath.
-    /// For instance, an e-commerce website could have a Facet for `/electronics/tv_and_video/led_tv`.
+    /// For instance, an e-commerce website could have a Facet for
+    /// `/electronics/tv_and_video/led_tv`.
     ///
     /// A document can be associated to any number of facets.
-    /// The hierarchy implicitly implies that a document belonging to a facet also belongs to the ancestor of its facet.
+    /// The hierarchy implicitly implies that a document belonging to a facet
+    /// also belongs to the ancestor of its facet.
     /// In the example above, `/electronics/tv_and_video/` and `/electronics`.
     Facet(BaseOptions),
 
@@ -44,7 +51,8 @@ pub enum Field {
 
     /// A JSON object field.
     ///
-    /// This field supports any structured JSON data providing the initial structure is a object.
+    /// This field supports any structured JSON data providing the initial
+    /// structure is a object.
     ///
     /// ✔️ Supported:
     /// ```json
@@ -73,55 +81,100 @@ pub enum Field {
     /// If returned or uploaded as a JSON object this field expects data to be submitted
     /// in the form of a `base64` encoded string and will be returned as such.
     Bytes(NumericFieldOptions),
+
+    /// A date time field.
+    ///
+    /// Dates are stored in UTC and can be uploaded in any of the field's
+    /// configured `formats`, by default either as a RFC3339 formatted string
+    /// or a unix timestamp in seconds.
+    Date(DateFieldOptions),
+
+    /// An IPv4 or IPv6 address field.
+    ///
+    /// IPv4 addresses are stored as IPv4-mapped IPv6 addresses, i.e.
+    /// `127.0.0.1` becomes `::ffff:127.0.0.1` internally.
+    IpAddr(NumericFieldOptions),
+
+    /// A boolean field.
+    Bool(NumericFieldOptions),
+
+    /// A geographic point field.
+    ///
+    /// Points are always indexed as fast fields so they can be used for distance
+    /// and bounding box filters, and sorting by distance.
+    ///
+    /// Points can be uploaded as a `{"lat": 51.5, "lon": -0.12}` object,
+    /// a `[lon, lat]` array, a `"lat,lon"` string or a geohash string.
+    GeoPoint(BaseOptions),
+
+    /// A dense vector field.
+    ///
+    /// Each document has a single vector with exactly `dims` dimensions,
+    /// uploaded as an array of numbers, i.e. `[0.12, -0.5, 0.33]`.
+    /// Vectors are stored as little endian bytes, building the nearest
+    /// neighbour index for them is not yet done when fragments are written.
+    Vector(VectorFieldOptions),
 }
 
 impl Field {
-    /// Is the field required or not.
-    pub fn is_required(&self) -> bool {
+    /// The options shared by every type of field.
+    pub fn base_options(&self) -> &BaseOptions {
         match self {
-            Field::Text(opts) => opts.base.required,
-            Field::RawStr(opts) => opts.required,
-            Field::Facet(opts) => opts.required,
-            Field::U64(opts) => opts.base.required,
-            Field::I64(opts) => opts.base.required,
-            Field::F64(opts) => opts.base.required,
-            Field::Json(opts) => opts.required,
-            Field::Bytes(opts) => opts.base.required,
+            Field::RawStr(opts)
+            | Field::Facet(opts)
+            | Field::Json(opts)
+            | Field::GeoPoint(opts) => opts,
+            Field::Text(opts) => &opts.base,
+            Field::U64(opts)
+            | Field::I64(opts)
+            | Field::F64(opts)
+            | Field::Bytes(opts)
+            | Field::IpAddr(opts)
+            | Field::Bool(opts) => &opts.base,
+            Field::Date(opts) => &opts.numeric.base,
+            Field::Vector(opts) => &opts.base,
         }
     }
 
-    /// Can the field support multiple values or just a single value.
-    pub fn is_multi(&self) -> bool {
+    /// The mutable options shared by every type of field.
+    pub fn base_options_mut(&mut self) -> &mut BaseOptions {
         match self {
-            Field::Text(opts) => opts.base.multi,
-            Field::RawStr(opts) => opts.multi,
-            Field::Facet(opts) => opts.multi,
-            Field::U64(opts) => opts.base.multi,
-            Field::I64(opts) => opts.base.multi,
-            Field::F64(opts) => opts.base.multi,
-            Field::Json(opts) => opts.multi,
-            Field::Bytes(opts) => opts.base.multi,
+            Field::RawStr(opts)
+            | Field::Facet(opts)
+            | Field::Json(opts)
+            | Field::GeoPoint(opts) => opts,
+            Field::Text(opts) => &mut opts.base,
+            Field::U64(opts)
+            | Field::I64(opts)
+            | Field::F64(opts)
+            | Field::Bytes(opts)
+            | Field::IpAddr(opts)
+            | Field::Bool(opts) => &mut opts.base,
+            Field::Date(opts) => &mut opts.numeric.base,
+            Field::Vector(opts) => &mut opts.base,
         }
     }
 
+    /// Is the field required or not.
+    pub fn is_required(&self) -> bool {
+        self.base_options().required
+    }
+
+    /// Can the field support multiple values or just a single value.
+    pub fn is_multi(&self) -> bool {
+        self.base_options().multi
+    }
+
     /// Is the field stored or not.
     pub fn is_stored(&self) -> bool {
-        match self {
-            Field::Text(opts) => opts.base.stored,
-            Field::RawStr(opts) => opts.stored,
-            Field::Facet(opts) => opts.stored,
-            Field::U64(opts) => opts.base.stored,
-            Field::I64(opts) => opts.base.stored,
-            Field::F64(opts) => opts.base.stored,
-            Field::Json(opts) => opts.stored,
-            Field::Bytes(opts) => opts.base.stored,
-        }
+        self.base_options().stored
     }
 }
 
 #[derive(
     Debug,
     Clone,
+    PartialEq,
     Default,
     serde::Serialize,
     serde::Deserialize,
@@ -149,13 +202,111 @@ pub struct BaseOptions {
     /// If the field is not mandatory they can be omitted from the uploaded
     /// document and when returned will be populated with a default value.
     ///
-    /// The default value is `null` for single value fields and `[]` for multi-value fields.
+    /// The default value is `null` for single value fields and `[]` for
+    /// multi-value fields.
     pub required: bool,
+
+    #[serde(default)]
+    /// The value used when the field is missing from an uploaded document.
+    pub default: Option<DefaultValue>,
+
+    #[serde(default)]
+    /// Compute the value of the field from other fields of the document.
+    ///
+    /// Computed fields cannot be provided in uploaded documents.
+    pub compute: Option<ComputedValue>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(untagged)]
+/// The default value of a field.
+///
+/// The value is coerced to the type of the field like any other value.
+pub enum DefaultValue {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+}
+
+impl From<&DefaultValue> for serde_json::Value {
+    fn from(value: &DefaultValue) -> Self {
+        match value {
+            DefaultValue::Bool(v) => serde_json::Value::from(*v),
+            DefaultValue::I64(v) => serde_json::Value::from(*v),
+            DefaultValue::U64(v) => serde_json::Value::from(*v),
+            DefaultValue::F64(v) => serde_json::Value::from(*v),
+            DefaultValue::String(v) => serde_json::Value::from(v.as_str()),
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// An expression computing the value of a field from other fields.
+///
+/// Expressions are evaluated against the uploaded document before
+/// it is validated, and can only reference fields which are not computed.
+pub enum ComputedValue {
+    /// Copies every value of the given fields.
+    ///
+    /// i.e. a `multi` text field copying from `title` and `description`
+    /// which can be used to search all text at once.
+    CopyFrom {
+        /// The fields to copy from.
+        fields: Vec<String>,
+    },
+
+    /// Joins the values of the given fields into a single string.
+    Concat {
+        /// The fields to join in order.
+        fields: Vec<String>,
+        #[serde(default)]
+        /// The string placed between each value.
+        separator: String,
+    },
+
+    /// Lowercases the string values of the given field.
+    Lowercase {
+        /// The field to lowercase.
+        field: String,
+    },
+}
+
+impl ComputedValue {
+    /// The fields the expression reads from.
+    pub fn source_fields(&self) -> &[String] {
+        match self {
+            ComputedValue::CopyFrom { fields } => fields,
+            ComputedValue::Concat { fields, .. } => fields,
+            ComputedValue::Lowercase { field } => std::slice::from_ref(field),
+        }
+    }
 }
 
 #[derive(
     Debug,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     rkyv::Archive,
@@ -165,11 +316,16 @@ pub struct BaseOptions {
 pub struct TextOptions {
     #[serde(flatten)]
     pub base: BaseOptions,
+
+    #[serde(default)]
+    /// The analyzer used to tokenize and index the text.
+    pub analyzer: AnalyzerOptions,
 }
 
 #[derive(
     Debug,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     rkyv::Archive,
@@ -197,6 +353,7 @@ pub struct NumericFieldOptions {
     /// are used when sorting and filtering.
     pub fast: bool,
 
+    #[serde(flatten)]
     pub base: BaseOptions,
 }
 
@@ -226,6 +383,126 @@ struct NumericFieldOptionsSchema {
     base: BaseOptions,
 }
 
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct DateFieldOptions {
+    #[serde(default = "default_date_formats")]
+    /// The formats accepted when uploading dates.
+    ///
+    /// Each format is tried in order until one matches the value.
+    pub formats: Vec<DateFormat>,
+
+    #[serde(default)]
+    /// The precision dates are stored with in the fast field.
+    pub precision: DatePrecision,
+
+    #[serde(flatten)]
+    pub numeric: NumericFieldOptions,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct VectorFieldOptions {
+    /// The number of dimensions of each vector.
+    pub dims: usize,
+
+    #[serde(default)]
+    /// The metric used to compare vectors.
+    pub metric: DistanceMetric,
+
+    #[serde(flatten)]
+    pub base: BaseOptions,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+/// A format dates can be uploaded in.
+pub enum DateFormat {
+    /// A RFC3339 formatted string, i.e. `2022-01-01T12:30:00Z`.
+    Rfc3339,
+
+    /// A RFC2822 formatted string, i.e. `Sat, 01 Jan 2022 12:30:00 +0000`.
+    Rfc2822,
+
+    /// A unix timestamp in seconds.
+    Timestamp,
+
+    /// A unix timestamp in milliseconds.
+    TimestampMillis,
+
+    /// A unix timestamp in microseconds.
+    TimestampMicros,
+
+    /// A custom format using the `time` crate's format description syntax,
+    /// i.e. `[year]-[month]-[day] [hour]:[minute]:[second]`.
+    ///
+    /// Dates without an offset are treated as UTC and dates without
+    /// a time are treated as midnight.
+    Custom(String),
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+/// The precision of a date fast field.
+pub enum DatePrecision {
+    #[default]
+    Seconds,
+    Milliseconds,
+    Microseconds,
+}
+
+impl From<DatePrecision> for TantivyDatePrecision {
+    fn from(precision: DatePrecision) -> Self {
+        match precision {
+            DatePrecision::Seconds => TantivyDatePrecision::Seconds,
+            DatePrecision::Milliseconds => TantivyDatePrecision::Milliseconds,
+            DatePrecision::Microseconds => TantivyDatePrecision::Microseconds,
+        }
+    }
+}
+
+fn default_date_formats() -> Vec<DateFormat> {
+    vec![DateFormat::Rfc3339, DateFormat::Timestamp]
+}
+
 // This is a hack to default to true for serde.
 fn default_to_true() -> bool {
     true
@@ -0,0 +1,704 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use serde_json::Value;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::fastfield::{Column, MultiValuedFastFieldReader};
+use tantivy::schema::{Cardinality, Field as TantivyField};
+use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader, TantivyError};
+
+/// The mean radius of the earth in metres.
+const EARTH_RADIUS_METRES: f64 = 6_371_008.8;
+/// The characters used by geohashes, in order of their value.
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+/// The maximum supported length of a geohash.
+const MAX_GEOHASH_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+/// A point on the earth in degrees.
+pub struct GeoPoint {
+    /// The latitude, between `-90.0` and `90.0`.
+    pub lat: f64,
+    /// The longitude, between `-180.0` and `180.0`.
+    pub lon: f64,
+}
+
+impl GeoPoint {
+    /// Creates a new point, returning the reason if the coordinates are out of range.
+    pub fn new(lat: f64, lon: f64) -> Result<Self, &'static str> {
+        if !(lat.is_finite() && (-90.0..=90.0).contains(&lat)) {
+            return Err("The latitude must be between `-90.0` and `90.0`");
+        }
+        if !(lon.is_finite() && (-180.0..=180.0).contains(&lon)) {
+            return Err("The longitude must be between `-180.0` and `180.0`");
+        }
+
+        Ok(Self { lat, lon })
+    }
+
+    /// Parses a point from a JSON value.
+    ///
+    /// Points can be provided as:
+    /// - An object, i.e. `{"lat": 51.5, "lon": -0.12}`
+    /// - An array in GeoJSON order, i.e. `[-0.12, 51.5]`
+    /// - A string of `lat,lon`, i.e. `"51.5,-0.12"`
+    /// - A geohash string, i.e. `"gcpuvr29"`
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let coordinate = |value: Option<&Value>, name: &str| {
+            value
+                .and_then(Value::as_f64)
+                .ok_or_else(|| format!("Expected `{name}` to be a number"))
+        };
+
+        match value {
+            Value::Object(object) => {
+                let lat = coordinate(object.get("lat"), "lat")?;
+                let lon = coordinate(object.get("lon"), "lon")?;
+                Self::new(lat, lon).map_err(String::from)
+            },
+            Value::Array(values) if values.len() == 2 => {
+                let lon = coordinate(values.first(), "lon")?;
+                let lat = coordinate(values.get(1), "lat")?;
+                Self::new(lat, lon).map_err(String::from)
+            },
+            Value::String(s) => Self::from_str(s),
+            _ => Err(
+                "Expected an object, `[lon, lat]` array, `lat,lon` string or geohash"
+                    .to_string(),
+            ),
+        }
+    }
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (lat, lon) = match s.split_once(',') {
+            Some(coordinates) => coordinates,
+            None => return Self::from_geohash(s).map_err(String::from),
+        };
+
+        let lat = lat
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("The latitude {lat:?} is not a number"))?;
+        let lon = lon
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("The longitude {lon:?} is not a number"))?;
+
+        Self::new(lat, lon).map_err(String::from)
+    }
+
+    /// Decodes a geohash into the point at the centre of its cell.
+    pub fn from_geohash(hash: &str) -> Result<Self, &'static str> {
+        if hash.is_empty() || hash.len() > MAX_GEOHASH_LENGTH {
+            return Err("The geohash must be between 1 and 12 characters long");
+        }
+
+        let mut lat_range = (-90.0, 90.0);
+        let mut lon_range = (-180.0, 180.0);
+        let mut is_lon = true;
+
+        for c in hash.bytes() {
+            let bits = GEOHASH_ALPHABET
+                .iter()
+                .position(|&v| v == c.to_ascii_lowercase())
+                .ok_or("The geohash contains an invalid character")?;
+
+            for shift in (0..5).rev() {
+                let range: &mut (f64, f64) = if is_lon {
+                    &mut lon_range
+                } else {
+                    &mut lat_range
+                };
+
+                let mid = (range.0 + range.1) / 2.0;
+                if (bits >> shift) & 1 == 1 {
+                    range.0 = mid;
+                } else {
+                    range.1 = mid;
+                }
+                is_lon = !is_lon;
+            }
+        }
+
+        Self::new(
+            (lat_range.0 + lat_range.1) / 2.0,
+            (lon_range.0 + lon_range.1) / 2.0,
+        )
+    }
+
+    /// Encodes the point into a single value for the fast field.
+    ///
+    /// The latitude and longitude are quantized to 32 bits each, which is
+    /// accurate to within a centimetre, with the latitude in the high bits.
+    pub fn encode(&self) -> u64 {
+        let lat = quantize(self.lat, -90.0, 90.0);
+        let lon = quantize(self.lon, -180.0, 180.0);
+        ((lat as u64) << 32) | lon as u64
+    }
+
+    /// Decodes a point previously encoded with [GeoPoint::encode].
+    pub fn decode(encoded: u64) -> Self {
+        Self {
+            lat: dequantize((encoded >> 32) as u32, -90.0, 90.0),
+            lon: dequantize(encoded as u32, -180.0, 180.0),
+        }
+    }
+
+    /// The great-circle distance to the other point in metres.
+    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
+        let lat1 = self.lat.to_radians();
+        let lat2 = other.lat.to_radians();
+        let d_lat = (other.lat - self.lat).to_radians();
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METRES * a.sqrt().min(1.0).asin()
+    }
+}
+
+fn quantize(value: f64, min: f64, max: f64) -> u32 {
+    ((value - min) / (max - min) * u32::MAX as f64).round() as u32
+}
+
+fn dequantize(value: u32, min: f64, max: f64) -> f64 {
+    min + (value as f64 / u32::MAX as f64) * (max - min)
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    try_from = "UncheckedGeoFilter"
+)]
+/// A filter matching documents by the location of a geo point field.
+pub enum GeoFilter {
+    /// Matches points within the radius of the centre.
+    Distance {
+        /// The centre of the circle.
+        center: GeoPoint,
+        /// The radius of the circle in metres.
+        radius: f64,
+    },
+
+    /// Matches points within the box.
+    ///
+    /// Boxes crossing the antimeridian are supported by providing a
+    /// `top_left` longitude greater than the `bottom_right` longitude.
+    BoundingBox {
+        /// The north-west corner of the box.
+        top_left: GeoPoint,
+        /// The south-east corner of the box.
+        bottom_right: GeoPoint,
+    },
+}
+
+impl GeoFilter {
+    /// Creates a new distance filter, returning the reason if the radius is invalid.
+    pub fn distance(center: GeoPoint, radius: f64) -> Result<Self, &'static str> {
+        let filter = GeoFilter::Distance { center, radius };
+        filter.validate()?;
+        Ok(filter)
+    }
+
+    /// Checks the filter can match any points, returning the reason if not.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            GeoFilter::Distance { radius, .. } => {
+                if !(radius.is_finite() && *radius >= 0.0) {
+                    return Err("The radius must be a finite, non-negative number");
+                }
+            },
+            GeoFilter::BoundingBox {
+                top_left,
+                bottom_right,
+            } => {
+                if top_left.lat < bottom_right.lat {
+                    return Err(
+                        "The `top_left` latitude must not be below the `bottom_right` \
+                         latitude",
+                    );
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Does the point match the filter.
+    pub fn matches(&self, point: &GeoPoint) -> bool {
+        match self {
+            GeoFilter::Distance { center, radius } => {
+                center.distance_to(point) <= *radius
+            },
+            GeoFilter::BoundingBox {
+                top_left,
+                bottom_right,
+            } => {
+                if point.lat > top_left.lat || point.lat < bottom_right.lat {
+                    return false;
+                }
+
+                if top_left.lon <= bottom_right.lon {
+                    (top_left.lon..=bottom_right.lon).contains(&point.lon)
+                } else {
+                    point.lon >= top_left.lon || point.lon <= bottom_right.lon
+                }
+            },
+        }
+    }
+
+    /// Does any of the encoded fast field values match the filter.
+    pub fn matches_any(&self, encoded: &[u64]) -> bool {
+        encoded
+            .iter()
+            .any(|&value| self.matches(&GeoPoint::decode(value)))
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// A [GeoFilter] which has not been validated yet.
+enum UncheckedGeoFilter {
+    Distance {
+        center: GeoPoint,
+        radius: f64,
+    },
+    BoundingBox {
+        top_left: GeoPoint,
+        bottom_right: GeoPoint,
+    },
+}
+
+impl TryFrom<UncheckedGeoFilter> for GeoFilter {
+    type Error = &'static str;
+
+    fn try_from(filter: UncheckedGeoFilter) -> Result<Self, Self::Error> {
+        let filter = match filter {
+            UncheckedGeoFilter::Distance { center, radius } => {
+                GeoFilter::Distance { center, radius }
+            },
+            UncheckedGeoFilter::BoundingBox {
+                top_left,
+                bottom_right,
+            } => GeoFilter::BoundingBox {
+                top_left,
+                bottom_right,
+            },
+        };
+        filter.validate()?;
+        Ok(filter)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+/// The order results are sorted in.
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+/// Sorts documents by the distance of a geo point field from the origin.
+pub struct GeoDistanceSort {
+    /// The point distances are measured from.
+    pub origin: GeoPoint,
+    #[serde(default)]
+    /// The order of the results, nearest first by default.
+    pub order: SortOrder,
+}
+
+impl GeoDistanceSort {
+    /// The distance in metres used to sort a document with the given encoded
+    /// fast field values.
+    ///
+    /// The nearest point is used for multi-value fields when sorting in
+    /// ascending order and the farthest point when sorting in descending order.
+    /// Documents without a point are always sorted last.
+    pub fn sort_key(&self, encoded: &[u64]) -> f64 {
+        let distances = encoded
+            .iter()
+            .map(|&value| self.origin.distance_to(&GeoPoint::decode(value)));
+
+        match self.order {
+            SortOrder::Asc => distances.min_by(f64::total_cmp).unwrap_or(f64::INFINITY),
+            SortOrder::Desc => distances
+                .max_by(f64::total_cmp)
+                .unwrap_or(f64::NEG_INFINITY),
+        }
+    }
+
+    /// Compares the sort keys of two documents in the sort order.
+    pub fn compare(&self, a: f64, b: f64) -> Ordering {
+        match self.order {
+            SortOrder::Asc => a.total_cmp(&b),
+            SortOrder::Desc => b.total_cmp(&a),
+        }
+    }
+}
+
+/// Collects the documents whose geo point field matches a [GeoFilter], ordered
+/// by a [GeoDistanceSort].
+///
+/// Each document is returned with its [GeoDistanceSort::sort_key], or `0.0`
+/// if no sort is set, in which case documents are returned in index order.
+/// Documents without a point never match a filter, note that single value
+/// fields read missing points as the encoded value `0`.
+pub struct GeoCollector {
+    field: TantivyField,
+    filter: Option<GeoFilter>,
+    sort: Option<GeoDistanceSort>,
+    limit: usize,
+}
+
+impl GeoCollector {
+    /// Creates a new collector returning at most `limit` documents, reading
+    /// points from the given `geopoint` field.
+    pub fn new(field: TantivyField, limit: usize) -> Self {
+        Self {
+            field,
+            filter: None,
+            sort: None,
+            limit,
+        }
+    }
+
+    /// Only collect documents with a point matching the filter.
+    pub fn with_filter(mut self, filter: GeoFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Order the collected documents by their distance from a point.
+    pub fn with_sort(mut self, sort: GeoDistanceSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+}
+
+impl Collector for GeoCollector {
+    type Fruit = Vec<(f64, DocAddress)>;
+    type Child = GeoSegmentCollector;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        let entry = segment.schema().get_field_entry(self.field);
+        let points = match entry.field_type().fastfield_cardinality() {
+            Some(Cardinality::SingleValue) => {
+                GeoPoints::Single(segment.fast_fields().u64(self.field)?)
+            },
+            Some(Cardinality::MultiValues) => {
+                GeoPoints::Multi(segment.fast_fields().u64s(self.field)?)
+            },
+            None => {
+                return Err(TantivyError::SchemaError(format!(
+                    "The field {:?} is not a fast field",
+                    entry.name(),
+                )))
+            },
+        };
+
+        Ok(GeoSegmentCollector {
+            segment_ord: segment_local_id,
+            points,
+            buffer: Vec::new(),
+            filter: self.filter.clone(),
+            sort: self.sort.clone(),
+            limit: self.limit,
+            hits: Vec::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<(f64, DocAddress)>>,
+    ) -> tantivy::Result<Self::Fruit> {
+        let mut hits = segment_fruits.into_iter().flatten().collect();
+        truncate_hits(&mut hits, self.sort.as_ref(), self.limit);
+        Ok(hits)
+    }
+}
+
+/// The encoded points of a segment's geo point fast field.
+enum GeoPoints {
+    Single(Arc<dyn Column<u64>>),
+    Multi(MultiValuedFastFieldReader<u64>),
+}
+
+impl GeoPoints {
+    fn read(&self, doc: DocId, encoded: &mut Vec<u64>) {
+        encoded.clear();
+        match self {
+            GeoPoints::Single(column) => encoded.push(column.get_val(doc)),
+            GeoPoints::Multi(reader) => reader.get_vals(doc, encoded),
+        }
+    }
+}
+
+/// The [SegmentCollector] of a [GeoCollector].
+pub struct GeoSegmentCollector {
+    segment_ord: SegmentOrdinal,
+    points: GeoPoints,
+    buffer: Vec<u64>,
+    filter: Option<GeoFilter>,
+    sort: Option<GeoDistanceSort>,
+    limit: usize,
+    hits: Vec<(f64, DocId)>,
+}
+
+impl SegmentCollector for GeoSegmentCollector {
+    type Fruit = Vec<(f64, DocAddress)>;
+
+    fn collect(&mut self, doc: DocId, _score: Score) {
+        self.points.read(doc, &mut self.buffer);
+
+        if let Some(filter) = self.filter.as_ref() {
+            if !filter.matches_any(&self.buffer) {
+                return;
+            }
+        }
+
+        let key = self
+            .sort
+            .as_ref()
+            .map_or(0.0, |sort| sort.sort_key(&self.buffer));
+        self.hits.push((key, doc));
+
+        // Hits are only pruned once enough have been collected to keep
+        // the cost of sorting them low.
+        if self.hits.len() >= self.limit.saturating_mul(2).max(64) {
+            truncate_hits(&mut self.hits, self.sort.as_ref(), self.limit);
+        }
+    }
+
+    fn harvest(mut self) -> Self::Fruit {
+        truncate_hits(&mut self.hits, self.sort.as_ref(), self.limit);
+        self.hits
+            .into_iter()
+            .map(|(key, doc)| (key, DocAddress::new(self.segment_ord, doc)))
+            .collect()
+    }
+}
+
+/// Sorts the hits by their sort key, then by their address, and keeps the
+/// first `limit` hits.
+fn truncate_hits<T: Ord>(
+    hits: &mut Vec<(f64, T)>,
+    sort: Option<&GeoDistanceSort>,
+    limit: usize,
+) {
+    hits.sort_unstable_by(|a, b| {
+        sort.map_or(Ordering::Equal, |sort| sort.compare(a.0, b.0))
+            .then_with(|| a.1.cmp(&b.1))
+    });
+    hits.truncate(limit);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_points() {
+        let expected = GeoPoint::new(51.5, -0.12).unwrap();
+        for value in [
+            json!({ "lat": 51.5, "lon": -0.12 }),
+            json!([-0.12, 51.5]),
+            json!(" 51.5, -0.12 "),
+        ] {
+            assert_eq!(GeoPoint::from_json(&value).unwrap(), expected, "{value}");
+        }
+
+        let point = GeoPoint::from_json(&json!("gcpuvr29")).unwrap();
+        assert!(point.distance_to(&GeoPoint::new(51.5, -0.12).unwrap()) < 50.0);
+
+        for value in [
+            json!({ "lat": 91.0, "lon": 0.0 }),
+            json!([181.0, 0.0]),
+            json!("abc,1"),
+            json!("gcpva"),
+            json!(1),
+        ] {
+            assert!(GeoPoint::from_json(&value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        for (lat, lon) in [(0.0, 0.0), (90.0, 180.0), (-90.0, -180.0), (51.5, -0.12)] {
+            let point = GeoPoint::new(lat, lon).unwrap();
+            let decoded = GeoPoint::decode(point.encode());
+            assert!(point.distance_to(&decoded) < 0.01, "{point:?} {decoded:?}");
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let london = GeoPoint::new(51.5074, -0.1278).unwrap();
+        let paris = GeoPoint::new(48.8566, 2.3522).unwrap();
+
+        let distance = london.distance_to(&paris);
+        assert!((distance - 343_500.0).abs() < 1_000.0, "{distance}");
+
+        let filter = GeoFilter::Distance {
+            center: london,
+            radius: 350_000.0,
+        };
+        assert!(filter.matches_any(&[paris.encode()]));
+        assert!(!filter.matches_any(&[]));
+
+        let filter: GeoFilter = serde_json::from_value(json!({
+            "type": "bounding_box",
+            "top_left": { "lat": 52.0, "lon": -1.0 },
+            "bottom_right": { "lat": 51.0, "lon": 1.0 },
+        }))
+        .unwrap();
+        assert!(filter.matches(&london));
+        assert!(!filter.matches(&paris));
+
+        let filter = GeoFilter::BoundingBox {
+            top_left: GeoPoint::new(10.0, 170.0).unwrap(),
+            bottom_right: GeoPoint::new(-10.0, -170.0).unwrap(),
+        };
+        assert!(filter.matches(&GeoPoint::new(0.0, 179.0).unwrap()));
+        assert!(filter.matches(&GeoPoint::new(0.0, -175.0).unwrap()));
+        assert!(!filter.matches(&GeoPoint::new(0.0, 0.0).unwrap()));
+    }
+
+    #[test]
+    fn test_distance_sort() {
+        let london = GeoPoint::new(51.5074, -0.1278).unwrap();
+        let paris = GeoPoint::new(48.8566, 2.3522).unwrap();
+
+        let sort = GeoDistanceSort {
+            origin: london,
+            order: SortOrder::Asc,
+        };
+        let mut keys = [
+            sort.sort_key(&[]),
+            sort.sort_key(&[paris.encode()]),
+            sort.sort_key(&[paris.encode(), london.encode()]),
+        ];
+        keys.sort_by(|a, b| sort.compare(*a, *b));
+        assert!(keys[0] < 1.0);
+        assert!(keys[1] > 300_000.0);
+        assert!(keys[2].is_infinite());
+    }
+
+    #[test]
+    fn test_distance_sort_desc() {
+        let london = GeoPoint::new(51.5074, -0.1278).unwrap();
+        let paris = GeoPoint::new(48.8566, 2.3522).unwrap();
+
+        let sort = GeoDistanceSort {
+            origin: london,
+            order: SortOrder::Desc,
+        };
+        let farthest = sort.sort_key(&[london.encode(), paris.encode()]);
+        assert_eq!(farthest, sort.sort_key(&[paris.encode()]));
+        assert_eq!(sort.sort_key(&[]), f64::NEG_INFINITY);
+        assert_eq!(sort.compare(farthest, 0.0), Ordering::Less);
+    }
+
+    #[test]
+    fn test_invalid_filters() {
+        let london = GeoPoint::new(51.5074, -0.1278).unwrap();
+        assert!(GeoFilter::distance(london, 0.0).is_ok());
+        for radius in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(GeoFilter::distance(london, radius).is_err(), "{radius}");
+        }
+
+        for value in [
+            json!({
+                "type": "distance",
+                "center": { "lat": 51.5, "lon": -0.12 },
+                "radius": -5.0,
+            }),
+            json!({
+                "type": "bounding_box",
+                "top_left": { "lat": 51.0, "lon": -1.0 },
+                "bottom_right": { "lat": 52.0, "lon": 1.0 },
+            }),
+        ] {
+            assert!(serde_json::from_value::<GeoFilter>(value).is_err());
+        }
+    }
+
+    #[test]
+    fn test_geo_collector() -> tantivy::Result<()> {
+        use tantivy::query::AllQuery;
+        use tantivy::schema::{NumericOptions, Schema};
+        use tantivy::{Document, Index};
+
+        let mut schema = Schema::builder();
+        let field = schema.add_u64_field(
+            "location",
+            NumericOptions::default().set_fast(Cardinality::MultiValues),
+        );
+        let index = Index::create_in_ram(schema.build());
+
+        let london = GeoPoint::new(51.5074, -0.1278).unwrap();
+        let paris = GeoPoint::new(48.8566, 2.3522).unwrap();
+        let berlin = GeoPoint::new(52.52, 13.405).unwrap();
+
+        let mut writer = index.writer_with_num_threads(1, 15_000_000)?;
+        for points in [vec![paris], vec![], vec![berlin, london], vec![london]] {
+            let mut doc = Document::new();
+            for point in points {
+                doc.add_u64(field, point.encode());
+            }
+            writer.add_document(doc)?;
+        }
+        writer.commit()?;
+
+        let searcher = index.reader()?.searcher();
+        let docs = |collector: GeoCollector| -> tantivy::Result<Vec<u32>> {
+            let hits = searcher.search(&AllQuery, &collector)?;
+            Ok(hits.into_iter().map(|(_, addr)| addr.doc_id).collect())
+        };
+
+        let filter = GeoFilter::distance(london, 400_000.0).unwrap();
+        let nearest = GeoDistanceSort {
+            origin: london,
+            order: SortOrder::Asc,
+        };
+        let farthest = GeoDistanceSort {
+            origin: london,
+            order: SortOrder::Desc,
+        };
+
+        assert_eq!(docs(GeoCollector::new(field, 10))?, [0, 1, 2, 3]);
+        assert_eq!(
+            docs(GeoCollector::new(field, 10).with_filter(filter.clone()))?,
+            [0, 2, 3],
+        );
+        assert_eq!(
+            docs(
+                GeoCollector::new(field, 2)
+                    .with_filter(filter)
+                    .with_sort(nearest)
+            )?,
+            [2, 3],
+        );
+        assert_eq!(
+            docs(GeoCollector::new(field, 10).with_sort(farthest))?,
+            [2, 0, 3, 1],
+        );
+
+        Ok(())
+    }
+}
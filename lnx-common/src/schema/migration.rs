@@ -0,0 +1,563 @@
+use std::collections::BTreeSet;
+use std::mem;
+
+use tantivy::Score;
+use validator::{Validate, ValidationErrors};
+
+use super::{Field, Schema, SchemaBuildError, WriterSettings};
+use crate::CorruptedData;
+
+/// The version of a schema within a [SchemaHistory].
+pub type SchemaVersion = u32;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single difference between two versions of a schema.
+pub enum SchemaChangeKind {
+    /// A new field was added.
+    AddField { name: String, field: Field },
+    /// An existing field was removed.
+    RemoveField { name: String },
+    /// The options or type of an existing field were changed.
+    ModifyField {
+        name: String,
+        old: Field,
+        new: Field,
+    },
+    /// The boost of a field was added, changed or removed.
+    ChangeBoost {
+        name: String,
+        old: Option<Score>,
+        new: Option<Score>,
+    },
+    /// The default search fields were changed.
+    ChangeSearchFields { old: Vec<String>, new: Vec<String> },
+    /// The indexing options were changed.
+    ChangeIndexingOptions {
+        old: WriterSettings,
+        new: WriterSettings,
+    },
+    /// The schema switched between being schema-less and having defined fields.
+    ChangeSchemaLess { schema_less: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a change affects the existing data of an index.
+pub enum ChangeImpact {
+    /// The change can be applied to a live index without touching existing data.
+    Safe,
+    /// Existing data is incompatible with the change and must be reindexed.
+    RequiresReindex {
+        /// Why the data must be reindexed.
+        reason: &'static str,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A classified difference between two versions of a schema.
+pub struct SchemaChange {
+    pub kind: SchemaChangeKind,
+    pub impact: ChangeImpact,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// The differences between two versions of a schema.
+pub struct SchemaDiff {
+    pub changes: Vec<SchemaChange>,
+}
+
+impl SchemaDiff {
+    /// Are the schemas identical.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Does any change require the existing data to be reindexed.
+    pub fn requires_reindex(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.impact != ChangeImpact::Safe)
+    }
+
+    fn push(&mut self, kind: SchemaChangeKind, impact: ChangeImpact) {
+        self.changes.push(SchemaChange { kind, impact });
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// An error preventing a schema from being migrated to a new version.
+pub enum SchemaMigrationError {
+    #[error("The index name cannot be changed from {old:?} to {new:?}")]
+    NameChanged { old: String, new: String },
+    #[error("The new schema is invalid: {0}")]
+    Invalid(#[from] ValidationErrors),
+    #[error("The new schema is invalid: {0}")]
+    Build(#[from] SchemaBuildError),
+    #[error("The schema changes require the existing data to be reindexed")]
+    RequiresReindex(SchemaDiff),
+}
+
+impl Schema {
+    /// Computes the changes required to migrate from this schema to the `new` schema.
+    ///
+    /// Each change is classified by whether it can be applied online or requires
+    /// the existing data to be reindexed.
+    pub fn diff(&self, new: &Schema) -> Result<SchemaDiff, SchemaMigrationError> {
+        if self.name != new.name {
+            return Err(SchemaMigrationError::NameChanged {
+                old: self.name.clone(),
+                new: new.name.clone(),
+            });
+        }
+
+        let mut diff = SchemaDiff::default();
+
+        match (&self.fields, &new.fields) {
+            (Some(old_fields), Some(new_fields)) => {
+                for (name, old) in old_fields {
+                    match new_fields.get(name) {
+                        None => diff.push(
+                            SchemaChangeKind::RemoveField { name: name.clone() },
+                            ChangeImpact::Safe,
+                        ),
+                        Some(new) if new != old => diff.push(
+                            SchemaChangeKind::ModifyField {
+                                name: name.clone(),
+                                old: old.clone(),
+                                new: new.clone(),
+                            },
+                            field_change_impact(old, new),
+                        ),
+                        Some(_) => {},
+                    }
+                }
+
+                for (name, field) in new_fields {
+                    if old_fields.contains_key(name) {
+                        continue;
+                    }
+
+                    let impact = if field.is_required() {
+                        ChangeImpact::RequiresReindex {
+                            reason: "Existing documents do not contain the new \
+                                     required field",
+                        }
+                    } else {
+                        ChangeImpact::Safe
+                    };
+                    diff.push(
+                        SchemaChangeKind::AddField {
+                            name: name.clone(),
+                            field: field.clone(),
+                        },
+                        impact,
+                    );
+                }
+            },
+            (None, None) => {},
+            (_, new_fields) => diff.push(
+                SchemaChangeKind::ChangeSchemaLess {
+                    schema_less: new_fields.is_none(),
+                },
+                ChangeImpact::RequiresReindex {
+                    reason: "The index switched between being schema-less and \
+                             having defined fields",
+                },
+            ),
+        }
+
+        let boosted_fields = self
+            .boosted_fields
+            .keys()
+            .chain(new.boosted_fields.keys())
+            .collect::<BTreeSet<_>>();
+        for name in boosted_fields {
+            let old = self.boosted_fields.get(name).copied();
+            let new = new.boosted_fields.get(name).copied();
+            if old != new {
+                diff.push(
+                    SchemaChangeKind::ChangeBoost {
+                        name: name.clone(),
+                        old,
+                        new,
+                    },
+                    ChangeImpact::Safe,
+                );
+            }
+        }
+
+        if self.search_fields != new.search_fields {
+            diff.push(
+                SchemaChangeKind::ChangeSearchFields {
+                    old: self.search_fields.clone(),
+                    new: new.search_fields.clone(),
+                },
+                ChangeImpact::Safe,
+            );
+        }
+
+        if self.indexing_options != new.indexing_options {
+            diff.push(
+                SchemaChangeKind::ChangeIndexingOptions {
+                    old: self.indexing_options,
+                    new: new.indexing_options,
+                },
+                ChangeImpact::Safe,
+            );
+        }
+
+        Ok(diff)
+    }
+}
+
+/// Classifies a change to the options of an existing field.
+fn field_change_impact(old: &Field, new: &Field) -> ChangeImpact {
+    if mem::discriminant(old) != mem::discriminant(new) {
+        return ChangeImpact::RequiresReindex {
+            reason: "The type of the field changed",
+        };
+    }
+
+    if !old.is_required() && new.is_required() {
+        return ChangeImpact::RequiresReindex {
+            reason: "Existing documents may not contain the now required field",
+        };
+    }
+
+    if let (Field::Text(old), Field::Text(new)) = (old, new) {
+        if old.analyzer != new.analyzer {
+            return ChangeImpact::RequiresReindex {
+                reason: "The analyzer of the field changed",
+            };
+        }
+    }
+
+    if without_safe_options(old) == without_safe_options(new) {
+        ChangeImpact::Safe
+    } else {
+        ChangeImpact::RequiresReindex {
+            reason: "The indexing options of the field changed",
+        }
+    }
+}
+
+/// Resets the options which only affect how new documents are validated.
+fn without_safe_options(field: &Field) -> Field {
+    let mut field = field.clone();
+
+    let base = field.base_options_mut();
+    base.required = false;
+    base.default = None;
+    base.compute = None;
+
+    if let Field::Date(opts) = &mut field {
+        opts.formats.clear();
+    }
+
+    field
+}
+
+#[derive(
+    Debug,
+    Clone,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+/// Every version of an index's schema.
+///
+/// Data is tagged with the version of the schema it was written with
+/// so older data can still be read after the schema is migrated.
+///
+/// Migrating only records a new version, existing data is never rewritten.
+/// Safe changes take effect once the history is persisted, new documents
+/// are validated and new fragments are tagged with the
+/// [SchemaHistory::current_version] while existing fragments keep being read
+/// with the version they were written with.
+pub struct SchemaHistory {
+    /// The schema of each version, the index is the version.
+    versions: Vec<Schema>,
+}
+
+impl SchemaHistory {
+    /// The index metadata key the history is persisted under.
+    pub const METADATA_KEY: &'static str = "schema-history";
+
+    /// Deserializes the history from a given buffer.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CorruptedData> {
+        let history: Self = serde_json::from_slice(bytes).map_err(|_| CorruptedData)?;
+        if history.versions.is_empty() {
+            return Err(CorruptedData);
+        }
+        Ok(history)
+    }
+
+    /// Serializes the history to a buffer.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CorruptedData> {
+        serde_json::to_vec(self).map_err(|_| CorruptedData)
+    }
+
+    /// Creates a new history with the given schema as the first version.
+    pub fn new(schema: Schema) -> Self {
+        Self {
+            versions: vec![schema],
+        }
+    }
+
+    /// The latest version of the schema.
+    pub fn current_version(&self) -> SchemaVersion {
+        (self.versions.len() - 1) as SchemaVersion
+    }
+
+    /// The latest schema.
+    pub fn current(&self) -> &Schema {
+        self.versions
+            .last()
+            .expect("History always contains a schema")
+    }
+
+    /// The schema for the given version.
+    pub fn get(&self, version: SchemaVersion) -> Option<&Schema> {
+        self.versions.get(version as usize)
+    }
+
+    /// Migrates the index to the `new` schema, returning the new version
+    /// and the changes that were applied.
+    ///
+    /// Only changes which are safe to apply to a live index are accepted,
+    /// if any change requires the data to be reindexed the schema is left
+    /// unchanged and [SchemaMigrationError::RequiresReindex] is returned.
+    /// No new version is created if the schemas are identical.
+    ///
+    /// Fields are checked against every version, so re-adding a removed field
+    /// with options incompatible with data written by an earlier version
+    /// requires a reindex.
+    pub fn migrate(
+        &mut self,
+        new: Schema,
+    ) -> Result<(SchemaVersion, SchemaDiff), SchemaMigrationError> {
+        new.validate()?;
+        if new.fields.is_some() {
+            new.tantivy_schema()?;
+        }
+
+        let mut diff = self.current().diff(&new)?;
+        self.check_readded_fields(&mut diff);
+        if diff.requires_reindex() {
+            return Err(SchemaMigrationError::RequiresReindex(diff));
+        }
+
+        if !diff.is_empty() {
+            self.versions.push(new);
+        }
+
+        Ok((self.current_version(), diff))
+    }
+
+    /// Marks any added fields which were defined by an earlier version with
+    /// incompatible options as requiring a reindex.
+    ///
+    /// Fragments written with the earlier version still contain the old field.
+    fn check_readded_fields(&self, diff: &mut SchemaDiff) {
+        for change in diff.changes.iter_mut() {
+            let (name, field) = match &change.kind {
+                SchemaChangeKind::AddField { name, field } => (name, field),
+                _ => continue,
+            };
+
+            let is_compatible = self
+                .versions
+                .iter()
+                .filter_map(|schema| schema.fields.as_ref()?.get(name))
+                .all(|old| field_change_impact(old, field) == ChangeImpact::Safe);
+            if !is_compatible && change.impact == ChangeImpact::Safe {
+                change.impact = ChangeImpact::RequiresReindex {
+                    reason: "An earlier version defined the field with \
+                             incompatible options",
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn test_schema(value: serde_json::Value) -> Schema {
+        let mut schema = json!({
+            "name": "test-index",
+            "fields": {
+                "title": { "type": "text" },
+                "count": { "type": "u64", "indexed": true, "required": true },
+                "created": { "type": "date", "fast": true },
+            },
+        });
+        for (key, value) in value.as_object().expect("Object") {
+            schema[key] = value.clone();
+        }
+
+        serde_json::from_value(schema).expect("Deserialize schema")
+    }
+
+    #[test]
+    fn test_safe_changes() {
+        let old = test_schema(json!({}));
+        let new = test_schema(json!({
+            "fields": {
+                "title": { "type": "text" },
+                "count": { "type": "u64", "indexed": true },
+                "created": { "type": "date", "fast": true, "formats": ["rfc2822"] },
+                "tags": { "type": "rawstr", "multi": true },
+            },
+            "search_fields": ["title"],
+            "boosted_fields": { "title": 2.0 },
+        }));
+
+        let diff = old.diff(&new).expect("Diff schemas");
+        assert!(!diff.requires_reindex());
+        assert_eq!(diff.changes.len(), 5, "{diff:?}");
+        assert!(diff.changes.iter().any(|change| matches!(
+            &change.kind,
+            SchemaChangeKind::AddField { name, .. } if name == "tags",
+        )));
+        assert!(diff.changes.iter().any(|change| matches!(
+            &change.kind,
+            SchemaChangeKind::ChangeBoost { name, old: None, new: Some(_) }
+                if name == "title",
+        )));
+    }
+
+    #[test]
+    fn test_changes_requiring_reindex() {
+        let old = test_schema(json!({}));
+
+        let changes = [
+            json!({ "title": { "type": "text", "analyzer": { "filters": [] } } }),
+            json!({ "count": { "type": "i64", "indexed": true, "required": true } }),
+            json!({
+                "count": {
+                    "type": "u64",
+                    "indexed": true,
+                    "fast": true,
+                    "required": true,
+                },
+            }),
+            json!({ "created": { "type": "date", "fast": true, "required": true } }),
+            json!({ "other": { "type": "u64", "fast": true, "required": true } }),
+        ];
+        for change in changes {
+            let mut fields = json!({
+                "title": { "type": "text" },
+                "count": { "type": "u64", "indexed": true, "required": true },
+                "created": { "type": "date", "fast": true },
+            });
+            for (name, field) in change.as_object().expect("Object") {
+                fields[name] = field.clone();
+            }
+
+            let new = test_schema(json!({ "fields": fields }));
+            let diff = old.diff(&new).expect("Diff schemas");
+            assert!(diff.requires_reindex(), "{change} should require a reindex");
+        }
+
+        let new = test_schema(json!({ "fields": null }));
+        assert!(old.diff(&new).expect("Diff schemas").requires_reindex());
+
+        let new = test_schema(json!({ "name": "other-index" }));
+        assert!(matches!(
+            old.diff(&new),
+            Err(SchemaMigrationError::NameChanged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_schema_history() {
+        let mut history = SchemaHistory::new(test_schema(json!({})));
+        assert_eq!(history.current_version(), 0);
+
+        let (version, diff) = history
+            .migrate(test_schema(json!({})))
+            .expect("Migrate schema");
+        assert_eq!(version, 0);
+        assert!(diff.is_empty());
+
+        let new = test_schema(json!({ "boosted_fields": { "title": 1.5 } }));
+        let (version, _) = history.migrate(new.clone()).expect("Migrate schema");
+        assert_eq!(version, 1);
+        assert_eq!(history.current(), &new);
+        assert!(history
+            .get(0)
+            .expect("Version exists")
+            .boosted_fields
+            .is_empty());
+
+        let new = test_schema(json!({ "fields": null }));
+        assert!(matches!(
+            history.migrate(new),
+            Err(SchemaMigrationError::RequiresReindex(_))
+        ));
+        assert_eq!(history.current_version(), 1);
+    }
+
+    #[test]
+    fn test_schema_history_readded_field() {
+        let mut history = SchemaHistory::new(test_schema(json!({})));
+
+        let removed = test_schema(json!({
+            "fields": {
+                "title": { "type": "text" },
+                "count": { "type": "u64", "indexed": true, "required": true },
+            },
+        }));
+        history.migrate(removed).expect("Removing a field is safe");
+
+        let readded = test_schema(json!({
+            "fields": {
+                "title": { "type": "text" },
+                "count": { "type": "u64", "indexed": true, "required": true },
+                "created": { "type": "i64", "fast": true },
+            },
+        }));
+        assert!(
+            !history.current().diff(&readded).unwrap().requires_reindex(),
+            "The diff against the current version alone is safe",
+        );
+        assert!(matches!(
+            history.migrate(readded),
+            Err(SchemaMigrationError::RequiresReindex(_))
+        ));
+        assert_eq!(history.current_version(), 1);
+
+        let readded = test_schema(json!({
+            "fields": {
+                "title": { "type": "text" },
+                "count": { "type": "u64", "indexed": true, "required": true },
+                "created": { "type": "date", "fast": true },
+            },
+        }));
+        let (version, _) = history
+            .migrate(readded)
+            .expect("Re-adding the same field is safe");
+        assert_eq!(version, 2);
+    }
+
+    #[test]
+    fn test_schema_history_bytes() {
+        let mut history = SchemaHistory::new(test_schema(json!({})));
+        let new = test_schema(json!({ "boosted_fields": { "title": 1.5 } }));
+        history.migrate(new.clone()).expect("Migrate schema");
+
+        let bytes = history.to_bytes().expect("Serialize history");
+        let loaded = SchemaHistory::from_bytes(&bytes).expect("Deserialize history");
+        assert_eq!(loaded.current_version(), 1);
+        assert_eq!(loaded.current(), &new);
+        assert_eq!(loaded.get(0), history.get(0));
+
+        assert!(SchemaHistory::from_bytes(b"{\"versions\":[]}").is_err());
+        assert!(SchemaHistory::from_bytes(b"not json").is_err());
+    }
+}
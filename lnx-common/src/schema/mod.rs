@@ -1,17 +1,74 @@
+mod analyzer;
+mod builder;
+mod document;
+mod dynamic;
 mod field;
+mod geo;
+mod migration;
+mod vector;
 mod writer;
 
 use std::collections::BTreeMap;
 
-pub use field::{BaseOptions, Field, NumericFieldOptions, TextOptions};
+pub use analyzer::{
+    AnalyzerOptions,
+    IndexRecord,
+    StemmerLanguage,
+    TokenFilter,
+    Tokenizer,
+};
+pub use builder::{tokenizer_name, SchemaBuildError, TantivySchemaBuilder};
+pub use document::{
+    DocumentError,
+    DocumentValidationError,
+    FieldError,
+    FieldValue,
+    ValidatedDocument,
+};
+pub use dynamic::{DynamicMapping, MappedDocument};
+pub use field::{
+    BaseOptions,
+    ComputedValue,
+    DateFieldOptions,
+    DateFormat,
+    DatePrecision,
+    DefaultValue,
+    Field,
+    NumericFieldOptions,
+    TextOptions,
+    VectorFieldOptions,
+};
+pub use geo::{
+    GeoCollector,
+    GeoDistanceSort,
+    GeoFilter,
+    GeoPoint,
+    GeoSegmentCollector,
+    SortOrder,
+};
+pub use migration::{
+    ChangeImpact,
+    SchemaChange,
+    SchemaChangeKind,
+    SchemaDiff,
+    SchemaHistory,
+    SchemaMigrationError,
+    SchemaVersion,
+};
 use tantivy::Score;
+use validator::Validate;
+pub use vector::{decode_vector, encode_vector, DistanceMetric, MAX_VECTOR_DIMS};
 pub use writer::WriterSettings;
 
 pub static RESERVED_DOCUMENT_ID_FIELD: &str = "_lnx_doc_id";
+/// The JSON field containing the values of schema-less indexes which
+/// could not be mapped to a typed field.
+pub static DYNAMIC_CATCH_ALL_FIELD: &str = "_lnx_dynamic";
 
 #[derive(
     Debug,
     Clone,
+    PartialEq,
     validator::Validate,
     serde::Serialize,
     serde::Deserialize,
@@ -35,6 +92,7 @@ pub struct Schema {
     pub name: String,
 
     #[serde(default)]
+    #[validate]
     /// Options and configuration for indexing documents.
     pub indexing_options: WriterSettings,
 
@@ -42,7 +100,8 @@ pub struct Schema {
     #[validate(custom = "validators::validate_schema_fields")]
     /// The schema's fields.
     ///
-    /// If this is set to `null` the system will become schema-less.
+    /// If this is set to `null` the system will become schema-less, and the
+    /// fields are inferred from the uploaded documents, see [DynamicMapping].
     /// This is not recommended for most production systems but is useful for
     /// prototyping.
     pub fields: Option<BTreeMap<String, Field>>,
@@ -66,13 +125,14 @@ pub struct Schema {
 
 mod validators {
     use std::borrow::Cow;
-    use std::collections::{BTreeMap, HashSet};
+    use std::collections::BTreeMap;
 
     use serde_json::{json, Value};
     use tantivy::Score;
     use validator::ValidationError;
 
-    use super::{Field, Schema};
+    use super::document::coerce_value;
+    use super::{Field, Schema, RESERVED_DOCUMENT_ID_FIELD};
 
     pub fn validate_schema(schema: &Schema) -> Result<(), ValidationError> {
         let fields = match &schema.fields {
@@ -82,46 +142,33 @@ mod validators {
 
         let mut error = ValidationError::new("bad_schema");
         error.message = Some(Cow::Borrowed(
-            "Search fields and boost fields must be defined within the `fields` property when not using the schema-less system.",
+            "Search fields and boost fields must be `text` fields defined within the \
+             `fields` property when not using the schema-less system.",
         ));
 
-        let keys = fields.keys().cloned().collect::<HashSet<String>>();
-
-        let param_key = Cow::Borrowed("search_fields");
         let mut is_fail = false;
-        for field_name in schema.search_fields.iter() {
-            if keys.contains(field_name) {
-                continue;
-            }
+        let properties = [
+            (
+                "search_fields",
+                schema.search_fields.iter().collect::<Vec<_>>(),
+            ),
+            ("boosted_fields", schema.boosted_fields.keys().collect()),
+        ];
+        for (param_key, field_names) in properties {
+            for field_name in field_names {
+                let reason = match fields.get(field_name) {
+                    Some(Field::Text(_)) => continue,
+                    Some(_) => "The field is not a searchable `text` field",
+                    None => "The field is not defined",
+                };
 
-            is_fail = true;
-            error
-                .params
-                .entry(param_key.clone())
-                .and_modify(|v| {
-                    if let Value::Array(inner) = v {
-                        inner.push(json!(field_name))
-                    }
-                })
-                .or_insert_with(|| json!(vec![field_name.clone()]));
-        }
-
-        let param_key = Cow::Borrowed("boost_fields");
-        for field_name in schema.search_fields.iter() {
-            if keys.contains(field_name) {
-                continue;
+                is_fail = true;
+                push_param(
+                    &mut error,
+                    param_key,
+                    json!({ "field": field_name, "reason": reason }),
+                );
             }
-
-            is_fail = true;
-            error
-                .params
-                .entry(param_key.clone())
-                .and_modify(|v| {
-                    if let Value::Array(inner) = v {
-                        inner.push(json!(field_name))
-                    }
-                })
-                .or_insert_with(|| json!(vec![field_name.clone()]));
         }
 
         if is_fail {
@@ -143,31 +190,25 @@ mod validators {
             return Err(error);
         }
 
+        if fields.contains_key(RESERVED_DOCUMENT_ID_FIELD) {
+            let mut error = ValidationError::new("reserved_field_name");
+            error.message = Some(Cow::Borrowed(
+                "The field name is reserved for internal use.",
+            ));
+            push_param(&mut error, "keys", json!(RESERVED_DOCUMENT_ID_FIELD));
+            return Err(error);
+        }
+
         error.message = Some(Cow::Borrowed(
-            "The field name must only contain alpha-numeric characters, `-` or `_` and must not start or end with `-` or `_`.",
+            "The field name must only contain alpha-numeric characters, `-` or `_` and \
+             must not start or end with `-` or `_`.",
         ));
 
-        let param_key = Cow::Borrowed("keys");
         let mut is_fail = false;
         for field_name in fields.keys() {
-            if field_name.starts_with('-')
-                || field_name.starts_with('_')
-                || field_name.ends_with('-')
-                || field_name.ends_with('_')
-                || field_name
-                    .chars()
-                    .any(|c| !(c.is_alphanumeric() || c == '-' || c == '_'))
-            {
+            if !is_valid_field_name(field_name) {
                 is_fail = true;
-                error
-                    .params
-                    .entry(param_key.clone())
-                    .and_modify(|v| {
-                        if let Value::Array(inner) = v {
-                            inner.push(json!(field_name))
-                        }
-                    })
-                    .or_insert_with(|| json!(vec![field_name.clone()]));
+                push_param(&mut error, "keys", json!(field_name));
             }
         }
 
@@ -175,9 +216,99 @@ mod validators {
             return Err(error);
         }
 
+        validate_defaults(fields)?;
+        validate_computed_fields(fields)
+    }
+
+    fn validate_defaults(
+        fields: &BTreeMap<String, Field>,
+    ) -> Result<(), ValidationError> {
+        let mut error = ValidationError::new("bad_default_value");
+        error.message = Some(Cow::Borrowed(
+            "The default value must be a valid value for the type of the field.",
+        ));
+
+        let mut is_fail = false;
+        for (field_name, field) in fields {
+            let default = match field.base_options().default.as_ref() {
+                Some(default) => default,
+                None => continue,
+            };
+
+            if let Err(e) = coerce_value(field, Value::from(default)) {
+                is_fail = true;
+                push_param(
+                    &mut error,
+                    "keys",
+                    json!({ "field": field_name, "reason": e.to_string() }),
+                );
+            }
+        }
+
+        if is_fail {
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    fn validate_computed_fields(
+        fields: &BTreeMap<String, Field>,
+    ) -> Result<(), ValidationError> {
+        let mut error = ValidationError::new("bad_computed_field");
+        error.message = Some(Cow::Borrowed(
+            "Computed fields must only reference other fields which are defined and \
+             not computed.",
+        ));
+
+        let mut is_fail = false;
+        for (field_name, field) in fields {
+            let compute = match field.base_options().compute.as_ref() {
+                Some(compute) => compute,
+                None => continue,
+            };
+
+            let sources = compute.source_fields();
+            let reason = if sources.is_empty() {
+                "The expression does not reference any fields"
+            } else if sources.iter().any(|source| !fields.contains_key(source)) {
+                "The expression references a field which is not defined"
+            } else if sources
+                .iter()
+                .any(|source| fields[source].base_options().compute.is_some())
+            {
+                "The expression references another computed field"
+            } else {
+                continue;
+            };
+
+            is_fail = true;
+            push_param(
+                &mut error,
+                "keys",
+                json!({ "field": field_name, "reason": reason }),
+            );
+        }
+
+        if is_fail {
+            return Err(error);
+        }
+
         Ok(())
     }
 
+    /// Is the name a valid field name which can be used in the schema.
+    pub fn is_valid_field_name(name: &str) -> bool {
+        !(name.is_empty()
+            || name.starts_with('-')
+            || name.starts_with('_')
+            || name.ends_with('-')
+            || name.ends_with('_')
+            || name
+                .chars()
+                .any(|c| !(c.is_alphanumeric() || c == '-' || c == '_')))
+    }
+
     pub fn validate_index_name(value: &str) -> Result<(), ValidationError> {
         let mut err = ValidationError::new("bad_index_name");
         err.params.insert(Cow::Borrowed("value"), json!(value));
@@ -211,25 +342,184 @@ mod validators {
     ) -> Result<(), ValidationError> {
         let mut error = ValidationError::new("bad_boost_factor");
         error.message = Some(Cow::Borrowed(
-            "The boosting multiplier must be greater than `0.0`.",
+            "The boosting multiplier must be a finite number greater than `0.0`.",
         ));
 
-        let param_key = Cow::Borrowed("fields");
-
+        let mut is_fail = false;
         for (field, &score) in map {
-            if score < 0.0f32 {
-                error
-                    .params
-                    .entry(param_key.clone())
-                    .and_modify(|v| {
-                        if let Value::Array(inner) = v {
-                            inner.push(json!(field))
-                        }
-                    })
-                    .or_insert_with(|| json!(vec![field.clone()]));
+            if score > 0.0 && score.is_finite() {
+                continue;
             }
+
+            is_fail = true;
+            push_param(&mut error, "fields", json!(field));
+        }
+
+        if is_fail {
+            return Err(error);
         }
 
         Ok(())
     }
+
+    /// Appends the value to the array parameter of the error.
+    fn push_param(error: &mut ValidationError, key: &'static str, value: Value) {
+        error
+            .params
+            .entry(Cow::Borrowed(key))
+            .and_modify(|v| {
+                if let Value::Array(inner) = v {
+                    inner.push(value.clone())
+                }
+            })
+            .or_insert_with(|| json!([value]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+    use super::*;
+
+    fn validate(value: serde_json::Value) -> Result<(), ValidationErrors> {
+        let mut schema = json!({
+            "name": "test-index",
+            "fields": {
+                "title": { "type": "text" },
+                "count": { "type": "u64", "indexed": true },
+            },
+        });
+        for (key, value) in value.as_object().expect("Object") {
+            schema[key] = value.clone();
+        }
+
+        serde_json::from_value::<Schema>(schema)
+            .expect("Deserialize schema")
+            .validate()
+    }
+
+    fn error_code(errors: &ValidationErrors, field: &str) -> String {
+        match errors.errors().get(field) {
+            Some(ValidationErrorsKind::Field(errors)) => errors[0].code.to_string(),
+            Some(ValidationErrorsKind::Struct(errors)) => {
+                let (_, kind) = errors.errors().iter().next().expect("Nested error");
+                match kind {
+                    ValidationErrorsKind::Field(errors) => errors[0].code.to_string(),
+                    other => panic!("Unexpected nested error {other:?}"),
+                }
+            },
+            other => panic!("Unexpected error for {field}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_valid_schema() {
+        validate(json!({
+            "search_fields": ["title"],
+            "boosted_fields": { "title": 1.5 },
+            "indexing_options": { "auto_commit_duration": "24h", "num_threads": 1 },
+        }))
+        .expect("Schema should be valid");
+    }
+
+    #[test]
+    fn test_search_and_boosted_fields() {
+        let err = validate(json!({
+            "search_fields": ["title", "missing", "count"],
+            "boosted_fields": { "count": 2.0 },
+        }))
+        .expect_err("Schema should be invalid");
+
+        let errors = err.field_errors();
+        let error = &errors["__all__"][0];
+        assert_eq!(error.code, "bad_schema");
+        assert_eq!(
+            error.params["search_fields"],
+            json!([
+                { "field": "missing", "reason": "The field is not defined" },
+                {
+                    "field": "count",
+                    "reason": "The field is not a searchable `text` field",
+                },
+            ]),
+        );
+        assert_eq!(error.params["boosted_fields"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_boost_factors() {
+        for boost in [json!(0.0), json!(-1.0), json!(f32::MAX as f64 * 2.0)] {
+            let err = validate(json!({ "boosted_fields": { "title": boost } }))
+                .expect_err("Schema should be invalid");
+            assert_eq!(error_code(&err, "boosted_fields"), "bad_boost_factor");
+        }
+    }
+
+    #[test]
+    fn test_reserved_field_name() {
+        let err = validate(json!({
+            "fields": { RESERVED_DOCUMENT_ID_FIELD: { "type": "u64", "indexed": true } },
+        }))
+        .expect_err("Schema should be invalid");
+        assert_eq!(error_code(&err, "fields"), "reserved_field_name");
+    }
+
+    #[test]
+    fn test_defaults_and_computed_fields() {
+        validate(json!({
+            "fields": {
+                "title": { "type": "text", "default": "Untitled" },
+                "count": { "type": "u64", "indexed": true, "default": 0 },
+                "all": {
+                    "type": "text",
+                    "multi": true,
+                    "compute": { "type": "copy_from", "fields": ["title", "count"] },
+                },
+            },
+        }))
+        .expect("Schema should be valid");
+
+        let err = validate(json!({
+            "fields": {
+                "count": { "type": "u64", "indexed": true, "default": -1 },
+            },
+        }))
+        .expect_err("Schema should be invalid");
+        assert_eq!(error_code(&err, "fields"), "bad_default_value");
+
+        for compute in [
+            json!({ "type": "lowercase", "field": "missing" }),
+            json!({ "type": "lowercase", "field": "lower" }),
+            json!({ "type": "concat", "fields": ["other"] }),
+            json!({ "type": "copy_from", "fields": [] }),
+        ] {
+            let err = validate(json!({
+                "fields": {
+                    "title": { "type": "text" },
+                    "lower": { "type": "text", "compute": compute },
+                    "other": {
+                        "type": "text",
+                        "compute": { "type": "lowercase", "field": "title" },
+                    },
+                },
+            }))
+            .expect_err("Schema should be invalid");
+            assert_eq!(error_code(&err, "fields"), "bad_computed_field");
+        }
+    }
+
+    #[test]
+    fn test_writer_settings() {
+        for options in [
+            json!({ "auto_commit_duration": "0s" }),
+            json!({ "auto_commit_duration": "25h" }),
+            json!({ "num_threads": 0, "memory_buffer_size": "100MB" }),
+        ] {
+            let err = validate(json!({ "indexing_options": options }))
+                .expect_err("Schema should be invalid");
+            assert_eq!(error_code(&err, "indexing_options"), "range");
+        }
+    }
 }
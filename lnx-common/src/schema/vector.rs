@@ -0,0 +1,170 @@
+use bytecheck::CheckBytes;
+use serde_json::Value;
+
+/// The maximum number of dimensions a vector field can have.
+pub const MAX_VECTOR_DIMS: usize = 4096;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[archive_attr(derive(CheckBytes))]
+#[serde(rename_all = "snake_case")]
+/// The metric used to compare the vectors of a field.
+pub enum DistanceMetric {
+    #[default]
+    /// The cosine of the angle between the vectors.
+    Cosine,
+    /// The dot product of the vectors.
+    ///
+    /// This is equivalent to `Cosine` when the vectors are already normalized.
+    Dot,
+    /// The euclidean distance between the vectors.
+    Euclidean,
+}
+
+impl DistanceMetric {
+    /// The similarity of two vectors with the same number of dimensions.
+    ///
+    /// A higher value is always more similar, euclidean distances are
+    /// converted to a similarity of `1 / (1 + distance)`.
+    pub fn similarity(&self, a: &[f32], b: &[f32]) -> f32 {
+        debug_assert_eq!(a.len(), b.len(), "Vectors must have the same dimensions");
+
+        match self {
+            DistanceMetric::Cosine => {
+                let norm = (dot(a, a) * dot(b, b)).sqrt();
+                if norm == 0.0 {
+                    0.0
+                } else {
+                    dot(a, b) / norm
+                }
+            },
+            DistanceMetric::Dot => dot(a, b),
+            DistanceMetric::Euclidean => {
+                let distance = a
+                    .iter()
+                    .zip(b)
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum::<f32>()
+                    .sqrt();
+                1.0 / (1.0 + distance)
+            },
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Parses a vector with the given number of dimensions from a JSON array.
+///
+/// Every value must be a finite number, and vectors compared by `Cosine`
+/// must not be all zeros as they have no direction.
+pub(super) fn parse_vector(
+    value: &Value,
+    dims: usize,
+    metric: DistanceMetric,
+) -> Result<Vec<f32>, String> {
+    let values = value
+        .as_array()
+        .ok_or_else(|| "Expected an array of numbers".to_string())?;
+
+    if values.len() != dims {
+        return Err(format!(
+            "Expected {dims} dimensions but got {}",
+            values.len()
+        ));
+    }
+
+    let vector = values
+        .iter()
+        .map(|value| {
+            value
+                .as_f64()
+                .map(|v| v as f32)
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| "Every dimension must be a finite number".to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if metric == DistanceMetric::Cosine && vector.iter().all(|v| *v == 0.0) {
+        return Err("Vectors compared by `cosine` cannot be all zeros".to_string());
+    }
+
+    Ok(vector)
+}
+
+/// Encodes the vector as little endian `f32`s, as stored in the bytes field.
+pub fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Decodes a vector encoded by [encode_vector].
+///
+/// Returns `None` if the length of the buffer is not a multiple of 4.
+pub fn decode_vector(bytes: &[u8]) -> Option<Vec<f32>> {
+    if !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let vector = bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect();
+
+    Some(vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        let a = [1.0, 0.0];
+        let b = [0.0, 2.0];
+        let c = [2.0, 0.0];
+
+        assert_eq!(DistanceMetric::Cosine.similarity(&a, &b), 0.0);
+        assert_eq!(DistanceMetric::Cosine.similarity(&a, &c), 1.0);
+        assert_eq!(DistanceMetric::Dot.similarity(&a, &c), 2.0);
+        assert_eq!(DistanceMetric::Euclidean.similarity(&a, &a), 1.0);
+        assert_eq!(DistanceMetric::Euclidean.similarity(&a, &c), 0.5);
+    }
+
+    #[test]
+    fn test_parse_vector() {
+        let vector = parse_vector(&json!([1, 0.5, -2]), 3, DistanceMetric::Cosine)
+            .expect("Parse vector");
+        assert_eq!(vector, [1.0, 0.5, -2.0]);
+
+        assert!(parse_vector(&json!([1, 2]), 3, DistanceMetric::Dot).is_err());
+        assert!(parse_vector(&json!([1, "2"]), 2, DistanceMetric::Dot).is_err());
+        assert!(parse_vector(&json!([1e300, 2]), 2, DistanceMetric::Dot).is_err());
+        assert!(parse_vector(&json!("1,2"), 2, DistanceMetric::Dot).is_err());
+        assert!(parse_vector(&json!([0, 0]), 2, DistanceMetric::Cosine).is_err());
+        assert!(parse_vector(&json!([0, 0]), 2, DistanceMetric::Euclidean).is_ok());
+    }
+
+    #[test]
+    fn test_encode_vector() {
+        let vector = vec![1.0, -0.25, 3.5];
+        let bytes = encode_vector(&vector);
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(decode_vector(&bytes), Some(vector));
+        assert_eq!(decode_vector(&bytes[..5]), None);
+    }
+}
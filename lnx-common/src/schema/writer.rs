@@ -5,11 +5,15 @@ const DEFAULT_BUFFER_SIZE: usize = 50 << 20;
 const BUFFER_SIZE_WARN_THRESHOLD_PER_THREAD: usize = 250 << 20;
 const BUFFER_HARD_CAP: u64 = 12 << 30;
 const AUTO_COMMIT_DEFAULT: u64 = 30;
+const AUTO_COMMIT_MIN: u64 = 1;
+const AUTO_COMMIT_MAX: u64 = 24 * 60 * 60;
 
 #[derive(
     Debug,
     Clone,
     Copy,
+    PartialEq,
+    validator::Validate,
     serde::Serialize,
     serde::Deserialize,
     rkyv::Archive,
@@ -18,13 +22,20 @@ const AUTO_COMMIT_DEFAULT: u64 = 30;
 )]
 #[serde(from = "WriterSettingsSchema")]
 pub struct WriterSettings {
+    #[validate(range(
+        min = "AUTO_COMMIT_MIN",
+        max = "AUTO_COMMIT_MAX",
+        message = "The auto commit duration must be between 1 second and 24 hours."
+    ))]
     /// The duration of inactivity to elapse before flushing a segment.
     pub auto_commit_duration: u64,
 
+    #[validate(range(min = 1, message = "At least one indexing thread is required."))]
     /// The number of indexing threads to use.
     pub num_threads: usize,
 
-    /// The size of the memory buffer to use in total across the number of indexing threads.
+    /// The size of the memory buffer to use in total across the number of
+    /// indexing threads.
     pub memory_buffer_size: usize,
 }
 
@@ -42,22 +53,27 @@ impl Default for WriterSettings {
 impl From<WriterSettingsSchema> for WriterSettings {
     fn from(schema: WriterSettingsSchema) -> Self {
         Self {
-            auto_commit_duration: schema.auto_commit_duration.unwrap_or(AUTO_COMMIT_DEFAULT),
+            auto_commit_duration: schema
+                .auto_commit_duration
+                .unwrap_or(AUTO_COMMIT_DEFAULT),
             num_threads: schema.num_threads,
-            memory_buffer_size: schema.memory_buffer_size
+            memory_buffer_size: schema
+                .memory_buffer_size
                 .map(|size| {
                     let required_size = DEFAULT_BUFFER_SIZE * schema.num_threads;
-                    let per_thread_size = size / schema.num_threads;
+                    // `num_threads` is validated separately, so avoid panicking here.
+                    let per_thread_size = size / schema.num_threads.max(1);
 
                     let total_pretty = humansize::format_size(size, humansize::DECIMAL);
-                    let per_thread_pretty = humansize::format_size(per_thread_size, humansize::DECIMAL);
+                    let per_thread_pretty =
+                        humansize::format_size(per_thread_size, humansize::DECIMAL);
 
                     if size < required_size {
                         warn!(
                             total_buffer_size = %total_pretty,
                             per_thread_buffer_size = %per_thread_pretty,
-                            "Total buffer size does not meet the minimum required size per thread. \
-                            Using default value."
+                            "Total buffer size does not meet the minimum required size \
+                             per thread. Using default value."
                         );
 
                         return required_size;
@@ -67,8 +83,8 @@ impl From<WriterSettingsSchema> for WriterSettings {
                         warn!(
                             total_buffer_size = %total_pretty,
                             per_thread_buffer_size = %per_thread_pretty,
-                            "The per-thread buffer size allocated is likely larger than required. \
-                            Recommend maximum per-thread is 500MB.",
+                            "The per-thread buffer size allocated is likely larger than \
+                             required. Recommend maximum per-thread is 500MB.",
                         )
                     }
 
@@ -119,19 +135,34 @@ impl WriterSettingsSchema {
     }
 }
 
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+/// A value given either as a plain number or a human readable string.
+///
+/// Plain numbers are what [WriterSettings] serializes to, so persisted
+/// settings can be read back.
+enum HumanValue {
+    Number(u64),
+    Human(String),
+}
+
 mod human_time_format {
     use serde::de::Error;
     use serde::{Deserialize, Deserializer};
 
+    use super::HumanValue;
+
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        let dur = s
-            .parse::<humantime::Duration>()
-            .map_err(Error::custom)?
-            .as_secs();
+        let dur = match HumanValue::deserialize(deserializer)? {
+            HumanValue::Number(secs) => secs,
+            HumanValue::Human(s) => s
+                .parse::<humantime::Duration>()
+                .map_err(Error::custom)?
+                .as_secs(),
+        };
 
         Ok(Some(dur))
     }
@@ -141,14 +172,19 @@ mod human_size_format {
     use serde::de::Error;
     use serde::{Deserialize, Deserializer};
 
-    use super::BUFFER_HARD_CAP;
+    use super::{HumanValue, BUFFER_HARD_CAP};
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        let n = parse_size::parse_size(&s).map_err(Error::custom)?;
+        let (s, n) = match HumanValue::deserialize(deserializer)? {
+            HumanValue::Number(n) => (n.to_string(), n),
+            HumanValue::Human(s) => {
+                let n = parse_size::parse_size(&s).map_err(Error::custom)?;
+                (s, n)
+            },
+        };
 
         if n > BUFFER_HARD_CAP {
             return Err(Error::custom(format!(
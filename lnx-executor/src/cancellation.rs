@@ -0,0 +1,124 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures::channel::oneshot;
+
+#[derive(Debug, Clone, Default)]
+/// A token passed to tasks which signals when they should stop early.
+///
+/// Tasks are expected to check the token periodically, i.e. between
+/// segments, and return early once it is cancelled.
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    /// Creates a new token with an optional deadline.
+    pub fn new(deadline: Option<Instant>) -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            deadline,
+        }
+    }
+
+    /// Cancels the task.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns if the task has been cancelled or its deadline has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.is_expired()
+    }
+
+    /// Returns if the deadline of the task has passed.
+    pub fn is_expired(&self) -> bool {
+        self.deadline
+            .map(|deadline| Instant::now() >= deadline)
+            .unwrap_or(false)
+    }
+
+    /// The deadline of the task if set.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+/// The result of a task executed on an executor pool.
+///
+/// Dropping the future cancels the task, if it has not yet started
+/// it will be skipped entirely.
+pub struct TaskFuture<T> {
+    rx: oneshot::Receiver<T>,
+    token: CancellationToken,
+}
+
+impl<T> TaskFuture<T> {
+    pub(crate) fn new(rx: oneshot::Receiver<T>, token: CancellationToken) -> Self {
+        Self { rx, token }
+    }
+
+    /// The cancellation token of the task.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl<T> Future for TaskFuture<T> {
+    /// `None` if the task was cancelled, expired or panicked.
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx).map(|v| v.ok())
+    }
+}
+
+impl<T> Drop for TaskFuture<T> {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_token_cancel() {
+        let token = CancellationToken::default();
+        assert!(!token.is_cancelled());
+
+        let cloned = token.clone();
+        cloned.cancel();
+        assert!(token.is_cancelled());
+        assert!(!token.is_expired());
+    }
+
+    #[test]
+    fn test_token_deadline() {
+        let token =
+            CancellationToken::new(Some(Instant::now() + Duration::from_secs(60)));
+        assert!(!token.is_cancelled());
+
+        let token =
+            CancellationToken::new(Some(Instant::now() - Duration::from_secs(1)));
+        assert!(token.is_expired());
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_drop_future_cancels() {
+        let (_tx, rx) = oneshot::channel::<()>();
+        let token = CancellationToken::default();
+        let fut = TaskFuture::new(rx, token.clone());
+        drop(fut);
+        assert!(token.is_cancelled());
+    }
+}
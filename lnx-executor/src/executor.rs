@@ -0,0 +1,570 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use once_cell::sync::OnceCell;
+use tokio::task::JoinHandle;
+
+use crate::cancellation::{CancellationToken, TaskFuture};
+use crate::metrics::{MetricsRegistry, PoolMetricsSnapshot};
+use crate::pools::{
+    get_threads,
+    spawn_runtime_pool,
+    spawn_thread_pool,
+    ExecutorPool,
+    RuntimePool,
+    DRAIN_CHECK_INTERVAL,
+};
+use crate::queue::{TaskOptions, TaskPriority};
+use crate::rebalance::{RebalanceConfig, Rebalancer};
+use crate::topology::{CpuPoolRole, ExecutorConfig, IoPoolRole, PoolConfig};
+
+/// The process wide executor used by the global functions.
+static GLOBAL_EXECUTOR: OnceCell<ExecutorInner> = OnceCell::new();
+
+fn global_inner() -> &'static ExecutorInner {
+    GLOBAL_EXECUTOR.get_or_init(ExecutorInner::default)
+}
+
+#[derive(Clone, Default)]
+/// A handle to a set of executor pools.
+///
+/// Handles either refer to the process wide executor used by the global
+/// functions, or an isolated executor created with [Executor::new] with its own
+/// threads and metrics, i.e. when embedding lnx or running multiple nodes in
+/// a single process.
+///
+/// The default handle refers to the process wide executor.
+pub struct Executor(ExecutorRef);
+
+#[derive(Clone, Default)]
+enum ExecutorRef {
+    #[default]
+    Global,
+    Owned(Arc<ExecutorInner>),
+}
+
+impl Executor {
+    /// Creates a new isolated executor with the given topology.
+    pub fn new(config: ExecutorConfig) -> anyhow::Result<Self> {
+        let inner = ExecutorInner::default();
+        inner.configure(config)?;
+        Ok(Self(ExecutorRef::Owned(Arc::new(inner))))
+    }
+
+    /// Creates a new isolated executor in the default configuration.
+    pub fn with_threads(num_threads: usize) -> anyhow::Result<Self> {
+        Self::new(ExecutorConfig::new(num_threads))
+    }
+
+    /// A handle to the process wide executor.
+    pub fn global() -> Self {
+        Self(ExecutorRef::Global)
+    }
+
+    fn inner(&self) -> &ExecutorInner {
+        match &self.0 {
+            ExecutorRef::Global => global_inner(),
+            ExecutorRef::Owned(inner) => inner,
+        }
+    }
+
+    /// Replaces the thread pools of the executor with the given topology.
+    ///
+    /// Existing thread pools complete their pending tasks before shutting down.
+    /// Runtime pools are only created if they do not already exist, replacing
+    /// them would cancel long-lived tasks such as actors and background loops.
+    pub fn reconfigure(&self, config: ExecutorConfig) -> anyhow::Result<()> {
+        self.inner().configure(config)
+    }
+
+    /// Creates the search and default pools with the given search share.
+    ///
+    /// The default pool is only created if it does not already exist.
+    pub(crate) fn resize(
+        &self,
+        num_threads: usize,
+        search_pct: f32,
+    ) -> anyhow::Result<()> {
+        self.inner().resize(num_threads, search_pct)
+    }
+
+    /// Creates the default pool with the given number of threads if
+    /// it does not already exist.
+    pub(crate) fn install_default_pool(&self, num_threads: usize) -> anyhow::Result<()> {
+        self.inner()
+            .install_default_pool(&PoolConfig::new(num_threads))
+    }
+
+    /// Replaces the search pool with a pool of the given number of threads.
+    pub(crate) fn resize_search_pool(&self, num_threads: usize) -> anyhow::Result<()> {
+        self.inner().resize_search_pool(num_threads)
+    }
+
+    /// Starts a rebalancer which resizes the executor's search and default pools.
+    pub fn start_rebalancer(
+        &self,
+        config: RebalanceConfig,
+    ) -> anyhow::Result<Rebalancer> {
+        Rebalancer::start(self.clone(), config)
+    }
+
+    /// Shuts down all pools of the executor.
+    ///
+    /// This waits up to `timeout` for pending tasks to complete, returning
+    /// `false` if any tasks were still pending. The worker threads of each pool
+    /// are stopped once this returns, and any further tasks submitted
+    /// to the executor will panic.
+    ///
+    /// This blocks the current thread.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        self.inner().shutdown(timeout)
+    }
+
+    /// Get the current search pool usage pct.
+    pub fn search_usage(&self) -> f32 {
+        self.inner()
+            .search
+            .load()
+            .as_ref()
+            .map(|pool| pool.get_threadpool_usage_pct())
+            .unwrap_or_default()
+    }
+
+    /// Reset the search pool usage metrics.
+    pub fn reset_search_usage(&self) {
+        if let Some(pool) = self.inner().search.load().as_ref() {
+            pool.reset_usage_metrics();
+        }
+    }
+
+    /// Get the current default pool usage pct.
+    pub fn default_usage(&self) -> f32 {
+        self.inner()
+            .default
+            .load()
+            .as_ref()
+            .map(|pool| pool.get_runtime_usage_pct())
+            .unwrap_or_default()
+    }
+
+    /// Reset the default pool usage metrics.
+    pub fn reset_default_usage(&self) {
+        if let Some(pool) = self.inner().default.load().as_ref() {
+            pool.reset_usage_metrics();
+        }
+    }
+
+    /// Get the average time search tasks have spent queued since the last reset.
+    pub fn search_queue_wait(&self) -> Duration {
+        self.inner()
+            .search
+            .load()
+            .as_ref()
+            .map(|pool| pool.get_average_queue_wait())
+            .unwrap_or_default()
+    }
+
+    /// Get the number of search tasks skipped due to being cancelled
+    /// before they started.
+    pub fn search_cancelled_tasks(&self) -> u64 {
+        self.inner()
+            .search
+            .load()
+            .as_ref()
+            .map(|pool| pool.num_cancelled_tasks())
+            .unwrap_or_default()
+    }
+
+    /// Get the number of search tasks which exceeded their deadline.
+    pub fn search_expired_tasks(&self) -> u64 {
+        self.inner()
+            .search
+            .load()
+            .as_ref()
+            .map(|pool| pool.num_expired_tasks())
+            .unwrap_or_default()
+    }
+
+    /// Set the weight of a tenant on the search pool.
+    pub fn set_search_tenant_weight(&self, tenant: impl Into<Arc<str>>, weight: u32) {
+        self.inner().search_pool().set_tenant_weight(tenant, weight)
+    }
+
+    /// Takes a snapshot of the metrics of every pool of this executor.
+    pub fn scrape_metrics(&self) -> Vec<PoolMetricsSnapshot> {
+        self.inner().metrics.scrape()
+    }
+
+    /// Execute an operation on the search pool.
+    pub fn execute_search<OP, T>(&self, op: OP) -> TaskFuture<T>
+    where
+        T: Send + 'static,
+        OP: FnOnce() -> T + Send + 'static,
+    {
+        self.execute_search_with(TaskOptions::default(), move |_| op())
+    }
+
+    /// Execute an operation on the search pool with the given scheduling options.
+    pub fn execute_search_with<OP, T>(
+        &self,
+        options: TaskOptions,
+        op: OP,
+    ) -> TaskFuture<T>
+    where
+        T: Send + 'static,
+        OP: FnOnce(&CancellationToken) -> T + Send + 'static,
+    {
+        self.inner().search_pool().execute_with(options, op)
+    }
+
+    /// Spawn an operation on the search pool.
+    pub fn schedule_search<OP>(&self, op: OP)
+    where
+        OP: FnOnce() + Send + 'static,
+    {
+        self.schedule_search_with(TaskOptions::default(), move |_| op());
+    }
+
+    /// Spawn an operation on the search pool with the given scheduling options.
+    pub fn schedule_search_with<OP>(
+        &self,
+        options: TaskOptions,
+        op: OP,
+    ) -> CancellationToken
+    where
+        OP: FnOnce(&CancellationToken) + Send + 'static,
+    {
+        self.inner().search_pool().schedule_with(options, op)
+    }
+
+    /// Execute an operation on the default pool.
+    pub fn spawn_task<OP, T>(&self, op: OP) -> JoinHandle<T>
+    where
+        T: Send + 'static,
+        OP: Future<Output = T> + Send + 'static,
+    {
+        self.inner().default_pool().spawn(op)
+    }
+
+    /// Execute an operation on the pool with the given role.
+    pub fn execute_on<OP, T>(
+        &self,
+        role: CpuPoolRole,
+        options: TaskOptions,
+        op: OP,
+    ) -> TaskFuture<T>
+    where
+        T: Send + 'static,
+        OP: FnOnce(&CancellationToken) -> T + Send + 'static,
+    {
+        let inner = self.inner();
+        let dedicated = inner.dedicated.load();
+        let pool = match role {
+            CpuPoolRole::Search => None,
+            CpuPoolRole::Indexing => dedicated.indexing.as_ref(),
+            CpuPoolRole::Compression => dedicated.compression.as_ref(),
+        };
+
+        match pool {
+            Some(pool) => pool.execute_with(options, op),
+            None if role == CpuPoolRole::Search => {
+                inner.search_pool().execute_with(options, op)
+            },
+            None => inner
+                .search_pool()
+                .execute_with(options.priority(TaskPriority::Background), op),
+        }
+    }
+
+    /// Spawn an async task on the pool with the given role.
+    pub fn spawn_on<F>(&self, role: IoPoolRole, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let inner = self.inner();
+        let dedicated = inner.dedicated.load();
+        let pool = match role {
+            IoPoolRole::Default => None,
+            IoPoolRole::ReplicationIo => dedicated.replication_io.as_ref(),
+        };
+
+        match pool {
+            Some(pool) => pool.spawn(fut),
+            None => inner.default_pool().spawn(fut),
+        }
+    }
+}
+
+#[derive(Default)]
+struct DedicatedPools {
+    indexing: Option<Arc<ExecutorPool>>,
+    compression: Option<Arc<ExecutorPool>>,
+    replication_io: Option<Arc<RuntimePool>>,
+}
+
+#[derive(Default)]
+struct ExecutorInner {
+    /// The executor pool used for searches only.
+    ///
+    /// This allows us to isolate load and prevent tail latencies
+    /// from becoming too high due to interference.
+    search: ArcSwapOption<ExecutorPool>,
+    /// The executor pool for any operations that are not searches.
+    ///
+    /// This typically includes compression, IO, etc...
+    default: ArcSwapOption<RuntimePool>,
+    /// Pools dedicated to a given role.
+    dedicated: ArcSwap<DedicatedPools>,
+    /// The metrics of each pool.
+    metrics: MetricsRegistry,
+}
+
+impl ExecutorInner {
+    fn search_pool(&self) -> Arc<ExecutorPool> {
+        self.search
+            .load_full()
+            .expect("Search executor pool should be initialised.")
+    }
+
+    fn default_pool(&self) -> Arc<RuntimePool> {
+        self.default
+            .load_full()
+            .expect("Default executor pool should be initialised.")
+    }
+
+    /// Creates the search and default pools with a given search executor pct.
+    fn resize(&self, num_threads: usize, search_pct: f32) -> anyhow::Result<()> {
+        if search_pct <= 10.0 {
+            bail!("Search executor percentage share must be above 10%");
+        }
+
+        let search_threads = get_threads(num_threads, search_pct / 100.0);
+        let default_threads = get_threads(num_threads, (100.0 - search_pct) / 100.0);
+
+        info!(
+            total_thread_allocation = num_threads,
+            search_threads = search_threads,
+            default_threads = default_threads,
+            "Creating threadpool."
+        );
+
+        self.install_default_pool(&PoolConfig::new(default_threads))?;
+        self.resize_search_pool(search_threads)
+    }
+
+    /// Replaces the search pool with a pool of the given number of threads.
+    fn resize_search_pool(&self, num_threads: usize) -> anyhow::Result<()> {
+        let search_pool =
+            spawn_thread_pool("search", &PoolConfig::new(num_threads), &self.metrics)?;
+        self.store_search_pool(search_pool);
+        Ok(())
+    }
+
+    /// Creates the default pool if it does not already exist.
+    ///
+    /// The default runtime runs long-lived tasks which never complete on their
+    /// own, so it is kept for the lifetime of the executor rather than replaced.
+    fn install_default_pool(&self, config: &PoolConfig) -> anyhow::Result<()> {
+        if let Some(existing) = self.default.load().as_ref() {
+            if existing.num_threads() != config.num_threads {
+                warn!(
+                    num_threads = existing.num_threads(),
+                    requested_threads = config.num_threads,
+                    "Default runtime pool is already running, keeping existing pool."
+                );
+            }
+            return Ok(());
+        }
+
+        let default_pool = spawn_runtime_pool("default", config, &self.metrics)?;
+        self.default.store(Some(Arc::new(default_pool)));
+        Ok(())
+    }
+
+    /// Creates and installs the pools described by the given config.
+    fn configure(&self, config: ExecutorConfig) -> anyhow::Result<()> {
+        info!(
+            search_threads = config.search.num_threads,
+            default_threads = config.default.num_threads,
+            indexing_threads = config.indexing.as_ref().map(|c| c.num_threads),
+            compression_threads = config.compression.as_ref().map(|c| c.num_threads),
+            replication_io_threads =
+                config.replication_io.as_ref().map(|c| c.num_threads),
+            "Creating executor topology."
+        );
+
+        // All thread pools are created upfront so a failure leaves the
+        // existing pools in place.
+        let search_pool = spawn_thread_pool("search", &config.search, &self.metrics)?;
+        let indexing = config
+            .indexing
+            .as_ref()
+            .map(|config| spawn_thread_pool("indexing", config, &self.metrics))
+            .transpose()?
+            .map(Arc::new);
+        let compression = config
+            .compression
+            .as_ref()
+            .map(|config| spawn_thread_pool("compression", config, &self.metrics))
+            .transpose()?
+            .map(Arc::new);
+
+        // Runtime pools are never replaced or removed once they exist.
+        let existing = self.dedicated.load_full();
+        let replication_io = match (&existing.replication_io, &config.replication_io) {
+            (Some(pool), _) => Some(pool.clone()),
+            (None, Some(config)) => Some(Arc::new(spawn_runtime_pool(
+                "replication-io",
+                config,
+                &self.metrics,
+            )?)),
+            (None, None) => None,
+        };
+        self.install_default_pool(&config.default)?;
+
+        self.store_search_pool(search_pool);
+
+        // Dedicated thread pools are kept alive by their pending tasks.
+        self.dedicated.store(Arc::new(DedicatedPools {
+            indexing,
+            compression,
+            replication_io,
+        }));
+
+        Ok(())
+    }
+
+    /// Replaces the current search pool with the given pool.
+    fn store_search_pool(&self, mut search_pool: ExecutorPool) {
+        // Tenant weights are kept across pool resizes.
+        if let Some(existing) = self.search.load().as_ref() {
+            search_pool.share_tenant_weights(existing);
+        }
+
+        // The old search pool is kept alive by its pending tasks, so it
+        // is shut down once the last task completes.
+        self.search.store(Some(Arc::new(search_pool)));
+    }
+
+    fn shutdown(&self, timeout: Duration) -> bool {
+        let dedicated = self.dedicated.swap(Arc::default());
+
+        let thread_pools = [
+            self.search.swap(None),
+            dedicated.indexing.clone(),
+            dedicated.compression.clone(),
+        ];
+        let runtime_pools = [self.default.swap(None), dedicated.replication_io.clone()];
+
+        let start = Instant::now();
+        let mut completed = false;
+        while start.elapsed() < timeout {
+            let num_pending = thread_pools
+                .iter()
+                .flatten()
+                .map(|pool| pool.num_pending_tasks())
+                .chain(
+                    runtime_pools
+                        .iter()
+                        .flatten()
+                        .map(|pool| pool.num_pending_tasks()),
+                )
+                .sum::<usize>();
+
+            if num_pending == 0 {
+                completed = true;
+                break;
+            }
+
+            std::thread::sleep(DRAIN_CHECK_INTERVAL);
+        }
+
+        if !completed {
+            warn!(
+                timeout = ?timeout,
+                "Executor shutdown timed out with tasks still pending.",
+            );
+        }
+
+        // Thread pools stop their workers once the last reference is dropped.
+        drop(thread_pools);
+        for pool in runtime_pools.into_iter().flatten() {
+            pool.shutdown();
+        }
+
+        completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_isolated_executors() -> anyhow::Result<()> {
+        let first = Executor::with_threads(2)?;
+        let second = Executor::with_threads(2)?;
+
+        assert_eq!(first.execute_search(|| 1).await, Some(1));
+        assert_eq!(first.spawn_task(async { 2 }).await?, 2);
+
+        let completed = |executor: &Executor| {
+            executor
+                .scrape_metrics()
+                .iter()
+                .map(|snapshot| snapshot.tasks_completed)
+                .sum::<u64>()
+        };
+        assert_eq!(completed(&first), 2);
+        assert_eq!(completed(&second), 0, "Metrics should be isolated");
+
+        assert!(first.shutdown(Duration::from_secs(5)));
+        assert!(second.shutdown(Duration::from_secs(5)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resize_keeps_default_runtime() -> anyhow::Result<()> {
+        let executor = Executor::with_threads(2)?;
+
+        // A long-lived task, i.e. an actor, running across the resize.
+        let (tx, rx) = futures::channel::oneshot::channel::<u64>();
+        let handle = executor.spawn_task(async move { rx.await.unwrap_or_default() });
+
+        executor.resize(4, 50.0)?;
+        executor.resize_search_pool(1)?;
+        executor.reconfigure(ExecutorConfig::new(8))?;
+
+        tx.send(1).expect("Task should still be running");
+        assert_eq!(handle.await?, 1);
+        assert_eq!(executor.execute_search(|| 2).await, Some(2));
+
+        assert!(executor.shutdown(Duration::from_secs(5)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_pending_tasks() -> anyhow::Result<()> {
+        let executor = Executor::with_threads(2)?;
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        executor.schedule_search(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            tx.send(()).unwrap();
+        });
+
+        assert!(executor.shutdown(Duration::from_secs(5)));
+        assert!(
+            rx.try_recv().is_ok(),
+            "Task should complete before shutdown"
+        );
+
+        Ok(())
+    }
+}
@@ -1,13 +1,49 @@
 #[macro_use]
 extern crate tracing;
 
+mod cancellation;
+mod executor;
+mod metrics;
 mod pools;
+mod queue;
+mod rebalance;
+mod topology;
 
+pub use cancellation::{CancellationToken, TaskFuture};
+pub use executor::Executor;
+pub use metrics::{
+    scrape_pool_metrics,
+    HistogramBucket,
+    HistogramSnapshot,
+    PoolMetricsSnapshot,
+};
 pub use pools::{
     build_default_pools,
     execute_search,
+    execute_search_with,
+    get_default_usage,
+    get_search_cancelled_tasks,
+    get_search_expired_tasks,
+    get_search_queue_wait,
     get_search_usage,
+    reset_default_usage,
     reset_search_usage,
     schedule_search,
+    schedule_search_with,
+    set_search_tenant_weight,
     spawn_task,
+    ExecutorPool,
+    RuntimePool,
+};
+pub use queue::{TaskOptions, TaskPriority};
+pub use rebalance::{start_rebalancer, RebalanceConfig, Rebalancer};
+pub use topology::{
+    execute_on,
+    install_executor,
+    spawn_on,
+    CpuPinning,
+    CpuPoolRole,
+    ExecutorConfig,
+    IoPoolRole,
+    PoolConfig,
 };
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+
+use crate::executor::Executor;
+
+/// The number of histogram buckets.
+///
+/// Each bucket `n` covers durations up to `2^n` microseconds, with
+/// the final bucket capturing anything beyond `2^25` (~33s).
+const NUM_BUCKETS: usize = 27;
+
+#[derive(Default)]
+/// The metrics of each named pool of an executor.
+///
+/// Metrics are kept per pool name rather than per pool instance, so they
+/// carry on accumulating when pools are rebuilt.
+pub(crate) struct MetricsRegistry {
+    pools: RwLock<BTreeMap<&'static str, Arc<PoolMetrics>>>,
+}
+
+impl MetricsRegistry {
+    /// Get the metrics for the pool with the given name, creating them
+    /// if they don't exist.
+    pub(crate) fn metrics_for(&self, name: &'static str) -> Arc<PoolMetrics> {
+        if let Some(metrics) = self.pools.read().get(name) {
+            return metrics.clone();
+        }
+
+        self.pools
+            .write()
+            .entry(name)
+            .or_insert_with(|| Arc::new(PoolMetrics::new(name)))
+            .clone()
+    }
+
+    /// Takes a snapshot of the metrics of every pool.
+    pub(crate) fn scrape(&self) -> Vec<PoolMetricsSnapshot> {
+        self.pools
+            .read()
+            .values()
+            .map(|metrics| metrics.snapshot())
+            .collect()
+    }
+}
+
+/// Takes a snapshot of the metrics of every pool of the global executor.
+///
+/// This is intended to be called periodically by a metrics exporter.
+pub fn scrape_pool_metrics() -> Vec<PoolMetricsSnapshot> {
+    Executor::global().scrape_metrics()
+}
+
+/// A histogram of durations with fixed power of two buckets.
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; NUM_BUCKETS],
+    sum_micros: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    /// Records a new observation.
+    pub(crate) fn record(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        self.buckets[bucket_index(micros)].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        let mut count = 0;
+        let buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| {
+                count += bucket.load(Ordering::Relaxed);
+                HistogramBucket {
+                    upper_bound: bucket_upper_bound(i),
+                    cumulative_count: count,
+                }
+            })
+            .collect();
+
+        HistogramSnapshot {
+            buckets,
+            count,
+            sum: Duration::from_micros(self.sum_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn bucket_index(micros: u64) -> usize {
+    if micros <= 1 {
+        return 0;
+    }
+
+    // Rounds up to the next power of two.
+    let index = (u64::BITS - (micros - 1).leading_zeros()) as usize;
+    index.min(NUM_BUCKETS - 1)
+}
+
+fn bucket_upper_bound(index: usize) -> Option<Duration> {
+    if index == NUM_BUCKETS - 1 {
+        return None;
+    }
+
+    Some(Duration::from_micros(1 << index))
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A single bucket of a [HistogramSnapshot].
+pub struct HistogramBucket {
+    /// The inclusive upper bound of the bucket, `None` being unbounded.
+    pub upper_bound: Option<Duration>,
+    /// The number of observations less than or equal to the upper bound.
+    pub cumulative_count: u64,
+}
+
+#[derive(Debug, Clone)]
+/// A point in time snapshot of a histogram.
+pub struct HistogramSnapshot {
+    /// The histogram buckets in ascending order.
+    pub buckets: Vec<HistogramBucket>,
+    /// The total number of observations.
+    pub count: u64,
+    /// The sum of all observations.
+    pub sum: Duration,
+}
+
+impl HistogramSnapshot {
+    /// Estimates the given quantile, returning the upper bound of the bucket
+    /// it falls within.
+    ///
+    /// Returns `None` if there are no observations or the quantile falls
+    /// within the unbounded bucket.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let target = (self.count as f64 * quantile.clamp(0.0, 1.0)).ceil() as u64;
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.cumulative_count >= target.max(1))
+            .and_then(|bucket| bucket.upper_bound)
+    }
+}
+
+/// The metrics of a single executor pool.
+pub(crate) struct PoolMetrics {
+    name: &'static str,
+    pub(crate) execution_time: Histogram,
+    pub(crate) queue_wait: Histogram,
+    pub(crate) queue_depth: AtomicU64,
+    pub(crate) tasks_submitted: AtomicU64,
+    pub(crate) tasks_completed: AtomicU64,
+    pub(crate) tasks_panicked: AtomicU64,
+    pub(crate) tasks_cancelled: AtomicU64,
+    pub(crate) tasks_expired: AtomicU64,
+}
+
+impl PoolMetrics {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            execution_time: Histogram::default(),
+            queue_wait: Histogram::default(),
+            queue_depth: AtomicU64::new(0),
+            tasks_submitted: AtomicU64::new(0),
+            tasks_completed: AtomicU64::new(0),
+            tasks_panicked: AtomicU64::new(0),
+            tasks_cancelled: AtomicU64::new(0),
+            tasks_expired: AtomicU64::new(0),
+        }
+    }
+
+    /// Marks a task as submitted and waiting in the queue.
+    pub(crate) fn task_submitted(&self) {
+        self.tasks_submitted.fetch_add(1, Ordering::Relaxed);
+        self.queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Marks a task as having left the queue after the given wait time.
+    pub(crate) fn task_started(&self, queue_wait: Duration) {
+        self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        self.queue_wait.record(queue_wait);
+    }
+
+    /// Marks a task as completed after the given execution time.
+    pub(crate) fn task_completed(&self, elapsed: Duration) {
+        self.tasks_completed.fetch_add(1, Ordering::Relaxed);
+        self.execution_time.record(elapsed);
+    }
+
+    fn snapshot(&self) -> PoolMetricsSnapshot {
+        PoolMetricsSnapshot {
+            pool_name: self.name,
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            tasks_submitted: self.tasks_submitted.load(Ordering::Relaxed),
+            tasks_completed: self.tasks_completed.load(Ordering::Relaxed),
+            tasks_panicked: self.tasks_panicked.load(Ordering::Relaxed),
+            tasks_cancelled: self.tasks_cancelled.load(Ordering::Relaxed),
+            tasks_expired: self.tasks_expired.load(Ordering::Relaxed),
+            execution_time: self.execution_time.snapshot(),
+            queue_wait: self.queue_wait.snapshot(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A point in time snapshot of the metrics of an executor pool.
+///
+/// All counters are cumulative since the process started.
+pub struct PoolMetricsSnapshot {
+    /// The name of the pool, i.e. `search`.
+    pub pool_name: &'static str,
+    /// The number of tasks currently waiting to be started.
+    pub queue_depth: u64,
+    /// The number of tasks submitted to the pool.
+    pub tasks_submitted: u64,
+    /// The number of tasks which ran to completion.
+    pub tasks_completed: u64,
+    /// The number of tasks which panicked.
+    pub tasks_panicked: u64,
+    /// The number of tasks skipped due to being cancelled before they started.
+    pub tasks_cancelled: u64,
+    /// The number of tasks which exceeded their deadline.
+    pub tasks_expired: u64,
+    /// The time spent executing each task.
+    pub execution_time: HistogramSnapshot,
+    /// The time each task spent waiting before it was started.
+    pub queue_wait: HistogramSnapshot,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_index() {
+        assert_eq!(bucket_index(0), 0);
+        assert_eq!(bucket_index(1), 0);
+        assert_eq!(bucket_index(2), 1);
+        assert_eq!(bucket_index(3), 2);
+        assert_eq!(bucket_index(4), 2);
+        assert_eq!(bucket_index(1_000), 10);
+        assert_eq!(bucket_index(u64::MAX), NUM_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_histogram_snapshot() {
+        let histogram = Histogram::default();
+        assert!(histogram.snapshot().quantile(0.5).is_none());
+
+        for _ in 0..9 {
+            histogram.record(Duration::from_micros(100));
+        }
+        histogram.record(Duration::from_millis(10));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 10);
+        assert_eq!(snapshot.sum, Duration::from_micros(10_900));
+        assert_eq!(snapshot.quantile(0.5), Some(Duration::from_micros(128)));
+        assert_eq!(snapshot.quantile(0.99), Some(Duration::from_micros(16_384)));
+        assert_eq!(snapshot.buckets.last().unwrap().cumulative_count, 10);
+    }
+}
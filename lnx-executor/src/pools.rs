@@ -1,69 +1,104 @@
 use std::any::Any;
 use std::cmp;
 use std::future::Future;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
 use std::time::{Duration, Instant};
 
-use anyhow::{bail, Context};
-use arc_swap::ArcSwap;
+use anyhow::Context;
 use futures::channel::oneshot;
-use futures::FutureExt;
-use once_cell::sync::OnceCell;
-use tokio::runtime::Runtime;
+use tokio::runtime::{Handle, Runtime};
 use tokio::task::JoinHandle;
 
-/// The executor pool used for searches only.
-///
-/// This allows us to isolate load and prevent tail latencies
-/// from becoming too high due to interference.
-static SEARCH_POOL: OnceCell<ArcSwap<ExecutorPool>> = OnceCell::new();
-/// The executor pool for any operations that are not searches.
-///
-/// This typically includes compression, IO, etc...
-static DEFAULT_POOL: OnceCell<ArcSwap<Runtime>> = OnceCell::new();
+use crate::cancellation::{CancellationToken, TaskFuture};
+use crate::executor::Executor;
+use crate::metrics::{MetricsRegistry, PoolMetrics};
+use crate::queue::{FairQueue, Job, TaskOptions};
+use crate::topology::{pin_current_thread, PoolConfig};
 
 /// The default ratio of other operations to CPU searches.
 ///
 /// This starts off at 25% other / 75% search to prevent
 /// search operations being impacted directly by things
 /// like indexing.
-const START_SEARCH_PCT: f32 = 75.0;
+pub(crate) const START_SEARCH_PCT: f32 = 75.0;
+
+/// The interval between checks of a pool's pending tasks during shutdown.
+pub(crate) const DRAIN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Creates the executor pools in their default configuration.
 pub fn build_default_pools(num_threads: usize) -> anyhow::Result<()> {
-    install_pools(num_threads, START_SEARCH_PCT)
+    Executor::global().resize(num_threads, START_SEARCH_PCT)
 }
 
 /// Get the current search pool usage pct.
 pub fn get_search_usage() -> f32 {
-    SEARCH_POOL
-        .get()
-        .map(|v: &ArcSwap<ExecutorPool>| {
-            let guard = v.load();
-            guard.get_threadpool_usage_pct()
-        })
-        .unwrap_or_default()
+    Executor::global().search_usage()
 }
 
 /// Reset the search pool usage metrics.
 pub fn reset_search_usage() {
-    if let Some(pool) = SEARCH_POOL.get() {
-        pool.load().reset_usage_metrics();
-    }
+    Executor::global().reset_search_usage()
+}
+
+/// Get the current default pool usage pct.
+pub fn get_default_usage() -> f32 {
+    Executor::global().default_usage()
+}
+
+/// Reset the default pool usage metrics.
+pub fn reset_default_usage() {
+    Executor::global().reset_default_usage()
+}
+
+/// Get the average time search tasks have spent queued since the last reset.
+pub fn get_search_queue_wait() -> Duration {
+    Executor::global().search_queue_wait()
+}
+
+/// Set the weight of a tenant on the search pool.
+///
+/// Tenants within the same priority class receive a share of the pool
+/// proportional to their weight, tenants default to a weight of `1`.
+pub fn set_search_tenant_weight(tenant: impl Into<Arc<str>>, weight: u32) {
+    Executor::global().set_search_tenant_weight(tenant, weight)
+}
+
+/// Get the number of search tasks skipped due to being cancelled before they started.
+pub fn get_search_cancelled_tasks() -> u64 {
+    Executor::global().search_cancelled_tasks()
+}
+
+/// Get the number of search tasks which exceeded their deadline.
+pub fn get_search_expired_tasks() -> u64 {
+    Executor::global().search_expired_tasks()
 }
 
 /// Execute an operation on the search pool.
-pub fn execute_search<OP, T>(op: OP) -> impl Future<Output = Option<T>>
+///
+/// Dropping the returned future cancels the operation if it has not yet started.
+pub fn execute_search<OP, T>(op: OP) -> TaskFuture<T>
 where
     T: Send + 'static,
     OP: FnOnce() -> T + Send + 'static,
 {
-    SEARCH_POOL
-        .get()
-        .expect("Search executor pool should be initialised.")
-        .load()
-        .execute(op)
+    Executor::global().execute_search(op)
+}
+
+/// Execute an operation on the search pool with the given scheduling options.
+///
+/// The operation is given a [CancellationToken] which should be checked
+/// periodically, the token is cancelled once the returned future is dropped
+/// or the deadline of the task has passed.
+pub fn execute_search_with<OP, T>(options: TaskOptions, op: OP) -> TaskFuture<T>
+where
+    T: Send + 'static,
+    OP: FnOnce(&CancellationToken) -> T + Send + 'static,
+{
+    Executor::global().execute_search_with(options, op)
 }
 
 /// Execute an operation on the default pool.
@@ -72,11 +107,7 @@ where
     T: Send + 'static,
     OP: Future<Output = T> + Send + 'static,
 {
-    DEFAULT_POOL
-        .get()
-        .expect("Default executor pool should be initialised.")
-        .load()
-        .spawn(op)
+    Executor::global().spawn_task(op)
 }
 
 /// Spawn an operation on the search pool.
@@ -84,45 +115,17 @@ pub fn schedule_search<OP>(op: OP)
 where
     OP: FnOnce() + Send + 'static,
 {
-    SEARCH_POOL
-        .get()
-        .expect("Search executor pool should be initialised.")
-        .load()
-        .schedule(op)
+    Executor::global().schedule_search(op)
 }
 
-/// Creates the executor pools with a given search executor pct.
-pub(crate) fn install_pools(num_threads: usize, search_pct: f32) -> anyhow::Result<()> {
-    if search_pct <= 10.0 {
-        bail!("Search executor percentage share must be above 10%");
-    }
-
-    let search_threads = get_threads(num_threads, search_pct / 100.0);
-    let default_threads = get_threads(num_threads, (100.0 - search_pct) / 100.0);
-
-    info!(
-        total_thread_allocation = num_threads,
-        search_threads = search_threads,
-        default_threads = default_threads,
-        "Creating threadpool."
-    );
-
-    let search_pool = spawn_thread_pool("search", search_threads)?;
-    let default_pool = spawn_runtime_pool("default", default_threads)?;
-
-    if let Some(existing) = SEARCH_POOL.get() {
-        existing.store(Arc::new(search_pool));
-    } else {
-        let _ = SEARCH_POOL.set(ArcSwap::from_pointee(search_pool));
-    }
-
-    if let Some(existing) = DEFAULT_POOL.get() {
-        existing.store(Arc::new(default_pool));
-    } else {
-        let _ = DEFAULT_POOL.set(ArcSwap::from_pointee(default_pool));
-    }
-
-    Ok(())
+/// Spawn an operation on the search pool with the given scheduling options.
+///
+/// The returned [CancellationToken] can be used to cancel the operation.
+pub fn schedule_search_with<OP>(options: TaskOptions, op: OP) -> CancellationToken
+where
+    OP: FnOnce(&CancellationToken) + Send + 'static,
+{
+    Executor::global().schedule_search_with(options, op)
 }
 
 /// A wrapper around a rayon threadpool that ensures
@@ -131,23 +134,33 @@ pub(crate) fn install_pools(num_threads: usize, search_pct: f32) -> anyhow::Resu
 /// This is done via attaching a reference to the pool for each task
 /// it owns, meaning it's self-referential as long as tasks are still
 /// pending.
+///
+/// Tasks are held in a fair queue in front of the pool, each task
+/// spawned on the pool executes the next task in the queue rather
+/// than the task that was submitted.
 pub struct ExecutorPool {
     pool: Arc<rayon::ThreadPool>,
+    queue: Arc<FairQueue>,
     start_instant: parking_lot::Mutex<Instant>,
     execution_time: Arc<AtomicU64>,
+    queue_wait_time: Arc<AtomicU64>,
+    num_dequeued: Arc<AtomicU64>,
+    metrics: Arc<PoolMetrics>,
 }
 
-impl From<rayon::ThreadPool> for ExecutorPool {
-    fn from(value: rayon::ThreadPool) -> Self {
+impl ExecutorPool {
+    fn new(pool: rayon::ThreadPool, metrics: Arc<PoolMetrics>) -> Self {
         Self {
-            pool: Arc::new(value),
+            pool: Arc::new(pool),
+            queue: Arc::new(FairQueue::default()),
             start_instant: parking_lot::Mutex::new(Instant::now()),
             execution_time: Arc::new(AtomicU64::new(0)),
+            queue_wait_time: Arc::new(AtomicU64::new(0)),
+            num_dequeued: Arc::new(AtomicU64::new(0)),
+            metrics,
         }
     }
-}
 
-impl ExecutorPool {
     /// Get the current average usage pct.
     pub fn get_threadpool_usage_pct(&self) -> f32 {
         let total_duration = self.start_instant.lock().elapsed();
@@ -167,110 +180,460 @@ impl ExecutorPool {
         (total_execution_time.as_secs_f32() / total_duration.as_secs_f32()) * 100.0
     }
 
+    /// Get the average time tasks have spent waiting in the queue.
+    ///
+    /// This is tracked separately from the execution time and is
+    /// not included in the usage pct.
+    pub fn get_average_queue_wait(&self) -> Duration {
+        let num_dequeued = self.num_dequeued.load(Ordering::Relaxed);
+        if num_dequeued == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_micros(
+            self.queue_wait_time.load(Ordering::Relaxed) / num_dequeued,
+        )
+    }
+
+    /// The number of tasks waiting to be executed.
+    pub fn queue_depth(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The number of tasks skipped due to being cancelled before they started.
+    pub fn num_cancelled_tasks(&self) -> u64 {
+        self.metrics.tasks_cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The number of tasks which exceeded their deadline.
+    ///
+    /// This includes tasks which expired while waiting in the queue
+    /// and tasks which completed after their deadline.
+    pub fn num_expired_tasks(&self) -> u64 {
+        self.metrics.tasks_expired.load(Ordering::Relaxed)
+    }
+
     /// Reset the current executor usage metrics.
     pub fn reset_usage_metrics(&self) {
         (*self.start_instant.lock()) = Instant::now();
         self.execution_time.store(0, Ordering::Relaxed);
+        self.queue_wait_time.store(0, Ordering::Relaxed);
+        self.num_dequeued.store(0, Ordering::Relaxed);
+    }
+
+    /// Set the weight of a tenant.
+    pub fn set_tenant_weight(&self, tenant: impl Into<Arc<str>>, weight: u32) {
+        self.queue.set_tenant_weight(tenant.into(), weight);
+    }
+
+    /// The number of tasks submitted to the pool which have not yet completed.
+    pub fn num_pending_tasks(&self) -> usize {
+        // Each pending task holds a reference to the pool.
+        Arc::strong_count(&self.pool) - 1
+    }
+
+    /// Shares the tenant weights of the given pool with this pool.
+    pub(crate) fn share_tenant_weights(&mut self, other: &Self) {
+        self.queue = Arc::new(FairQueue::with_shared_weights(&other.queue));
     }
 
     /// Executes a blocking task in the CPU executor and returns the result.
-    pub fn execute<OP, T>(&self, op: OP) -> impl Future<Output = Option<T>>
+    pub fn execute<OP, T>(&self, op: OP) -> TaskFuture<T>
     where
         T: Send + 'static,
         OP: FnOnce() -> T + Send + 'static,
+    {
+        self.execute_with(TaskOptions::default(), move |_| op())
+    }
+
+    /// Executes a blocking task in the CPU executor with the given
+    /// scheduling options and returns the result.
+    pub fn execute_with<OP, T>(&self, options: TaskOptions, op: OP) -> TaskFuture<T>
+    where
+        T: Send + 'static,
+        OP: FnOnce(&CancellationToken) -> T + Send + 'static,
     {
         trace!("Executing operation");
 
-        let guard = self.pool.clone();
-        let execution_time = self.execution_time.clone();
+        let token = CancellationToken::new(options.get_deadline());
 
         let (tx, rx) = oneshot::channel();
-        let callable = move || {
-            let start = Instant::now();
-
-            let result = (op)();
-            if tx.send(result).is_err() {
-                warn!("Executor completed task but receiver was already dropped, has the submitter crashed?");
+        let callable = move |token: &CancellationToken| {
+            let result = (op)(token);
+            if tx.send(result).is_err() && !token.is_cancelled() {
+                warn!(
+                    "Executor completed task but receiver was already dropped, \
+                     has the submitter crashed?"
+                );
             }
-
-            let elapsed = start.elapsed();
-            trace!(elapsed = ?elapsed, "Execute operation completed.");
-            execution_time.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
-
-            drop(guard);
         };
-        self.pool.spawn(callable);
+        self.submit(options, token.clone(), callable);
 
-        rx.map(|v| v.ok())
+        TaskFuture::new(rx, token)
     }
 
     /// Schedule a task to be executed some time in the near future.
     pub fn schedule<OP>(&self, op: OP)
     where
         OP: FnOnce() + Send + 'static,
+    {
+        self.schedule_with(TaskOptions::default(), move |_| op());
+    }
+
+    /// Schedule a task to be executed some time in the near future
+    /// with the given scheduling options.
+    ///
+    /// The returned [CancellationToken] can be used to cancel the task.
+    pub fn schedule_with<OP>(&self, options: TaskOptions, op: OP) -> CancellationToken
+    where
+        OP: FnOnce(&CancellationToken) + Send + 'static,
     {
         trace!("Scheduling operation");
 
+        let token = CancellationToken::new(options.get_deadline());
+        self.submit(options, token.clone(), op);
+        token
+    }
+
+    fn submit<OP>(&self, options: TaskOptions, token: CancellationToken, op: OP)
+    where
+        OP: FnOnce(&CancellationToken) + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let job: Job = Box::new(move || {
+            if token.is_expired() {
+                trace!("Skipping operation which expired before starting.");
+                metrics.tasks_expired.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            if token.is_cancelled() {
+                trace!("Skipping operation which was cancelled before starting.");
+                metrics.tasks_cancelled.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            let start = Instant::now();
+
+            (op)(&token);
+
+            metrics.task_completed(start.elapsed());
+            if token.is_expired() {
+                metrics.tasks_expired.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        self.metrics.task_submitted();
+        self.queue.push(options, job);
+
         let guard = self.pool.clone();
+        let queue = self.queue.clone();
         let execution_time = self.execution_time.clone();
+        let queue_wait_time = self.queue_wait_time.clone();
+        let num_dequeued = self.num_dequeued.clone();
+        let metrics = self.metrics.clone();
 
         let wrapper = move || {
+            // There is exactly one spawned wrapper per queued job.
+            let dequeued = match queue.pop() {
+                Some(dequeued) => dequeued,
+                None => return,
+            };
+
+            let queue_wait = dequeued.enqueued_at.elapsed();
+            queue_wait_time.fetch_add(queue_wait.as_micros() as u64, Ordering::Relaxed);
+            num_dequeued.fetch_add(1, Ordering::Relaxed);
+            metrics.task_started(queue_wait);
+
             let start = Instant::now();
 
-            (op)();
+            (dequeued.job)();
 
             let elapsed = start.elapsed();
-            trace!(elapsed = ?elapsed, "Scheduled operation completed.");
+            trace!(elapsed = ?elapsed, queue_wait = ?queue_wait, "Operation completed.");
             execution_time.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
 
+            drop(dequeued.ticket);
             drop(guard);
         };
         self.pool.spawn(wrapper)
     }
 }
 
-fn spawn_thread_pool(
-    nickname: &'static str,
+/// A wrapper around a tokio runtime that tracks the time spent
+/// polling its tasks and the number of tasks still pending.
+pub struct RuntimePool {
+    runtime: parking_lot::Mutex<Option<Runtime>>,
+    handle: Handle,
     num_threads: usize,
+    start_instant: parking_lot::Mutex<Instant>,
+    execution_time: Arc<AtomicU64>,
+    pending_tasks: Arc<AtomicUsize>,
+    metrics: Arc<PoolMetrics>,
+}
+
+impl RuntimePool {
+    fn new(runtime: Runtime, num_threads: usize, metrics: Arc<PoolMetrics>) -> Self {
+        Self {
+            handle: runtime.handle().clone(),
+            runtime: parking_lot::Mutex::new(Some(runtime)),
+            num_threads,
+            start_instant: parking_lot::Mutex::new(Instant::now()),
+            execution_time: Arc::new(AtomicU64::new(0)),
+            pending_tasks: Arc::new(AtomicUsize::new(0)),
+            metrics,
+        }
+    }
+
+    /// Get the current average usage pct.
+    pub fn get_runtime_usage_pct(&self) -> f32 {
+        let total_duration = self.start_instant.lock().elapsed();
+
+        // The total is derived from the execution time per thread.
+        let total_micros =
+            self.execution_time.load(Ordering::Relaxed) / self.num_threads as u64;
+
+        let total_execution_time = Duration::from_micros(total_micros);
+
+        if total_execution_time.as_secs_f32() <= 0.0
+            || total_duration.as_secs_f32() <= 0.0
+        {
+            return 0.0;
+        }
+
+        (total_execution_time.as_secs_f32() / total_duration.as_secs_f32()) * 100.0
+    }
+
+    /// Reset the current executor usage metrics.
+    pub fn reset_usage_metrics(&self) {
+        (*self.start_instant.lock()) = Instant::now();
+        self.execution_time.store(0, Ordering::Relaxed);
+    }
+
+    /// The number of tasks spawned on the pool which have not yet completed.
+    pub fn num_pending_tasks(&self) -> usize {
+        self.pending_tasks.load(Ordering::Relaxed)
+    }
+
+    /// The number of worker threads of the runtime.
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    /// Spawns a new task on the runtime.
+    pub fn spawn<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.pending_tasks.fetch_add(1, Ordering::Relaxed);
+        self.metrics.task_submitted();
+        let tracked = TrackedFuture {
+            inner: Box::pin(fut),
+            execution_time: self.execution_time.clone(),
+            metrics: self.metrics.clone(),
+            spawned_at: Instant::now(),
+            started: false,
+            task_execution_time: Duration::ZERO,
+            _guard: PendingTaskGuard(self.pending_tasks.clone()),
+        };
+
+        self.handle.spawn(tracked)
+    }
+
+    /// Shuts down the runtime, any pending tasks are cancelled.
+    pub(crate) fn shutdown(&self) {
+        // Shutting down in the background allows the pool to be dropped
+        // from within an async context.
+        if let Some(runtime) = self.runtime.lock().take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl Drop for RuntimePool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Decrements the pending task count once the task is dropped.
+struct PendingTaskGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingTaskGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A future which records the time spent polling the inner future.
+struct TrackedFuture<F> {
+    inner: Pin<Box<F>>,
+    execution_time: Arc<AtomicU64>,
+    metrics: Arc<PoolMetrics>,
+    spawned_at: Instant,
+    started: bool,
+    task_execution_time: Duration,
+    _guard: PendingTaskGuard,
+}
+
+impl<F: Future> Future for TrackedFuture<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        if !self.started {
+            self.started = true;
+            self.metrics.task_started(self.spawned_at.elapsed());
+        }
+
+        let start = Instant::now();
+        let result =
+            match panic::catch_unwind(AssertUnwindSafe(|| self.inner.as_mut().poll(cx)))
+            {
+                Ok(result) => result,
+                Err(error) => {
+                    self.metrics.tasks_panicked.fetch_add(1, Ordering::Relaxed);
+                    panic::resume_unwind(error);
+                },
+            };
+
+        let elapsed = start.elapsed();
+        self.task_execution_time += elapsed;
+        self.execution_time
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+
+        if result.is_ready() {
+            self.metrics.task_completed(self.task_execution_time);
+        }
+
+        result
+    }
+}
+
+impl<F> Drop for TrackedFuture<F> {
+    fn drop(&mut self) {
+        // The task was aborted or the runtime was shut down before it was polled.
+        if !self.started {
+            self.metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub(crate) fn spawn_thread_pool(
+    nickname: &'static str,
+    config: &PoolConfig,
+    registry: &MetricsRegistry,
 ) -> anyhow::Result<ExecutorPool> {
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(num_threads)
+    let metrics = registry.metrics_for(nickname);
+    let panic_metrics = metrics.clone();
+    let cores = config.resolve_cores()?;
+
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if let Some(stack_size) = config.stack_size {
+        builder = builder.stack_size(stack_size);
+    }
+
+    builder
+        .num_threads(config.num_threads)
         .thread_name(move |n| format!("lnx-executor-{nickname}-{n}"))
         .panic_handler(move |error: Box<dyn Any + Send>| {
+            panic_metrics.tasks_panicked.fetch_add(1, Ordering::Relaxed);
+
             if let Some(error) = error.downcast_ref::<&str>() {
-                error!(pool_name = nickname, error = %error, "Executor thread panicked while completing task.");
-                return
+                error!(
+                    pool_name = nickname,
+                    error = %error,
+                    "Executor thread panicked while completing task.",
+                );
+                return;
             }
 
             if let Some(error) = error.downcast_ref::<String>() {
-                error!(pool_name = nickname, error = %error, "Executor thread panicked while completing task.");
-                return
+                error!(
+                    pool_name = nickname,
+                    error = %error,
+                    "Executor thread panicked while completing task.",
+                );
+                return;
             }
 
-            error!(pool_name = nickname, error = "Unknown", "Executor thread panicked while completing task.");
+            error!(
+                pool_name = nickname,
+                error = "Unknown",
+                "Executor thread panicked while completing task.",
+            );
         })
         .start_handler(move |thread_id| {
-            debug!(pool_name = nickname, thread_id = thread_id, "Spawning worker thread.");
+            debug!(
+                pool_name = nickname,
+                thread_id = thread_id,
+                "Spawning worker thread.",
+            );
+            if let Some(cores) = cores.as_ref() {
+                pin_current_thread(nickname, cores);
+            }
         })
         .build()
         .context("Create executor pool")
-        .map(ExecutorPool::from)
+        .map(|pool| ExecutorPool::new(pool, metrics))
 }
 
-fn spawn_runtime_pool(
+pub(crate) fn spawn_runtime_pool(
     nickname: &'static str,
-    num_threads: usize,
-) -> anyhow::Result<Runtime> {
-    tokio::runtime::Builder::new_multi_thread()
+    config: &PoolConfig,
+    registry: &MetricsRegistry,
+) -> anyhow::Result<RuntimePool> {
+    let cores = config.resolve_cores()?;
+
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if let Some(stack_size) = config.stack_size {
+        builder.thread_stack_size(stack_size);
+    }
+    if let Some(cores) = cores {
+        builder.on_thread_start(move || pin_current_thread(nickname, &cores));
+    }
+
+    builder
         .thread_name(format!("lnx-executor-{nickname}"))
-        .worker_threads(num_threads)
+        .worker_threads(config.num_threads)
         .enable_all()
         .build()
         .context("Build runtime executor")
+        .map(|runtime| {
+            RuntimePool::new(runtime, config.num_threads, registry.metrics_for(nickname))
+        })
 }
 
-fn get_threads(num_total: usize, ratio: f32) -> usize {
+pub(crate) fn get_threads(num_total: usize, ratio: f32) -> usize {
     let num_threads = num_total as f32;
     let n_threads = (num_threads * ratio) as usize;
     cmp::max(n_threads, 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scrape_pool_metrics() -> anyhow::Result<()> {
+        build_default_pools(2)?;
+
+        let value = execute_search(|| 1).await;
+        assert_eq!(value, Some(1));
+        let value = spawn_task(async { 2 }).await?;
+        assert_eq!(value, 2);
+
+        let snapshots = crate::metrics::scrape_pool_metrics();
+        for pool_name in ["search", "default"] {
+            let snapshot = snapshots
+                .iter()
+                .find(|snapshot| snapshot.pool_name == pool_name)
+                .expect("Pool metrics should exist");
+            assert!(snapshot.tasks_completed >= 1);
+            assert!(snapshot.execution_time.count >= 1);
+            assert!(snapshot.queue_wait.count >= 1);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+
+/// A unit of work waiting in the queue.
+pub(crate) type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// The tenant a task is attributed to, `None` being the shared default tenant.
+type TenantKey = Option<Arc<str>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The priority class of a task.
+///
+/// Classes are served in strict priority order, a task is only
+/// started once there are no queued tasks of a higher class.
+pub enum TaskPriority {
+    #[default]
+    /// Latency sensitive tasks, i.e. user facing lookups.
+    Interactive,
+    /// Throughput orientated tasks, i.e. aggregations or exports.
+    Batch,
+    /// Maintenance tasks which should only use spare capacity.
+    Background,
+}
+
+impl TaskPriority {
+    const ALL: [Self; 3] = [Self::Interactive, Self::Batch, Self::Background];
+
+    fn index(self) -> usize {
+        match self {
+            Self::Interactive => 0,
+            Self::Batch => 1,
+            Self::Background => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The scheduling options of a given task.
+pub struct TaskOptions {
+    priority: TaskPriority,
+    tenant: TenantKey,
+    deadline: Option<Instant>,
+}
+
+impl TaskOptions {
+    /// Set the priority class of the task.
+    pub fn priority(mut self, priority: TaskPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set the tenant the task is attributed to.
+    ///
+    /// Tenants within the same priority class share the pool
+    /// according to their weights.
+    pub fn tenant(mut self, tenant: impl Into<Arc<str>>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Set the deadline of the task.
+    ///
+    /// The task is skipped if the deadline has passed before it starts.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the deadline of the task relative to now.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    pub(crate) fn get_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+/// A job which has been taken from the queue.
+pub(crate) struct DequeuedJob {
+    pub(crate) job: Job,
+    pub(crate) enqueued_at: Instant,
+    pub(crate) ticket: RunningTicket,
+}
+
+/// Charges the tenant for the time spent executing the job once dropped.
+///
+/// This is done on drop so tenants are still charged if the job panics.
+pub(crate) struct RunningTicket {
+    queue: Arc<FairQueue>,
+    priority: TaskPriority,
+    tenant: TenantKey,
+    start: Instant,
+}
+
+impl Drop for RunningTicket {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        self.queue
+            .complete(self.priority, self.tenant.take(), elapsed);
+    }
+}
+
+#[derive(Default)]
+/// A queue which orders tasks by priority class, then by weighted
+/// fair queueing between the tenants of each class.
+///
+/// Each tenant has a virtual time which advances by the time spent executing
+/// its tasks divided by its weight, the tenant with the lowest virtual time
+/// is served first.
+pub(crate) struct FairQueue {
+    classes: Mutex<[ClassQueue; 3]>,
+    weights: Arc<RwLock<HashMap<Arc<str>, u32>>>,
+}
+
+#[derive(Default)]
+struct ClassQueue {
+    virtual_time: u64,
+    tenants: HashMap<TenantKey, TenantQueue>,
+}
+
+#[derive(Default)]
+struct TenantQueue {
+    virtual_time: u64,
+    running: usize,
+    jobs: VecDeque<(Job, Instant)>,
+}
+
+impl TenantQueue {
+    fn is_idle(&self, class_virtual_time: u64) -> bool {
+        self.jobs.is_empty()
+            && self.running == 0
+            && self.virtual_time <= class_virtual_time
+    }
+}
+
+impl FairQueue {
+    /// Creates a new queue sharing the tenant weights of the given queue.
+    pub(crate) fn with_shared_weights(other: &Self) -> Self {
+        Self {
+            classes: Mutex::default(),
+            weights: other.weights.clone(),
+        }
+    }
+
+    /// Sets the weight of the given tenant, a higher weight
+    /// receives a larger share of the pool.
+    pub(crate) fn set_tenant_weight(&self, tenant: Arc<str>, weight: u32) {
+        self.weights.write().insert(tenant, weight.max(1));
+    }
+
+    /// Adds a new job to the queue.
+    pub(crate) fn push(&self, options: TaskOptions, job: Job) {
+        let mut classes = self.classes.lock();
+        let class = &mut classes[options.priority.index()];
+
+        let class_virtual_time = class.virtual_time;
+        let tenant = class.tenants.entry(options.tenant).or_default();
+
+        // A tenant which was idle must not be able to build up credit.
+        if tenant.jobs.is_empty() {
+            tenant.virtual_time = cmp::max(tenant.virtual_time, class_virtual_time);
+        }
+
+        tenant.jobs.push_back((job, Instant::now()));
+    }
+
+    /// Takes the next job to be executed.
+    pub(crate) fn pop(self: &Arc<Self>) -> Option<DequeuedJob> {
+        let mut classes = self.classes.lock();
+
+        for priority in TaskPriority::ALL {
+            let class = &mut classes[priority.index()];
+
+            let class_virtual_time = class.virtual_time;
+            class
+                .tenants
+                .retain(|_, tenant| !tenant.is_idle(class_virtual_time));
+
+            let next = class
+                .tenants
+                .iter_mut()
+                .filter(|(_, tenant)| !tenant.jobs.is_empty())
+                .min_by_key(|(_, tenant)| tenant.virtual_time);
+
+            let (key, tenant) = match next {
+                None => continue,
+                Some(next) => next,
+            };
+
+            let (job, enqueued_at) = tenant.jobs.pop_front()?;
+            tenant.running += 1;
+            let key = key.clone();
+
+            class.virtual_time = cmp::max(class.virtual_time, tenant.virtual_time);
+
+            return Some(DequeuedJob {
+                job,
+                enqueued_at,
+                ticket: RunningTicket {
+                    queue: self.clone(),
+                    priority,
+                    tenant: key,
+                    start: Instant::now(),
+                },
+            });
+        }
+
+        None
+    }
+
+    /// The number of jobs waiting in the queue.
+    pub(crate) fn len(&self) -> usize {
+        self.classes
+            .lock()
+            .iter()
+            .flat_map(|class| class.tenants.values())
+            .map(|tenant| tenant.jobs.len())
+            .sum()
+    }
+
+    fn complete(&self, priority: TaskPriority, tenant: TenantKey, elapsed: Duration) {
+        let weight = tenant
+            .as_ref()
+            .and_then(|tenant| self.weights.read().get(tenant).copied())
+            .unwrap_or(1);
+        let cost = cmp::max(elapsed.as_micros() as u64 / weight as u64, 1);
+
+        let mut classes = self.classes.lock();
+        let class = &mut classes[priority.index()];
+        if let Some(tenant) = class.tenants.get_mut(&tenant) {
+            tenant.running = tenant.running.saturating_sub(1);
+            tenant.virtual_time += cost;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_tagged(
+        queue: &FairQueue,
+        options: TaskOptions,
+        log: &Arc<Mutex<Vec<&'static str>>>,
+        tag: &'static str,
+    ) {
+        let log = log.clone();
+        queue.push(options, Box::new(move || log.lock().push(tag)));
+    }
+
+    /// Runs all queued jobs, charging each tenant the given cost.
+    fn drain(queue: &Arc<FairQueue>, cost: Duration) {
+        while let Some(mut dequeued) = queue.pop() {
+            (dequeued.job)();
+            dequeued.ticket.start = Instant::now() - cost;
+        }
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let queue = Arc::new(FairQueue::default());
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let background = TaskOptions::default().priority(TaskPriority::Background);
+        let batch = TaskOptions::default().priority(TaskPriority::Batch);
+        push_tagged(&queue, background, &log, "background");
+        push_tagged(&queue, batch, &log, "batch");
+        push_tagged(&queue, TaskOptions::default(), &log, "interactive");
+
+        assert_eq!(queue.len(), 3);
+        drain(&queue, Duration::from_millis(1));
+        assert_eq!(queue.len(), 0);
+        assert_eq!(*log.lock(), ["interactive", "batch", "background"]);
+    }
+
+    #[test]
+    fn test_weighted_tenants() {
+        let queue = Arc::new(FairQueue::default());
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        queue.set_tenant_weight("heavy".into(), 1);
+        queue.set_tenant_weight("light".into(), 2);
+
+        for _ in 0..4 {
+            push_tagged(
+                &queue,
+                TaskOptions::default().tenant("heavy"),
+                &log,
+                "heavy",
+            );
+        }
+        for _ in 0..4 {
+            push_tagged(
+                &queue,
+                TaskOptions::default().tenant("light"),
+                &log,
+                "light",
+            );
+        }
+
+        drain(&queue, Duration::from_millis(10));
+
+        // The light tenant has twice the weight so is served twice as often.
+        let log = log.lock();
+        let light_in_first_six = log[..6].iter().filter(|tag| **tag == "light").count();
+        assert_eq!(light_in_first_six, 4, "Unexpected order {log:?}");
+    }
+}
@@ -0,0 +1,246 @@
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+
+use crate::executor::Executor;
+use crate::pools::{get_threads, START_SEARCH_PCT};
+
+#[derive(Debug, Clone)]
+/// The configuration of the executor pool rebalancer.
+pub struct RebalanceConfig {
+    /// The total number of threads shared between the search and default pools.
+    pub total_threads: usize,
+    /// The minimum share of threads given to the search pool.
+    ///
+    /// This must be above 10%.
+    pub min_search_pct: f32,
+    /// The maximum share of threads given to the search pool.
+    pub max_search_pct: f32,
+    /// The amount the search pool share is adjusted by on each resize.
+    pub step_pct: f32,
+    /// The minimum difference in utilisation between the two pools
+    /// before a resize is considered.
+    pub threshold_pct: f32,
+    /// The interval between each utilisation sample.
+    pub interval: Duration,
+}
+
+impl RebalanceConfig {
+    /// Creates a new config with the default bounds for the given number of threads.
+    pub fn new(total_threads: usize) -> Self {
+        Self {
+            total_threads,
+            min_search_pct: 50.0,
+            max_search_pct: 90.0,
+            step_pct: 5.0,
+            threshold_pct: 20.0,
+            interval: Duration::from_secs(30),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.total_threads == 0 {
+            bail!("Total threads must be above 0");
+        }
+        if self.min_search_pct <= 10.0 {
+            bail!("Minimum search executor percentage share must be above 10%");
+        }
+        if self.max_search_pct >= 100.0 {
+            bail!("Maximum search executor percentage share must be below 100%");
+        }
+        if self.min_search_pct > self.max_search_pct {
+            bail!(
+                "Minimum search executor percentage share must not exceed the maximum"
+            );
+        }
+        if self.step_pct <= 0.0 {
+            bail!("Rebalance step must be above 0%");
+        }
+        if self.interval.is_zero() {
+            bail!("Rebalance interval must be above 0");
+        }
+        Ok(())
+    }
+
+    /// Calculates the next search pool share from the sampled utilisation of each pool.
+    fn next_search_pct(
+        &self,
+        current: f32,
+        search_usage: f32,
+        default_usage: f32,
+    ) -> f32 {
+        let delta = search_usage - default_usage;
+
+        let next = if delta >= self.threshold_pct {
+            current + self.step_pct
+        } else if -delta >= self.threshold_pct {
+            current - self.step_pct
+        } else {
+            current
+        };
+
+        next.clamp(self.min_search_pct, self.max_search_pct)
+    }
+}
+
+/// A handle to the background pool rebalancer.
+///
+/// The rebalancer is stopped when the handle is dropped.
+pub struct Rebalancer {
+    stop_tx: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Rebalancer {
+    /// Resizes the executor's search pool to the initial configuration and
+    /// starts the rebalancer thread.
+    ///
+    /// If the executor has no default pool yet, one is created with the largest
+    /// share the rebalancer can give it, its usage is then capped by the tasks
+    /// submitted to it rather than by resizing it.
+    pub(crate) fn start(
+        executor: Executor,
+        config: RebalanceConfig,
+    ) -> anyhow::Result<Self> {
+        config.validate()?;
+
+        let initial_pct =
+            START_SEARCH_PCT.clamp(config.min_search_pct, config.max_search_pct);
+        executor.install_default_pool(get_threads(
+            config.total_threads,
+            (100.0 - config.min_search_pct) / 100.0,
+        ))?;
+        executor.resize_search_pool(get_threads(
+            config.total_threads,
+            initial_pct / 100.0,
+        ))?;
+
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let handle = std::thread::Builder::new()
+            .name("lnx-executor-rebalancer".to_string())
+            .spawn(move || run_rebalancer(executor, config, initial_pct, stop_rx))
+            .context("Spawn rebalancer thread")?;
+
+        Ok(Self {
+            stop_tx: Some(stop_tx),
+            handle: Some(handle),
+        })
+    }
+
+    /// Stops the rebalancer and waits for it to exit.
+    ///
+    /// The pools are left in their current configuration.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(tx) = self.stop_tx.take() {
+            let _ = tx.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Rebalancer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Installs the global executor pools and starts a background thread which
+/// periodically samples the utilisation of the search and default pools,
+/// resizing the search pool within the configured bounds.
+///
+/// Only the search pool is resized, pending tasks on an old search pool run
+/// to completion before its threads are shut down. The default runtime is
+/// never replaced as it runs long-lived tasks such as actors and listeners.
+pub fn start_rebalancer(config: RebalanceConfig) -> anyhow::Result<Rebalancer> {
+    Executor::global().start_rebalancer(config)
+}
+
+fn run_rebalancer(
+    executor: Executor,
+    config: RebalanceConfig,
+    initial_pct: f32,
+    stop_rx: mpsc::Receiver<()>,
+) {
+    let mut search_pct = initial_pct;
+
+    executor.reset_search_usage();
+    executor.reset_default_usage();
+
+    while let Err(mpsc::RecvTimeoutError::Timeout) =
+        stop_rx.recv_timeout(config.interval)
+    {
+        let search_usage = executor.search_usage();
+        let default_usage = executor.default_usage();
+        executor.reset_search_usage();
+        executor.reset_default_usage();
+
+        let next_pct = config.next_search_pct(search_pct, search_usage, default_usage);
+
+        trace!(
+            search_usage = search_usage,
+            default_usage = default_usage,
+            search_pct = search_pct,
+            next_search_pct = next_pct,
+            "Sampled executor pool usage."
+        );
+
+        let threads_changed = get_threads(config.total_threads, search_pct / 100.0)
+            != get_threads(config.total_threads, next_pct / 100.0);
+        if !threads_changed {
+            continue;
+        }
+
+        info!(
+            search_usage = search_usage,
+            default_usage = default_usage,
+            old_search_pct = search_pct,
+            new_search_pct = next_pct,
+            "Rebalancing executor pools."
+        );
+
+        let search_threads = get_threads(config.total_threads, next_pct / 100.0);
+        match executor.resize_search_pool(search_threads) {
+            Ok(()) => search_pct = next_pct,
+            Err(e) => error!(error = ?e, "Failed to rebalance executor pools."),
+        }
+    }
+
+    debug!("Executor pool rebalancer stopped.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_search_pct() {
+        let config = RebalanceConfig::new(16);
+
+        assert_eq!(config.next_search_pct(75.0, 50.0, 40.0), 75.0);
+        assert_eq!(config.next_search_pct(75.0, 90.0, 10.0), 80.0);
+        assert_eq!(config.next_search_pct(75.0, 10.0, 90.0), 70.0);
+        assert_eq!(config.next_search_pct(90.0, 90.0, 10.0), 90.0);
+        assert_eq!(config.next_search_pct(50.0, 10.0, 90.0), 50.0);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(RebalanceConfig::new(16).validate().is_ok());
+
+        let mut config = RebalanceConfig::new(16);
+        config.min_search_pct = 5.0;
+        assert!(config.validate().is_err());
+
+        let mut config = RebalanceConfig::new(16);
+        config.min_search_pct = 95.0;
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use tokio::task::JoinHandle;
+
+use crate::cancellation::{CancellationToken, TaskFuture};
+use crate::executor::Executor;
+use crate::pools::{get_threads, START_SEARCH_PCT};
+use crate::queue::TaskOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The role of a pool executing blocking, CPU bound operations.
+pub enum CpuPoolRole {
+    /// Search operations.
+    Search,
+    /// Building and merging index segments.
+    Indexing,
+    /// Compressing and decompressing blocks.
+    Compression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The role of a pool executing async operations.
+pub enum IoPoolRole {
+    /// Any operations without a dedicated pool.
+    Default,
+    /// Streaming fragments and documents between nodes.
+    ReplicationIo,
+}
+
+#[cfg(target_os = "linux")]
+/// The number of cores which can be represented by a CPU set.
+const MAX_CPU_CORES: usize = libc::CPU_SETSIZE as usize;
+
+#[cfg(not(target_os = "linux"))]
+const MAX_CPU_CORES: usize = usize::MAX;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The CPUs the threads of a pool are allowed to run on.
+///
+/// Pinning is only supported on Linux and is ignored on other platforms.
+pub enum CpuPinning {
+    /// Pin threads to the given set of logical cores.
+    Cores(Vec<usize>),
+    /// Pin threads to the cores of the given NUMA node.
+    NumaNode(usize),
+}
+
+impl CpuPinning {
+    /// Resolves the set of cores the pool should be pinned to.
+    fn resolve_cores(&self) -> anyhow::Result<Vec<usize>> {
+        let cores = match self {
+            Self::Cores(cores) => cores.clone(),
+            Self::NumaNode(node) => numa_node_cores(*node)?,
+        };
+
+        if cores.is_empty() {
+            bail!("CPU pinning must contain at least one core");
+        }
+
+        if let Some(core) = cores.iter().find(|&&core| core >= MAX_CPU_CORES) {
+            bail!(
+                "CPU pinning core {core} is out of range, cores must be less \
+                 than {MAX_CPU_CORES}"
+            );
+        }
+
+        Ok(cores)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The configuration of a single executor pool.
+pub struct PoolConfig {
+    /// The number of worker threads.
+    pub num_threads: usize,
+    /// The stack size of each worker thread in bytes.
+    ///
+    /// Defaults to the pool implementation's default if not set.
+    pub stack_size: Option<usize>,
+    /// The CPUs the worker threads are pinned to.
+    pub pinning: Option<CpuPinning>,
+}
+
+impl PoolConfig {
+    /// Creates a new pool config with the given number of threads.
+    pub fn new(num_threads: usize) -> Self {
+        Self {
+            num_threads,
+            stack_size: None,
+            pinning: None,
+        }
+    }
+
+    /// Resolves the set of cores the pool should be pinned to, if any.
+    pub(crate) fn resolve_cores(&self) -> anyhow::Result<Option<Arc<Vec<usize>>>> {
+        if self.num_threads == 0 {
+            bail!("Executor pools must have at least one thread");
+        }
+
+        match self.pinning.as_ref() {
+            None => Ok(None),
+            Some(_) if !cfg!(target_os = "linux") => {
+                warn!("CPU pinning is only supported on Linux, ignoring.");
+                Ok(None)
+            },
+            Some(pinning) => pinning.resolve_cores().map(Arc::new).map(Some),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The topology of the executor pools.
+///
+/// Roles without a dedicated pool fall back to a shared pool, CPU bound
+/// roles run on the search pool with
+/// [TaskPriority::Background](crate::TaskPriority::Background) and async
+/// roles run on the default pool.
+pub struct ExecutorConfig {
+    /// The pool used for searches.
+    pub search: PoolConfig,
+    /// The pool used for any async operations without a dedicated pool.
+    pub default: PoolConfig,
+    /// A dedicated pool for indexing.
+    pub indexing: Option<PoolConfig>,
+    /// A dedicated pool for compression.
+    pub compression: Option<PoolConfig>,
+    /// A dedicated pool for replication IO.
+    pub replication_io: Option<PoolConfig>,
+}
+
+impl ExecutorConfig {
+    /// Creates a config with the default search and default pool
+    /// split of the given number of threads and no dedicated pools.
+    pub fn new(num_threads: usize) -> Self {
+        Self {
+            search: PoolConfig::new(get_threads(num_threads, START_SEARCH_PCT / 100.0)),
+            default: PoolConfig::new(get_threads(
+                num_threads,
+                (100.0 - START_SEARCH_PCT) / 100.0,
+            )),
+            indexing: None,
+            compression: None,
+            replication_io: None,
+        }
+    }
+}
+
+/// Creates and installs the global executor pools described by the given config.
+///
+/// Existing thread pools are replaced once their pending tasks complete,
+/// existing runtime pools are kept.
+pub fn install_executor(config: ExecutorConfig) -> anyhow::Result<()> {
+    Executor::global().reconfigure(config)
+}
+
+/// Execute an operation on the global pool with the given role.
+pub fn execute_on<OP, T>(
+    role: CpuPoolRole,
+    options: TaskOptions,
+    op: OP,
+) -> TaskFuture<T>
+where
+    T: Send + 'static,
+    OP: FnOnce(&CancellationToken) -> T + Send + 'static,
+{
+    Executor::global().execute_on(role, options, op)
+}
+
+/// Spawn an async task on the global pool with the given role.
+pub fn spawn_on<F>(role: IoPoolRole, fut: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Executor::global().spawn_on(role, fut)
+}
+
+#[cfg(target_os = "linux")]
+/// Reads the cores belonging to the given NUMA node.
+fn numa_node_cores(node: usize) -> anyhow::Result<Vec<usize>> {
+    let path = format!("/sys/devices/system/node/node{node}/cpulist");
+    let cpulist = std::fs::read_to_string(&path)
+        .with_context(|| format!("Read NUMA node {node} cpu list"))?;
+    parse_cpu_list(&cpulist)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn numa_node_cores(_node: usize) -> anyhow::Result<Vec<usize>> {
+    bail!("NUMA pinning is only supported on Linux")
+}
+
+/// Parses a kernel CPU list, i.e. `0-3,8,10-11`.
+fn parse_cpu_list(cpulist: &str) -> anyhow::Result<Vec<usize>> {
+    let mut cores = Vec::new();
+    for part in cpulist.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = start.parse().context("Parse CPU list")?;
+                let end: usize = end.parse().context("Parse CPU list")?;
+                cores.extend(start..=end);
+            },
+            None => cores.push(part.parse().context("Parse CPU list")?),
+        }
+    }
+    Ok(cores)
+}
+
+#[cfg(target_os = "linux")]
+/// Pins the current thread to the given set of cores.
+pub(crate) fn pin_current_thread(pool_name: &str, cores: &[usize]) {
+    // SAFETY: The cpu set is zero initialised and only modified via the libc macros.
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &core in cores {
+            libc::CPU_SET(core, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+
+    if result != 0 {
+        warn!(
+            pool_name = pool_name,
+            error = ?std::io::Error::last_os_error(),
+            "Failed to pin executor thread to cores.",
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn pin_current_thread(_pool_name: &str, _cores: &[usize]) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(
+            parse_cpu_list("0-3,8,10-11\n").unwrap(),
+            [0, 1, 2, 3, 8, 10, 11]
+        );
+        assert_eq!(parse_cpu_list("5").unwrap(), [5]);
+        assert!(parse_cpu_list("a-b").is_err());
+    }
+
+    #[test]
+    fn test_pinning_rejects_out_of_range_cores() {
+        let pinning = CpuPinning::Cores(vec![0, MAX_CPU_CORES]);
+        assert!(pinning.resolve_cores().is_err());
+
+        let pinning = CpuPinning::Cores(vec![0, MAX_CPU_CORES - 1]);
+        assert_eq!(pinning.resolve_cores().unwrap(), [0, MAX_CPU_CORES - 1]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_on_role() -> anyhow::Result<()> {
+        let mut config = ExecutorConfig::new(2);
+        config.indexing = Some(PoolConfig::new(1));
+        config.replication_io = Some(PoolConfig {
+            num_threads: 1,
+            stack_size: Some(4 << 20),
+            pinning: None,
+        });
+        install_executor(config)?;
+
+        let value =
+            execute_on(CpuPoolRole::Indexing, TaskOptions::default(), |_| 1).await;
+        assert_eq!(value, Some(1));
+        let value =
+            execute_on(CpuPoolRole::Compression, TaskOptions::default(), |_| 2).await;
+        assert_eq!(value, Some(2));
+        let value = spawn_on(IoPoolRole::ReplicationIo, async { 3 }).await?;
+        assert_eq!(value, 3);
+
+        Ok(())
+    }
+}
@@ -1 +1 @@
-
+pub mod vector;
@@ -0,0 +1,151 @@
+use std::io;
+
+use lnx_storage::{FragmentReader, LnxStorageHandle};
+
+use super::{DocId, HnswIndex, ScoredDoc, VectorIndexError};
+
+/// Persists the index of a vector field in the given fragment.
+///
+/// This must be called before the fragment is committed, the index is then
+/// replicated along with the rest of the fragment.
+pub async fn write_fragment_index(
+    storage: &LnxStorageHandle,
+    fragment_id: u64,
+    field: &str,
+    index: &HnswIndex,
+) -> io::Result<()> {
+    let bytes = index.to_bytes().map_err(io::Error::other)?;
+    storage
+        .set_fragment_vector_index(fragment_id, field, bytes)
+        .await
+}
+
+/// Loads the index of a vector field from the given fragment.
+///
+/// Returns `None` if the fragment has no index for the field. Loading an index
+/// copies it out of the fragment, so it should be loaded once per fragment and
+/// kept for as long as the fragment is searched.
+pub fn read_fragment_index(
+    reader: &FragmentReader,
+    field: &str,
+) -> Result<Option<HnswIndex>, VectorIndexError> {
+    let bytes = match reader.vector_index(field) {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+
+    HnswIndex::from_bytes(&bytes).map(Some).map_err(|_| {
+        VectorIndexError::CorruptedIndex {
+            fragment_id: reader.id(),
+        }
+    })
+}
+
+/// Builds the index of a vector field for a fragment compacted from the
+/// given fragments.
+///
+/// Documents which do not pass the `keep` filter, i.e. deleted documents,
+/// are left out of the new index. Returns `None` if none of the fragments
+/// have an index for the field.
+pub fn merge_fragment_indexes(
+    readers: &[FragmentReader],
+    field: &str,
+    keep: impl Fn(DocId) -> bool,
+) -> Result<Option<HnswIndex>, VectorIndexError> {
+    let mut indexes = Vec::with_capacity(readers.len());
+    for reader in readers {
+        if let Some(index) = read_fragment_index(reader, field)? {
+            indexes.push(index);
+        }
+    }
+
+    merge_indexes(indexes.iter(), keep)
+}
+
+/// Merges the indexes into a single index built with the parameters
+/// of the first index.
+///
+/// Returns `None` if there are no indexes to merge.
+pub fn merge_indexes<'a>(
+    indexes: impl IntoIterator<Item = &'a HnswIndex>,
+    keep: impl Fn(DocId) -> bool,
+) -> Result<Option<HnswIndex>, VectorIndexError> {
+    let mut merged: Option<HnswIndex> = None;
+    for index in indexes {
+        merged
+            .get_or_insert_with(|| {
+                HnswIndex::with_params(index.dims(), index.metric(), index.params())
+            })
+            .merge(index, &keep)?;
+    }
+
+    Ok(merged)
+}
+
+/// Finds the approximate `k` nearest neighbours of the query vector across
+/// the indexes of several fragments.
+///
+/// Each index is searched with the given `ef`, see [HnswIndex::search].
+pub fn search_indexes<'a>(
+    indexes: impl IntoIterator<Item = &'a HnswIndex>,
+    query: &[f32],
+    k: usize,
+    ef: usize,
+) -> Result<Vec<ScoredDoc>, VectorIndexError> {
+    let mut hits = Vec::new();
+    for index in indexes {
+        hits.extend(index.search(query, k, ef)?);
+    }
+
+    hits.sort_unstable_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    hits.truncate(k);
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use lnx_common::schema::DistanceMetric;
+
+    use super::*;
+
+    fn build_index(vectors: &[(DocId, [f32; 2])]) -> HnswIndex {
+        let mut index = HnswIndex::new(2, DistanceMetric::Euclidean);
+        for (doc_id, vector) in vectors {
+            index.insert(*doc_id, vector).expect("Insert vector");
+        }
+        index
+    }
+
+    #[test]
+    fn test_merge_and_search_indexes() {
+        let first = build_index(&[(1, [0.0, 0.0]), (2, [1.0, 0.0])]);
+        let second = build_index(&[(3, [0.0, 1.0]), (4, [5.0, 5.0])]);
+
+        let hits = search_indexes([&first, &second], &[0.1, 0.9], 2, 16)
+            .expect("Search indexes");
+        let doc_ids = hits.iter().map(|hit| hit.doc_id).collect::<Vec<_>>();
+        assert_eq!(doc_ids, [3, 1]);
+
+        let merged = merge_indexes([&first, &second], |doc_id| doc_id != 3)
+            .expect("Merge indexes")
+            .expect("Indexes should be merged");
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged.params(), first.params());
+        let hits = merged.search(&[0.1, 0.9], 1, 16).expect("Search index");
+        assert_eq!(hits[0].doc_id, 1);
+
+        assert!(merge_indexes([], |_| true)
+            .expect("Merge indexes")
+            .is_none());
+
+        let other = HnswIndex::new(3, DistanceMetric::Euclidean);
+        assert!(matches!(
+            merge_indexes([&first, &other], |_| true),
+            Err(VectorIndexError::IncompatibleIndexes),
+        ));
+    }
+}
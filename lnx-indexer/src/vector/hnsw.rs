@@ -0,0 +1,670 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+use bytecheck::CheckBytes;
+use lnx_common::schema::DistanceMetric;
+use lnx_common::CorruptedData;
+use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
+
+use super::{DocId, ScoredDoc, VectorIndexError};
+
+/// The seed of the generator used to pick the layer of each node.
+///
+/// A fixed seed keeps building the same index deterministic.
+const RNG_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[archive_attr(derive(CheckBytes))]
+/// The parameters controlling how the graph is built.
+pub struct HnswParams {
+    /// The maximum number of neighbours of each node on the upper layers.
+    ///
+    /// Nodes on the bottom layer can have twice as many neighbours.
+    pub m: u32,
+    /// The number of candidates considered when inserting a node.
+    ///
+    /// Higher values build a more accurate graph at the cost of slower inserts.
+    pub ef_construction: u32,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+        }
+    }
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+#[archive_attr(derive(CheckBytes))]
+/// A hierarchical navigable small world graph of document vectors.
+///
+/// An index should be built for each vector field of a fragment and persisted
+/// with [write_fragment_index](super::write_fragment_index) before the fragment
+/// is committed, and compacted fragments should merge the indexes of the old
+/// fragments with [merge_fragment_indexes](super::merge_fragment_indexes).
+pub struct HnswIndex {
+    dims: u32,
+    metric: DistanceMetric,
+    params: HnswParams,
+    /// The document of each node.
+    doc_ids: Vec<DocId>,
+    /// The vector of each node, laid out contiguously.
+    ///
+    /// Vectors compared by cosine similarity are normalized when inserted.
+    vectors: Vec<f32>,
+    /// The neighbours of each node on each of the layers the node is part of.
+    neighbours: Vec<Vec<Vec<u32>>>,
+    /// The node on the highest layer where searches start from.
+    entry_point: Option<u32>,
+    rng_state: u64,
+}
+
+impl HnswIndex {
+    /// Creates a new empty index using the default [HnswParams].
+    pub fn new(dims: usize, metric: DistanceMetric) -> Self {
+        Self::with_params(dims, metric, HnswParams::default())
+    }
+
+    /// Creates a new empty index with the given parameters.
+    pub fn with_params(dims: usize, metric: DistanceMetric, params: HnswParams) -> Self {
+        Self {
+            dims: dims as u32,
+            metric,
+            params: HnswParams {
+                m: params.m.max(2),
+                ef_construction: params.ef_construction.max(1),
+            },
+            doc_ids: Vec::new(),
+            vectors: Vec::new(),
+            neighbours: Vec::new(),
+            entry_point: None,
+            rng_state: RNG_SEED,
+        }
+    }
+
+    /// Deserializes the index from a given buffer.
+    ///
+    /// The buffer does not need to be aligned, it is copied into an
+    /// aligned buffer before being validated.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CorruptedData> {
+        let mut aligned = AlignedVec::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+
+        let index: Self = rkyv::from_bytes(&aligned).map_err(|_| CorruptedData)?;
+
+        if index.is_consistent() {
+            Ok(index)
+        } else {
+            Err(CorruptedData)
+        }
+    }
+
+    /// Serializes the index to a buffer.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CorruptedData> {
+        rkyv::to_bytes::<_, 4096>(self)
+            .map(|v| v.into_vec())
+            .map_err(|_| CorruptedData)
+    }
+
+    #[inline]
+    /// The number of dimensions of each vector.
+    pub fn dims(&self) -> usize {
+        self.dims as usize
+    }
+
+    #[inline]
+    /// The metric used to compare vectors.
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    #[inline]
+    /// The parameters the graph is built with.
+    pub fn params(&self) -> HnswParams {
+        self.params
+    }
+
+    #[inline]
+    /// The number of vectors in the index.
+    pub fn len(&self) -> usize {
+        self.doc_ids.len()
+    }
+
+    #[inline]
+    /// Returns if the index contains no vectors.
+    pub fn is_empty(&self) -> bool {
+        self.doc_ids.is_empty()
+    }
+
+    /// Adds the vector of a document to the index.
+    ///
+    /// Document ids are expected to be unique, each document should only be
+    /// inserted once.
+    pub fn insert(
+        &mut self,
+        doc_id: DocId,
+        vector: &[f32],
+    ) -> Result<(), VectorIndexError> {
+        self.check_dims(vector.len())?;
+
+        let vector = self.prepare(vector);
+        self.insert_prepared(doc_id, &vector);
+
+        Ok(())
+    }
+
+    /// Adds the vectors of another index which pass the `keep` filter.
+    ///
+    /// This is intended for compacting fragments, where the index of the new
+    /// fragment is built by merging each of the old indexes while filtering out
+    /// any deleted documents.
+    pub fn merge(
+        &mut self,
+        other: &HnswIndex,
+        keep: impl Fn(DocId) -> bool,
+    ) -> Result<(), VectorIndexError> {
+        if self.dims != other.dims || self.metric != other.metric {
+            return Err(VectorIndexError::IncompatibleIndexes);
+        }
+
+        for (node, doc_id) in other.doc_ids.iter().enumerate() {
+            if keep(*doc_id) {
+                self.insert_prepared(*doc_id, other.vector(node as u32));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the approximate `k` nearest neighbours of the query vector.
+    ///
+    /// `ef` is the number of candidates considered, higher values are more
+    /// accurate but slower. It is always at least `k`.
+    pub fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef: usize,
+    ) -> Result<Vec<ScoredDoc>, VectorIndexError> {
+        self.search_filtered(query, k, ef, |_| true)
+    }
+
+    /// Finds the approximate `k` nearest neighbours of the query vector
+    /// which pass the `filter`.
+    ///
+    /// The filter is applied to the candidates, so `ef` should be increased when
+    /// filtering out a large portion of the documents.
+    pub fn search_filtered(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef: usize,
+        filter: impl Fn(DocId) -> bool,
+    ) -> Result<Vec<ScoredDoc>, VectorIndexError> {
+        self.check_dims(query.len())?;
+
+        let entry_point = match self.entry_point {
+            Some(entry_point) if k > 0 => entry_point,
+            _ => return Ok(Vec::new()),
+        };
+
+        let query = self.prepare(query);
+        let mut entry_points = vec![entry_point];
+        for layer in (1..=self.level_of(entry_point)).rev() {
+            entry_points = self.closest(&query, &entry_points, layer);
+        }
+
+        let hits = self
+            .search_layer(&query, &entry_points, ef.max(k), 0)
+            .into_iter()
+            .map(|candidate| ScoredDoc {
+                doc_id: self.doc_ids[candidate.node as usize],
+                score: candidate.similarity,
+            })
+            .filter(|hit| filter(hit.doc_id))
+            .take(k)
+            .collect();
+
+        Ok(hits)
+    }
+
+    fn insert_prepared(&mut self, doc_id: DocId, vector: &[f32]) {
+        let node = self.doc_ids.len() as u32;
+        let level = self.random_level();
+
+        self.doc_ids.push(doc_id);
+        self.vectors.extend_from_slice(vector);
+        self.neighbours.push(vec![Vec::new(); level + 1]);
+
+        let entry_point = match self.entry_point {
+            Some(entry_point) => entry_point,
+            None => {
+                self.entry_point = Some(node);
+                return;
+            },
+        };
+
+        let top_level = self.level_of(entry_point);
+        let mut entry_points = vec![entry_point];
+        for layer in (level + 1..=top_level).rev() {
+            entry_points = self.closest(vector, &entry_points, layer);
+        }
+
+        for layer in (0..=level.min(top_level)).rev() {
+            let candidates = self.search_layer(
+                vector,
+                &entry_points,
+                self.params.ef_construction as usize,
+                layer,
+            );
+
+            let neighbours = candidates
+                .iter()
+                .take(self.max_neighbours(layer))
+                .map(|candidate| candidate.node)
+                .collect::<Vec<_>>();
+            for neighbour in neighbours.iter().copied() {
+                self.neighbours[neighbour as usize][layer].push(node);
+                self.prune(neighbour, layer);
+            }
+            self.neighbours[node as usize][layer] = neighbours;
+
+            entry_points = candidates.into_iter().map(|c| c.node).collect();
+        }
+
+        if level > top_level {
+            self.entry_point = Some(node);
+        }
+    }
+
+    /// Removes the least similar neighbours of the node if it has too many.
+    fn prune(&mut self, node: u32, layer: usize) {
+        let max_neighbours = self.max_neighbours(layer);
+        if self.neighbours[node as usize][layer].len() <= max_neighbours {
+            return;
+        }
+
+        let vector = self.vector(node);
+        let mut candidates = self.neighbours[node as usize][layer]
+            .iter()
+            .map(|&neighbour| Candidate {
+                similarity: self.similarity(vector, self.vector(neighbour)),
+                node: neighbour,
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+        candidates.truncate(max_neighbours);
+
+        self.neighbours[node as usize][layer] =
+            candidates.into_iter().map(|c| c.node).collect();
+    }
+
+    /// Greedily finds the single closest node to the query on the layer.
+    fn closest(&self, query: &[f32], entry_points: &[u32], layer: usize) -> Vec<u32> {
+        self.search_layer(query, entry_points, 1, layer)
+            .into_iter()
+            .map(|c| c.node)
+            .collect()
+    }
+
+    /// Finds the `ef` closest nodes to the query on the layer, sorted
+    /// from most to least similar.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[u32],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited = HashSet::new();
+        // The candidates to visit, most similar first.
+        let mut candidates = BinaryHeap::new();
+        // The closest nodes found, least similar first.
+        let mut results = BinaryHeap::new();
+
+        for node in entry_points.iter().copied() {
+            if visited.insert(node) {
+                let candidate = Candidate {
+                    similarity: self.similarity(query, self.vector(node)),
+                    node,
+                };
+                candidates.push(candidate);
+                results.push(Reverse(candidate));
+            }
+        }
+
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = results.peek().map(|Reverse(c)| c.similarity);
+            if results.len() >= ef && worst.is_some_and(|w| candidate.similarity < w) {
+                break;
+            }
+
+            for neighbour in self.neighbours[candidate.node as usize][layer]
+                .iter()
+                .copied()
+            {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let similarity = self.similarity(query, self.vector(neighbour));
+                let worst = results.peek().map(|Reverse(c)| c.similarity);
+                if results.len() < ef || worst.is_some_and(|w| similarity > w) {
+                    let candidate = Candidate {
+                        similarity,
+                        node: neighbour,
+                    };
+                    candidates.push(candidate);
+                    results.push(Reverse(candidate));
+
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut results = results.into_iter().map(|Reverse(c)| c).collect::<Vec<_>>();
+        results.sort_unstable_by(|a, b| b.cmp(a));
+        results
+    }
+
+    fn check_dims(&self, found: usize) -> Result<(), VectorIndexError> {
+        if found == self.dims() {
+            Ok(())
+        } else {
+            Err(VectorIndexError::DimensionMismatch {
+                expected: self.dims(),
+                found,
+            })
+        }
+    }
+
+    /// Normalizes the vector if it is compared by cosine similarity.
+    ///
+    /// This allows normalized vectors to be compared with the dot product.
+    fn prepare(&self, vector: &[f32]) -> Vec<f32> {
+        let mut vector = vector.to_vec();
+        if self.metric == DistanceMetric::Cosine {
+            let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|v| *v /= norm);
+            }
+        }
+        vector
+    }
+
+    #[inline]
+    fn similarity(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self.metric {
+            DistanceMetric::Cosine => DistanceMetric::Dot.similarity(a, b),
+            metric => metric.similarity(a, b),
+        }
+    }
+
+    #[inline]
+    fn vector(&self, node: u32) -> &[f32] {
+        let start = node as usize * self.dims();
+        &self.vectors[start..start + self.dims()]
+    }
+
+    #[inline]
+    fn level_of(&self, node: u32) -> usize {
+        self.neighbours[node as usize].len() - 1
+    }
+
+    #[inline]
+    fn max_neighbours(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.params.m as usize * 2
+        } else {
+            self.params.m as usize
+        }
+    }
+
+    /// Picks the highest layer of a new node, each layer is `m` times
+    /// less likely than the layer below.
+    fn random_level(&mut self) -> usize {
+        // xorshift64*
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        let random = self.rng_state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+
+        let uniform = ((random >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level_multiplier = 1.0 / (self.params.m as f64).ln();
+        (-uniform.ln() * level_multiplier) as usize
+    }
+
+    /// Checks the graph only references nodes and layers which exist.
+    fn is_consistent(&self) -> bool {
+        let len = self.doc_ids.len();
+        if self.vectors.len() != len * self.dims()
+            || self.neighbours.len() != len
+            || self.params.m < 2
+        {
+            return false;
+        }
+
+        let entry_point_valid = match self.entry_point {
+            Some(entry_point) => (entry_point as usize) < len,
+            None => len == 0,
+        };
+
+        entry_point_valid
+            && self.neighbours.iter().all(|layers| {
+                !layers.is_empty()
+                    && layers.iter().enumerate().all(|(layer, neighbours)| {
+                        neighbours.iter().all(|&neighbour| {
+                            self.neighbours
+                                .get(neighbour as usize)
+                                .is_some_and(|n| n.len() > layer)
+                        })
+                    })
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    similarity: f32,
+    node: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMS: usize = 16;
+
+    fn random_vectors(count: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 2000) as f32 / 1000.0 - 1.0
+        };
+
+        (0..count)
+            .map(|_| (0..DIMS).map(|_| next()).collect())
+            .collect()
+    }
+
+    fn build_index(metric: DistanceMetric, vectors: &[Vec<f32>]) -> HnswIndex {
+        let mut index = HnswIndex::new(DIMS, metric);
+        for (doc_id, vector) in vectors.iter().enumerate() {
+            index
+                .insert(doc_id as DocId, vector)
+                .expect("Insert vector");
+        }
+        index
+    }
+
+    fn exact_neighbours(
+        metric: DistanceMetric,
+        vectors: &[Vec<f32>],
+        query: &[f32],
+        k: usize,
+    ) -> Vec<DocId> {
+        let mut scored = vectors
+            .iter()
+            .enumerate()
+            .map(|(doc_id, vector)| (metric.similarity(query, vector), doc_id as DocId))
+            .collect::<Vec<_>>();
+        scored.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(k)
+            .map(|(_, doc_id)| doc_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_search_recall() {
+        let vectors = random_vectors(500, 42);
+        let queries = random_vectors(20, 7);
+
+        for metric in [
+            DistanceMetric::Cosine,
+            DistanceMetric::Dot,
+            DistanceMetric::Euclidean,
+        ] {
+            let index = build_index(metric, &vectors);
+            assert_eq!(index.len(), vectors.len());
+
+            let mut found = 0;
+            for query in queries.iter() {
+                let expected = exact_neighbours(metric, &vectors, query, 10);
+                let hits = index.search(query, 10, 64).expect("Search index");
+                assert_eq!(hits.len(), 10);
+                assert!(hits.windows(2).all(|w| w[0].score >= w[1].score));
+
+                found += hits
+                    .iter()
+                    .filter(|hit| expected.contains(&hit.doc_id))
+                    .count();
+            }
+
+            let recall = found as f32 / (queries.len() * 10) as f32;
+            assert!(recall >= 0.9, "{metric:?} recall was {recall}");
+        }
+    }
+
+    #[test]
+    fn test_search_filtered() {
+        let vectors = random_vectors(200, 3);
+        let index = build_index(DistanceMetric::Euclidean, &vectors);
+
+        let hits = index
+            .search_filtered(&vectors[10], 5, 100, |doc_id| doc_id.is_multiple_of(2))
+            .expect("Search index");
+        assert_eq!(hits.len(), 5);
+        assert_eq!(hits[0].doc_id, 10);
+        assert_eq!(hits[0].score, 1.0);
+        assert!(hits.iter().all(|hit| hit.doc_id.is_multiple_of(2)));
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let mut index = HnswIndex::new(DIMS, DistanceMetric::Cosine);
+        assert!(matches!(
+            index.insert(1, &[1.0, 2.0]),
+            Err(VectorIndexError::DimensionMismatch {
+                expected: DIMS,
+                found: 2
+            }),
+        ));
+        assert!(index.search(&[1.0], 10, 10).is_err());
+        assert!(index
+            .search(&[1.0; DIMS], 10, 10)
+            .expect("Search index")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let vectors = random_vectors(300, 11);
+        let first = build_index(DistanceMetric::Cosine, &vectors[..150]);
+        let mut second = HnswIndex::new(DIMS, DistanceMetric::Cosine);
+        for (doc_id, vector) in vectors.iter().enumerate().skip(150) {
+            second
+                .insert(doc_id as DocId, vector)
+                .expect("Insert vector");
+        }
+
+        let mut merged = HnswIndex::new(DIMS, DistanceMetric::Cosine);
+        let deleted = |doc_id: DocId| doc_id.is_multiple_of(3);
+        merged
+            .merge(&first, |id| !deleted(id))
+            .expect("Merge index");
+        merged
+            .merge(&second, |id| !deleted(id))
+            .expect("Merge index");
+        assert_eq!(merged.len(), 200);
+
+        let hits = merged.search(&vectors[200], 1, 32).expect("Search index");
+        assert_eq!(hits[0].doc_id, 200);
+
+        let hits = merged
+            .search(&vectors[150], 200, 200)
+            .expect("Search index");
+        assert!(hits.iter().all(|hit| !deleted(hit.doc_id)));
+
+        let other = HnswIndex::new(DIMS, DistanceMetric::Dot);
+        assert!(matches!(
+            merged.merge(&other, |_| true),
+            Err(VectorIndexError::IncompatibleIndexes),
+        ));
+    }
+
+    #[test]
+    fn test_serialize_index() {
+        let vectors = random_vectors(100, 5);
+        let index = build_index(DistanceMetric::Dot, &vectors);
+
+        let bytes = index.to_bytes().expect("Serialize index");
+        let loaded = HnswIndex::from_bytes(&bytes).expect("Deserialize index");
+        assert_eq!(loaded.len(), index.len());
+        assert_eq!(
+            loaded.search(&vectors[0], 5, 32).expect("Search index"),
+            index.search(&vectors[0], 5, 32).expect("Search index"),
+        );
+
+        assert!(HnswIndex::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+
+        // Buffers read from a fragment are not necessarily aligned.
+        let mut unaligned = vec![0];
+        unaligned.extend_from_slice(&bytes);
+        let loaded = HnswIndex::from_bytes(&unaligned[1..]).expect("Deserialize index");
+        assert_eq!(loaded.len(), index.len());
+    }
+}
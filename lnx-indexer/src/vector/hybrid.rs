@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use super::{search_indexes, DocId, HnswIndex, ScoredDoc, VectorIndexError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How the results of a lexical and a vector search are combined.
+pub enum FusionMethod {
+    /// Reciprocal rank fusion.
+    ///
+    /// Each document scores `1 / (k + rank)` in each list it appears in,
+    /// so only the order of the results matters and not their scores.
+    ReciprocalRank {
+        /// Dampens the impact of the top ranked results, typically `60.0`.
+        k: f32,
+    },
+
+    /// A weighted sum of the scores, after each list is normalized
+    /// between `0.0` and `1.0`.
+    Weighted {
+        /// The weight of the vector scores between `0.0` and `1.0`,
+        /// the lexical scores are weighted by the remainder.
+        vector_weight: f32,
+    },
+}
+
+impl Default for FusionMethod {
+    fn default() -> Self {
+        Self::ReciprocalRank { k: 60.0 }
+    }
+}
+
+/// Combines the results of a lexical and a vector search into a
+/// single list of at most `limit` documents.
+///
+/// Both lists must be sorted from most to least relevant, and the results
+/// are sorted the same way.
+pub fn fuse(
+    lexical: &[ScoredDoc],
+    vector: &[ScoredDoc],
+    method: FusionMethod,
+    limit: usize,
+) -> Vec<ScoredDoc> {
+    let mut scores = HashMap::<DocId, f32>::new();
+
+    match method {
+        FusionMethod::ReciprocalRank { k } => {
+            for results in [lexical, vector] {
+                for (rank, hit) in results.iter().enumerate() {
+                    *scores.entry(hit.doc_id).or_default() +=
+                        1.0 / (k + rank as f32 + 1.0);
+                }
+            }
+        },
+        FusionMethod::Weighted { vector_weight } => {
+            let vector_weight = vector_weight.clamp(0.0, 1.0);
+            for (results, weight) in
+                [(lexical, 1.0 - vector_weight), (vector, vector_weight)]
+            {
+                for (doc_id, score) in normalize(results) {
+                    *scores.entry(doc_id).or_default() += score * weight;
+                }
+            }
+        },
+    }
+
+    let mut fused = scores
+        .into_iter()
+        .map(|(doc_id, score)| ScoredDoc { doc_id, score })
+        .collect::<Vec<_>>();
+    fused.sort_unstable_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    fused.truncate(limit);
+    fused
+}
+
+/// Searches the vector indexes of each fragment for the `limit` nearest
+/// neighbours of the query vector and fuses them with the results of a
+/// lexical search.
+///
+/// The lexical results must be sorted from most to least relevant.
+pub fn hybrid_search<'a>(
+    indexes: impl IntoIterator<Item = &'a HnswIndex>,
+    query: &[f32],
+    lexical: &[ScoredDoc],
+    method: FusionMethod,
+    limit: usize,
+    ef: usize,
+) -> Result<Vec<ScoredDoc>, VectorIndexError> {
+    let vector = search_indexes(indexes, query, limit, ef)?;
+    Ok(fuse(lexical, &vector, method, limit))
+}
+
+/// Scales the scores between `0.0` and `1.0` using min-max normalization.
+///
+/// If every score is the same they are all scaled to `1.0`.
+fn normalize(results: &[ScoredDoc]) -> impl Iterator<Item = (DocId, f32)> + '_ {
+    let min = results
+        .iter()
+        .map(|hit| hit.score)
+        .fold(f32::INFINITY, f32::min);
+    let max = results
+        .iter()
+        .map(|hit| hit.score)
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    results.iter().map(move |hit| {
+        let score = if max > min {
+            (hit.score - min) / (max - min)
+        } else {
+            1.0
+        };
+        (hit.doc_id, score)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lnx_common::schema::DistanceMetric;
+
+    use super::*;
+
+    fn hits(results: &[(DocId, f32)]) -> Vec<ScoredDoc> {
+        results
+            .iter()
+            .map(|&(doc_id, score)| ScoredDoc { doc_id, score })
+            .collect()
+    }
+
+    fn doc_ids(results: &[ScoredDoc]) -> Vec<DocId> {
+        results.iter().map(|hit| hit.doc_id).collect()
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let lexical = hits(&[(1, 12.0), (2, 8.0), (3, 1.0)]);
+        let vector = hits(&[(3, 0.9), (2, 0.8), (4, 0.1)]);
+
+        let fused = fuse(&lexical, &vector, FusionMethod::default(), 10);
+        assert_eq!(doc_ids(&fused), [3, 2, 1, 4]);
+        assert_eq!(fused[1].score, 2.0 / 62.0);
+
+        let fused = fuse(&lexical, &vector, FusionMethod::default(), 2);
+        assert_eq!(doc_ids(&fused), [3, 2]);
+    }
+
+    #[test]
+    fn test_weighted_fusion() {
+        let lexical = hits(&[(1, 10.0), (2, 5.0), (3, 0.0)]);
+        let vector = hits(&[(3, 4.0), (2, 3.0), (1, 0.0)]);
+
+        let fused = fuse(
+            &lexical,
+            &vector,
+            FusionMethod::Weighted { vector_weight: 0.0 },
+            10,
+        );
+        assert_eq!(doc_ids(&fused), [1, 2, 3]);
+
+        let fused = fuse(
+            &lexical,
+            &vector,
+            FusionMethod::Weighted { vector_weight: 1.0 },
+            10,
+        );
+        assert_eq!(doc_ids(&fused), [3, 2, 1]);
+        assert_eq!(fused[0].score, 1.0);
+
+        let fused = fuse(
+            &lexical,
+            &vector,
+            FusionMethod::Weighted { vector_weight: 0.5 },
+            10,
+        );
+        assert_eq!(fused[0].doc_id, 2);
+        assert_eq!(fused[0].score, 0.625);
+
+        let fused = fuse(
+            &[],
+            &hits(&[(5, 0.3)]),
+            FusionMethod::Weighted { vector_weight: 0.5 },
+            10,
+        );
+        assert_eq!(fused, hits(&[(5, 0.5)]));
+    }
+
+    #[test]
+    fn test_hybrid_search() {
+        let mut index = HnswIndex::new(2, DistanceMetric::Euclidean);
+        for (doc_id, vector) in [(1, [0.0, 0.0]), (2, [1.0, 1.0]), (3, [0.9, 1.0])] {
+            index.insert(doc_id, &vector).expect("Insert vector");
+        }
+
+        let lexical = hits(&[(1, 3.0), (2, 2.0)]);
+        let fused = hybrid_search(
+            [&index],
+            &[1.0, 1.0],
+            &lexical,
+            FusionMethod::default(),
+            2,
+            16,
+        )
+        .expect("Search index");
+        assert_eq!(doc_ids(&fused), [2, 1]);
+
+        assert!(hybrid_search(
+            [&index],
+            &[1.0],
+            &lexical,
+            FusionMethod::default(),
+            2,
+            16
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,46 @@
+mod fragment;
+mod hnsw;
+mod hybrid;
+
+pub use fragment::{
+    merge_fragment_indexes,
+    merge_indexes,
+    read_fragment_index,
+    search_indexes,
+    write_fragment_index,
+};
+pub use hnsw::{HnswIndex, HnswParams};
+pub use hybrid::{fuse, hybrid_search, FusionMethod};
+
+/// The unique id of a document, as stored in the `RESERVED_DOCUMENT_ID_FIELD`.
+pub type DocId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A document and its score from a search.
+pub struct ScoredDoc {
+    /// The id of the document.
+    pub doc_id: DocId,
+    /// The score of the document, a higher score is more relevant.
+    pub score: f32,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// An error preventing a vector from being indexed or searched.
+pub enum VectorIndexError {
+    #[error("Expected a vector with {expected} dimensions but got {found}")]
+    DimensionMismatch {
+        /// The number of dimensions of the index.
+        expected: usize,
+        /// The number of dimensions of the provided vector.
+        found: usize,
+    },
+    #[error(
+        "The indexes have different dimensions or distance metrics and cannot be merged"
+    )]
+    IncompatibleIndexes,
+    #[error("The vector index of fragment {fragment_id} is corrupted")]
+    CorruptedIndex {
+        /// The fragment containing the index.
+        fragment_id: u64,
+    },
+}
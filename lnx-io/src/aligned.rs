@@ -0,0 +1,180 @@
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// The alignment of buffers and file offsets required for direct IO.
+///
+/// This covers the logical block size of practically all devices.
+pub const ALIGNMENT: usize = 4096;
+
+/// Rounds the given value up to the next multiple of [ALIGNMENT].
+pub const fn align_up(n: usize) -> usize {
+    (n + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+/// Rounds the given value down to the previous multiple of [ALIGNMENT].
+pub const fn align_down(n: usize) -> usize {
+    n & !(ALIGNMENT - 1)
+}
+
+/// A fixed capacity, zero initialised buffer aligned to [ALIGNMENT].
+///
+/// The capacity is always a multiple of [ALIGNMENT] so the full buffer
+/// can be passed to direct IO operations.
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    capacity: usize,
+}
+
+// SAFETY: The buffer uniquely owns its allocation.
+unsafe impl Send for AlignedBuffer {}
+// SAFETY: The buffer is only mutated via `&mut self`.
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    /// Creates a new empty buffer which can hold at least `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = align_up(capacity.max(1));
+        let layout = Self::layout(capacity);
+
+        // SAFETY: The layout always has a non-zero size.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = match NonNull::new(ptr) {
+            Some(ptr) => ptr,
+            None => alloc::handle_alloc_error(layout),
+        };
+
+        Self {
+            ptr,
+            len: 0,
+            capacity,
+        }
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity, ALIGNMENT).expect("Valid buffer layout")
+    }
+
+    /// The number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The total number of bytes the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns if no more bytes can be added to the buffer.
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity
+    }
+
+    /// Removes all bytes from the buffer.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Shortens the buffer to `len` bytes.
+    ///
+    /// This has no effect if `len` is greater than the current length.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Copies as many bytes as possible from the given slice into the
+    /// buffer, returning the number of bytes copied.
+    pub fn extend_from_slice(&mut self, buf: &[u8]) -> usize {
+        let n = buf.len().min(self.capacity - self.len);
+        let len = self.len;
+        self.full_slice_mut()[len..len + n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        n
+    }
+
+    /// Sets the length of the buffer.
+    ///
+    /// The whole allocation is zero initialised so any length up to
+    /// the capacity is valid.
+    pub(crate) fn set_len(&mut self, len: usize) {
+        assert!(len <= self.capacity, "Length exceeds buffer capacity");
+        self.len = len;
+    }
+
+    /// The buffer contents zero padded up to the next multiple of [ALIGNMENT].
+    pub(crate) fn padded(&mut self) -> &[u8] {
+        let len = self.len;
+        let padded_len = align_up(len);
+        self.full_slice_mut()[len..padded_len].fill(0);
+        &self.full_slice_mut()[..padded_len]
+    }
+
+    /// The full allocation of the buffer regardless of its length.
+    pub(crate) fn full_slice_mut(&mut self) -> &mut [u8] {
+        // SAFETY: The allocation is `capacity` bytes long and zero initialised.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.capacity) }
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `len` never exceeds the initialised allocation.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: `len` never exceeds the initialised allocation.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: The pointer was allocated with the same layout.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.capacity)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_alignment() {
+        let mut buffer = AlignedBuffer::with_capacity(5_000);
+        assert_eq!(buffer.capacity(), 2 * ALIGNMENT);
+        assert_eq!(buffer.as_ptr() as usize % ALIGNMENT, 0);
+
+        assert_eq!(buffer.extend_from_slice(b"hello"), 5);
+        assert_eq!(&buffer[..], b"hello");
+        assert_eq!(buffer.padded().len(), ALIGNMENT);
+
+        let data = vec![1; 10_000];
+        assert_eq!(buffer.extend_from_slice(&data), 2 * ALIGNMENT - 5);
+        assert!(buffer.is_full());
+    }
+
+    #[test]
+    fn test_align() {
+        assert_eq!(align_up(0), 0);
+        assert_eq!(align_up(1), ALIGNMENT);
+        assert_eq!(align_up(ALIGNMENT), ALIGNMENT);
+        assert_eq!(align_down(ALIGNMENT + 1), ALIGNMENT);
+    }
+}
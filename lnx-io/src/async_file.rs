@@ -0,0 +1,288 @@
+use std::io::{self, ErrorKind, Seek, SeekFrom, Write};
+use std::mem;
+
+use crate::file::WriterFile;
+
+/// The default number of bytes buffered before being written out.
+const DEFAULT_BUFFER_SIZE: usize = 512 << 10;
+
+/// A buffered file writer which runs all blocking IO on the tokio
+/// blocking thread pool.
+///
+/// Writes are collected in memory and written out once the buffer is full,
+/// so writing and syncing large amounts of data never blocks the runtime's
+/// worker threads.
+///
+/// Dropping the writer writes out any buffered data in the background,
+/// [AsyncWriter::close] should be used to wait for the data to be written.
+///
+/// This must be used within a tokio runtime.
+pub struct AsyncWriter {
+    /// The file, this is `None` if a previous operation was cancelled.
+    file: Option<WriterFile>,
+    buffer: Vec<u8>,
+    capacity: usize,
+    /// The file position of the start of the buffer.
+    file_position: u64,
+}
+
+impl AsyncWriter {
+    /// Creates a new writer for the given file.
+    ///
+    /// The `position` must be the current position of the file.
+    pub fn new(file: WriterFile, position: u64) -> Self {
+        Self::with_capacity(DEFAULT_BUFFER_SIZE, file, position)
+    }
+
+    /// Creates a new writer with a buffer of at least `capacity` bytes.
+    ///
+    /// The `position` must be the current position of the file.
+    pub fn with_capacity(capacity: usize, file: WriterFile, position: u64) -> Self {
+        let capacity = capacity.max(1);
+
+        Self {
+            file: Some(file),
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            file_position: position,
+        }
+    }
+
+    /// The logical position of the writer, including any buffered data.
+    pub fn position(&self) -> u64 {
+        self.file_position + self.buffer.len() as u64
+    }
+
+    /// Writes all of the given bytes to the writer.
+    ///
+    /// The data is only written to the file once the buffer is full.
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let n = buf.len().min(self.capacity - self.buffer.len());
+            self.buffer.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+
+            if self.buffer.len() >= self.capacity {
+                self.write_buffer(false).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes any buffered data to the file without syncing it.
+    ///
+    /// This includes any data buffered by the file itself, so once complete
+    /// the data survives the process crashing but not the OS.
+    ///
+    /// If the write fails the data is kept in the buffer so it can be retried.
+    pub async fn write_out(&mut self) -> io::Result<()> {
+        self.write_buffer(true).await
+    }
+
+    /// Writes the buffer to the file, additionally writing out the
+    /// file's own buffer if `write_out` is `true`.
+    async fn write_buffer(&mut self, write_out: bool) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let start = self.file_position;
+        let buffer = mem::take(&mut self.buffer);
+        let (mut buffer, res) = self
+            .run(move |file| {
+                let res = file
+                    .write_all(&buffer)
+                    .and_then(|_| if write_out { file.write_out() } else { Ok(()) })
+                    .inspect_err(|_| {
+                        // Restore the position so the whole buffer is rewritten
+                        // on retry.
+                        if let Err(e) = file.seek(SeekFrom::Start(start)) {
+                            warn!(
+                                error = ?e,
+                                "Failed to restore the file position after a failed write",
+                            );
+                        }
+                    });
+                (buffer, res)
+            })
+            .await?;
+
+        if res.is_ok() {
+            self.file_position += buffer.len() as u64;
+            buffer.clear();
+        }
+        self.buffer = buffer;
+
+        res
+    }
+
+    /// Writes any buffered data and releases any disk space reserved
+    /// beyond the end of the file.
+    ///
+    /// This should be called once no more data will be written to the file.
+    pub async fn release_preallocation(&mut self) -> io::Result<()> {
+        self.write_out().await?;
+        self.run(|file| file.release_preallocation()).await?
+    }
+
+    /// Writes any buffered data and closes the file without syncing it.
+    ///
+    /// Dropping the writer does the same in the background, this should be
+    /// used when the data must be written before continuing.
+    pub async fn close(mut self) -> io::Result<()> {
+        self.write_out().await?;
+
+        let mut file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        // Closing the file can also write out data, so it's closed on the
+        // blocking thread pool.
+        tokio::task::spawn_blocking(move || {
+            let res = file.release_preallocation();
+            drop(file);
+            res
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    /// Writes any buffered data and syncs the file to disk.
+    pub async fn sync(&mut self) -> io::Result<()> {
+        self.write_out().await?;
+        self.run(|file| file.flush()).await?
+    }
+
+    /// Moves the writer back to the given position, discarding any
+    /// buffered data after it.
+    ///
+    /// The position cannot be after the current position of the writer.
+    pub async fn seek_to(&mut self, pos: u64) -> io::Result<()> {
+        if pos > self.position() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Cannot seek past the current position of the writer",
+            ));
+        }
+
+        if pos >= self.file_position {
+            self.buffer.truncate((pos - self.file_position) as usize);
+            return Ok(());
+        }
+
+        self.buffer.clear();
+        self.run(move |file| file.seek(SeekFrom::Start(pos)))
+            .await??;
+        self.file_position = pos;
+
+        Ok(())
+    }
+
+    /// Runs the operation with the file on the blocking thread pool.
+    async fn run<F, T>(&mut self, op: F) -> io::Result<T>
+    where
+        F: FnOnce(&mut WriterFile) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut file = self.file.take().ok_or_else(|| {
+            io::Error::other(
+                "The file is unavailable as a previous operation was cancelled",
+            )
+        })?;
+
+        let (file, output) = tokio::task::spawn_blocking(move || {
+            let output = op(&mut file);
+            (file, output)
+        })
+        .await
+        .map_err(io::Error::other)?;
+
+        self.file = Some(file);
+        Ok(output)
+    }
+}
+
+impl Drop for AsyncWriter {
+    fn drop(&mut self) {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return,
+        };
+        let buffer = mem::take(&mut self.buffer);
+
+        // Like a buffered writer, any buffered data is written but not synced,
+        // no more data is written so the reserved space is released.
+        let close = move || {
+            let mut file = file;
+            if let Err(e) = file.write_all(&buffer) {
+                warn!(error = ?e, "Failed to write buffered data to file");
+            }
+            if let Err(e) = file.release_preallocation() {
+                warn!(error = ?e, "Failed to release the file's reserved disk space");
+            }
+        };
+
+        // The writer is usually dropped on a runtime worker thread, which
+        // must not be blocked by the final write.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(close);
+            },
+            Err(_) => close(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::file::IoBackend;
+
+    #[tokio::test]
+    async fn test_async_writer() {
+        let path = std::env::temp_dir()
+            .join(format!("lnx-io-{}-async-writer", std::process::id()));
+        let file = fs::File::create(&path).unwrap();
+        let mut writer = AsyncWriter::with_capacity(8, WriterFile::from(file), 0);
+
+        writer.write_all(b"hello, world").await.unwrap();
+        assert_eq!(writer.position(), 12);
+        writer.sync().await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"hello, world");
+
+        // Seek within the buffer.
+        writer.write_all(b"abc").await.unwrap();
+        writer.seek_to(13).await.unwrap();
+        // Seek within the file.
+        writer.seek_to(7).await.unwrap();
+        writer.write_all(b"there").await.unwrap();
+        assert!(writer.seek_to(20).await.is_err());
+        writer.close().await.unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"hello, there");
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_async_writer_write_out() {
+        let path = std::env::temp_dir().join(format!(
+            "lnx-io-{}-async-writer-write-out",
+            std::process::id()
+        ));
+        let file = IoBackend::detect().open_writer(&path, true).unwrap();
+        let mut writer = AsyncWriter::new(file, 0);
+
+        // The data must be visible without syncing, including any
+        // data buffered by a direct IO file.
+        writer.write_all(b"hello, world").await.unwrap();
+        writer.write_out().await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"hello, world");
+
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,634 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Seek, SeekFrom, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use io_uring::{opcode, squeue, types, IoUring, Probe};
+use once_cell::sync::Lazy;
+
+use crate::aligned::{align_down, align_up, AlignedBuffer, ALIGNMENT};
+
+/// The number of bytes buffered before being written out.
+const WRITE_BUFFER_SIZE: usize = 1 << 20;
+/// The number of bytes written by each submitted operation.
+const WRITE_CHUNK_SIZE: usize = 128 << 10;
+/// The number of bytes read by each submitted operation.
+const READ_CHUNK_SIZE: usize = 1 << 20;
+/// The number of entries in each ring.
+///
+/// This bounds the number of operations submitted in a single batch.
+const RING_ENTRIES: u32 = 32;
+
+static IS_SUPPORTED: Lazy<bool> = Lazy::new(|| match probe() {
+    Ok(supported) => supported,
+    Err(e) => {
+        info!(error = ?e, "io_uring is not available");
+        false
+    },
+});
+
+/// Returns if the kernel supports the io_uring operations used by [DirectFile].
+pub fn is_supported() -> bool {
+    *IS_SUPPORTED
+}
+
+fn probe() -> io::Result<bool> {
+    let ring = IoUring::new(RING_ENTRIES)?;
+    let mut probe = Probe::new();
+    ring.submitter().register_probe(&mut probe)?;
+
+    Ok(probe.is_supported(opcode::Read::CODE)
+        && probe.is_supported(opcode::Write::CODE)
+        && probe.is_supported(opcode::Fsync::CODE))
+}
+
+/// A ring which submits batches of operations.
+///
+/// Every submitted operation must be waited for before the next batch
+/// is pushed, so completions are always matched to the current batch.
+struct Ring(IoUring);
+
+impl Ring {
+    fn new() -> io::Result<Self> {
+        IoUring::new(RING_ENTRIES).map(Self)
+    }
+
+    /// Pushes the entries tagged with their index and submits them without
+    /// waiting, returning the number of entries submitted.
+    ///
+    /// # Safety
+    /// Any buffers referenced by the entries must be valid until
+    /// [Ring::wait_all] returns for the batch.
+    unsafe fn push_all(
+        &mut self,
+        entries: impl IntoIterator<Item = squeue::Entry>,
+    ) -> io::Result<usize> {
+        let entries = entries.into_iter().collect::<Vec<_>>();
+
+        let mut submission = self.0.submission();
+        if entries.len() > submission.capacity() - submission.len() {
+            return Err(io::Error::other("io_uring submission queue is full"));
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            let entry = entry.clone().user_data(i as u64);
+            submission
+                .push(&entry)
+                .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+        }
+        drop(submission);
+
+        loop {
+            match self.0.submit() {
+                Ok(_) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(entries.len())
+    }
+
+    /// Waits for `n` submitted operations to complete, returning
+    /// their results in submission order.
+    fn wait_all(&mut self, n: usize) -> io::Result<Vec<io::Result<usize>>> {
+        let mut results = Vec::with_capacity(n);
+        results.resize_with(n, || None);
+
+        let mut remaining = n;
+        while remaining > 0 {
+            match self.0.submit_and_wait(remaining) {
+                Ok(_) => {},
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+
+            for cqe in self.0.completion() {
+                let result = if cqe.result() < 0 {
+                    Err(io::Error::from_raw_os_error(-cqe.result()))
+                } else {
+                    Ok(cqe.result() as usize)
+                };
+
+                let slot =
+                    results.get_mut(cqe.user_data() as usize).ok_or_else(|| {
+                        io::Error::other("io_uring completed an unknown operation")
+                    })?;
+                *slot = Some(result);
+                remaining -= 1;
+            }
+        }
+
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// Submits the entry and waits for its result.
+    ///
+    /// # Safety
+    /// Any buffers referenced by the entry must be valid until this returns.
+    unsafe fn submit(&mut self, entry: squeue::Entry) -> io::Result<usize> {
+        let n = self.push_all([entry])?;
+        self.wait_all(n)?
+            .pop()
+            .ok_or_else(|| io::Error::other("io_uring operation did not complete"))?
+    }
+
+    /// Submits writes of the buffer in chunks without waiting for them,
+    /// returning the number of operations submitted.
+    ///
+    /// The buffer must fit in a single batch.
+    ///
+    /// # Safety
+    /// The buffer must be valid until [Ring::wait_all] returns for the batch.
+    unsafe fn push_writes(
+        &mut self,
+        file: &File,
+        buf: &[u8],
+        offset: u64,
+    ) -> io::Result<usize> {
+        let entries = buf.chunks(WRITE_CHUNK_SIZE).enumerate().map(|(i, chunk)| {
+            opcode::Write::new(
+                types::Fd(file.as_raw_fd()),
+                chunk.as_ptr(),
+                chunk.len() as u32,
+            )
+            .offset64((offset + (i * WRITE_CHUNK_SIZE) as u64) as i64)
+            .build()
+        });
+        self.push_all(entries)
+    }
+
+    /// Checks the results of writes submitted by [Ring::push_writes],
+    /// writing out the remainder of any chunks which were only partially written.
+    fn finish_writes(
+        &mut self,
+        file: &File,
+        buf: &[u8],
+        offset: u64,
+        results: Vec<io::Result<usize>>,
+    ) -> io::Result<()> {
+        for ((i, chunk), result) in buf.chunks(WRITE_CHUNK_SIZE).enumerate().zip(results)
+        {
+            let n = result?;
+            if n == 0 {
+                return Err(ErrorKind::WriteZero.into());
+            }
+            if n < chunk.len() {
+                let chunk_offset = offset + (i * WRITE_CHUNK_SIZE + n) as u64;
+                self.write_all_at(file, &chunk[n..], chunk_offset)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_all_at(&mut self, file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+        let batch_size = WRITE_CHUNK_SIZE * RING_ENTRIES as usize;
+        for (i, batch) in buf.chunks(batch_size).enumerate() {
+            let batch_offset = offset + (i * batch_size) as u64;
+
+            // SAFETY: The buffer outlives the operations as we wait for them.
+            let n = unsafe { self.push_writes(file, batch, batch_offset)? };
+            let results = self.wait_all(n)?;
+            self.finish_writes(file, batch, batch_offset, results)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads until the buffer is full or the end of the file is reached.
+    ///
+    /// The buffer is read in chunks which are submitted together.
+    fn read_at(
+        &mut self,
+        file: &File,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> io::Result<usize> {
+        let batch_size = READ_CHUNK_SIZE * RING_ENTRIES as usize;
+        let mut total = 0;
+        for batch in buf.chunks_mut(batch_size) {
+            let batch_offset = offset + total as u64;
+            let entries =
+                batch
+                    .chunks_mut(READ_CHUNK_SIZE)
+                    .enumerate()
+                    .map(|(i, chunk)| {
+                        opcode::Read::new(
+                            types::Fd(file.as_raw_fd()),
+                            chunk.as_mut_ptr(),
+                            chunk.len() as u32,
+                        )
+                        .offset64((batch_offset + (i * READ_CHUNK_SIZE) as u64) as i64)
+                        .build()
+                    });
+
+            // SAFETY: The buffer outlives the operations as we wait for them.
+            let n = unsafe { self.push_all(entries)? };
+            let results = self.wait_all(n)?;
+
+            for (chunk, result) in batch.chunks_mut(READ_CHUNK_SIZE).zip(results) {
+                let chunk_offset = offset + total as u64;
+                let mut n = result?;
+
+                // Direct IO only returns a partial block at the end of the file.
+                while n < chunk.len() && n % ALIGNMENT == 0 {
+                    let rest = &mut chunk[n..];
+                    let entry = opcode::Read::new(
+                        types::Fd(file.as_raw_fd()),
+                        rest.as_mut_ptr(),
+                        rest.len() as u32,
+                    )
+                    .offset64((chunk_offset + n as u64) as i64)
+                    .build();
+
+                    // SAFETY: The buffer outlives the operation as we wait for it.
+                    let read = unsafe { self.submit(entry)? };
+                    if read == 0 {
+                        break;
+                    }
+                    n += read;
+                }
+
+                total += n;
+                if n < chunk.len() {
+                    return Ok(total);
+                }
+            }
+        }
+
+        Ok(total)
+    }
+
+    fn sync_data(&mut self, file: &File) -> io::Result<()> {
+        let entry = opcode::Fsync::new(types::Fd(file.as_raw_fd()))
+            .flags(types::FsyncFlags::DATASYNC)
+            .build();
+
+        // SAFETY: No buffers are referenced by the operation.
+        unsafe { self.submit(entry) }.map(|_| ())
+    }
+}
+
+fn open_direct(path: &Path, options: &mut OpenOptions) -> io::Result<File> {
+    options.custom_flags(libc::O_DIRECT).open(path)
+}
+
+/// An append only file which bypasses the page cache, writing via io_uring.
+///
+/// Writes are collected in an aligned buffer and written out in whole
+/// blocks, the partially filled final block is kept in memory and rewritten
+/// on each flush. Once a buffer fills up its writes are submitted in the
+/// background while writes continue into a second buffer.
+///
+/// Unlike a standard file, seeking backwards truncates any data after
+/// the new position.
+///
+/// Flushing syncs the data to disk like a
+/// [SyncOnFlushFile](crate::file::SyncOnFlushFile).
+pub struct DirectFile {
+    file: File,
+    ring: Ring,
+    buffer: AlignedBuffer,
+    /// The file offset of the start of the buffer, always a multiple of [ALIGNMENT].
+    buffer_offset: u64,
+    /// A full buffer which is being written out.
+    pending: Option<PendingWrite>,
+    /// A buffer to swap in once the current buffer is full.
+    spare: Option<AlignedBuffer>,
+    /// The file length disk space has been reserved up to.
+    reserved: u64,
+}
+
+/// A full buffer with submitted writes which have not been waited for.
+struct PendingWrite {
+    buffer: AlignedBuffer,
+    offset: u64,
+    /// The number of operations submitted for the buffer.
+    in_flight: usize,
+}
+
+impl DirectFile {
+    /// Opens the file at the given path positioned at the end of any existing data.
+    ///
+    /// This fails with [ErrorKind::InvalidInput] if the filesystem does
+    /// not support direct IO.
+    pub fn open(path: impl AsRef<Path>, create: bool) -> io::Result<Self> {
+        let file = open_direct(
+            path.as_ref(),
+            OpenOptions::new().read(true).write(true).create(create),
+        )?;
+        let len = file.metadata()?.len();
+
+        let mut slf = Self {
+            file,
+            ring: Ring::new()?,
+            buffer: AlignedBuffer::with_capacity(WRITE_BUFFER_SIZE),
+            buffer_offset: 0,
+            pending: None,
+            spare: None,
+            reserved: 0,
+        };
+        slf.load_block(len)?;
+
+        Ok(slf)
+    }
+
+    /// Reserves disk space for the file to grow up to `len` bytes.
+    ///
+    /// Trimming the padding from the end of the file can release the
+    /// reservation on some filesystems, so it is re-applied after each write out.
+    pub fn preallocate(&mut self, len: u64) -> io::Result<()> {
+        self.reserved = self.reserved.max(len);
+        self.reserve_remaining()
+    }
+
+    /// Writes out the buffer and releases any disk space reserved
+    /// beyond the end of the file.
+    ///
+    /// Disk space is no longer reserved by future writes.
+    pub fn release_preallocation(&mut self) -> io::Result<()> {
+        self.reserved = 0;
+        self.write_out()
+    }
+
+    fn reserve_remaining(&self) -> io::Result<()> {
+        let position = self.position();
+        if self.reserved <= position {
+            return Ok(());
+        }
+        crate::space::preallocate(&self.file, position, self.reserved - position)
+    }
+
+    /// The logical position of the writer.
+    fn position(&self) -> u64 {
+        self.buffer_offset + self.buffer.len() as u64
+    }
+
+    /// Replaces the buffer with the block containing `pos` so writes
+    /// can continue from that position.
+    fn load_block(&mut self, pos: u64) -> io::Result<()> {
+        self.wait_pending()?;
+        self.buffer.clear();
+        self.buffer_offset = align_down(pos as usize) as u64;
+
+        let len = (pos - self.buffer_offset) as usize;
+        if len != 0 {
+            let n = self.ring.read_at(
+                &self.file,
+                &mut self.buffer.full_slice_mut()[..ALIGNMENT],
+                self.buffer_offset,
+            )?;
+
+            if n < len {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "File is shorter than expected",
+                ));
+            }
+        }
+
+        self.buffer.set_len(len);
+        Ok(())
+    }
+
+    /// Submits the writes for the full buffer without waiting for them
+    /// and continues writing into the spare buffer.
+    ///
+    /// Only one buffer is written in the background at a time.
+    fn submit_buffer(&mut self) -> io::Result<()> {
+        self.wait_pending()?;
+
+        let mut next = self
+            .spare
+            .take()
+            .unwrap_or_else(|| AlignedBuffer::with_capacity(WRITE_BUFFER_SIZE));
+        next.clear();
+
+        let buffer = std::mem::replace(&mut self.buffer, next);
+        let offset = self.buffer_offset;
+        self.buffer_offset += buffer.len() as u64;
+
+        // SAFETY: The buffer is kept in `pending` until its writes complete.
+        let in_flight = unsafe { self.ring.push_writes(&self.file, &buffer, offset)? };
+        self.pending = Some(PendingWrite {
+            buffer,
+            offset,
+            in_flight,
+        });
+
+        Ok(())
+    }
+
+    /// Waits for the writes of the buffer submitted in the background.
+    fn wait_pending(&mut self) -> io::Result<()> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        let results = match self.ring.wait_all(pending.in_flight) {
+            Ok(results) => results,
+            Err(e) => {
+                // The kernel may still be writing from the buffer so it cannot be freed.
+                std::mem::forget(pending.buffer);
+                return Err(e);
+            },
+        };
+        self.ring
+            .finish_writes(&self.file, &pending.buffer, pending.offset, results)?;
+
+        self.spare = Some(pending.buffer);
+        Ok(())
+    }
+
+    /// Writes the buffer padded to a whole number of blocks.
+    fn write_buffer(&mut self) -> io::Result<()> {
+        self.wait_pending()?;
+
+        let offset = self.buffer_offset;
+        let padded = self.buffer.padded();
+        if padded.is_empty() {
+            return Ok(());
+        }
+        self.ring.write_all_at(&self.file, padded, offset)
+    }
+
+    /// Writes out the buffer without syncing it, trimming any padding
+    /// from the end of the file.
+    pub fn write_out(&mut self) -> io::Result<()> {
+        self.write_buffer()?;
+        self.file.set_len(self.position())?;
+        self.reserve_remaining()?;
+
+        // Keep the partially filled block so the next write continues from it.
+        let len = self.buffer.len();
+        let tail_start = align_down(len);
+        if tail_start != 0 {
+            self.buffer.full_slice_mut().copy_within(tail_start..len, 0);
+            self.buffer.set_len(len - tail_start);
+            self.buffer_offset += tail_start as u64;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for DirectFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.is_full() {
+            self.submit_buffer()?;
+        }
+
+        Ok(self.buffer.extend_from_slice(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_out()?;
+        self.ring.sync_data(&self.file)
+    }
+}
+
+impl Seek for DirectFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = self.position();
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) | SeekFrom::Current(n) => position.checked_add_signed(n),
+        };
+
+        let target = match target {
+            Some(target) if target <= position => target,
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "Direct files can only seek within the existing data",
+                ))
+            },
+        };
+
+        if target >= self.buffer_offset {
+            self.buffer.truncate((target - self.buffer_offset) as usize);
+        } else {
+            self.load_block(target)?;
+        }
+
+        Ok(target)
+    }
+}
+
+impl Drop for DirectFile {
+    fn drop(&mut self) {
+        // Like a buffered writer, any buffered data is written but not synced,
+        // no more data is written so the reserved space is released.
+        if let Err(e) = self.release_preallocation() {
+            warn!(error = ?e, "Failed to write buffered data to direct file");
+        }
+    }
+}
+
+/// Reads the whole file at the given path into an aligned buffer,
+/// bypassing the page cache.
+///
+/// This fails with [ErrorKind::InvalidInput] if the filesystem does
+/// not support direct IO.
+pub fn read_to_buffer(path: impl AsRef<Path>) -> io::Result<AlignedBuffer> {
+    let file = open_direct(path.as_ref(), OpenOptions::new().read(true))?;
+    let len = file.metadata()?.len() as usize;
+
+    let mut ring = Ring::new()?;
+    let mut buffer = AlignedBuffer::with_capacity(len);
+    let n = ring.read_at(&file, &mut buffer.full_slice_mut()[..align_up(len)], 0)?;
+    if n < len {
+        return Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "File is shorter than expected",
+        ));
+    }
+
+    buffer.set_len(len);
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lnx-io-{}-{name}", std::process::id()))
+    }
+
+    /// Opens a direct file, returning `None` if direct IO is unavailable.
+    fn open_or_skip(path: &Path, create: bool) -> Option<DirectFile> {
+        if !is_supported() {
+            return None;
+        }
+
+        match DirectFile::open(path, create) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == ErrorKind::InvalidInput => None,
+            Err(e) => panic!("Open direct file: {e}"),
+        }
+    }
+
+    #[test]
+    fn test_direct_write_and_reopen() {
+        let path = temp_path("write");
+        let mut file = match open_or_skip(&path, true) {
+            Some(file) => file,
+            None => return,
+        };
+        // The reservation must not change the visible length of the file.
+        file.preallocate(4 << 20).unwrap();
+
+        // Spans several buffers so writes continue while others are in flight.
+        let data = (0..3 * WRITE_BUFFER_SIZE + 10_000)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        file.write_all(&data).unwrap();
+        file.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        file.write_all(b"hello").unwrap();
+        drop(file);
+
+        let mut file = DirectFile::open(&path, false).unwrap();
+        let len = data.len() as u64 + 5;
+        assert_eq!(file.stream_position().unwrap(), len);
+
+        // Seeking backwards truncates the data.
+        file.seek(SeekFrom::Start(100)).unwrap();
+        file.write_all(b"world").unwrap();
+        file.flush().unwrap();
+
+        let mut expected = data[..100].to_vec();
+        expected.extend_from_slice(b"world");
+        assert_eq!(read_to_buffer(&path).unwrap().as_ref(), expected);
+        assert!(file.seek(SeekFrom::Start(len)).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_direct_read_to_buffer() {
+        if !is_supported() {
+            return;
+        }
+
+        let path = temp_path("read");
+        let data = (0..3 * READ_CHUNK_SIZE + 1_234)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        std::fs::write(&path, &data).unwrap();
+
+        match read_to_buffer(&path) {
+            Ok(buffer) => assert_eq!(buffer.as_ref(), data),
+            Err(e) if e.kind() == ErrorKind::InvalidInput => {},
+            Err(e) => panic!("Read direct file: {e}"),
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,12 +1,22 @@
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
 use std::{fs, io};
 
+use crate::aligned::AlignedBuffer;
+
 /// A wrapper type that calls `File::sync_data()` when `flush` is called.
 ///
 /// This effectively patches the fact that `flush` on the standard `File`
 /// is a no-op.
 pub struct SyncOnFlushFile(fs::File);
 
+impl SyncOnFlushFile {
+    /// Gets a reference to the underlying file.
+    pub fn get_ref(&self) -> &fs::File {
+        &self.0
+    }
+}
+
 impl From<fs::File> for SyncOnFlushFile {
     fn from(value: fs::File) -> Self {
         Self(value)
@@ -34,3 +44,161 @@ impl Seek for SyncOnFlushFile {
         self.0.seek(pos)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The implementation used for reading and writing data files.
+pub enum IoBackend {
+    /// Standard buffered IO via the page cache.
+    Std,
+    /// Direct IO submitted via io_uring, bypassing the page cache.
+    ///
+    /// This is only available on Linux.
+    Direct,
+}
+
+impl Default for IoBackend {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+impl IoBackend {
+    /// Selects direct IO if the kernel supports it, otherwise standard IO.
+    pub fn detect() -> Self {
+        #[cfg(target_os = "linux")]
+        if crate::direct::is_supported() {
+            return Self::Direct;
+        }
+
+        Self::Std
+    }
+
+    /// Opens a file for writing positioned at the end of any existing data.
+    ///
+    /// Direct IO falls back to standard IO if the filesystem does not support it.
+    pub fn open_writer(self, path: &Path, create: bool) -> io::Result<WriterFile> {
+        #[cfg(target_os = "linux")]
+        if self == Self::Direct {
+            match crate::direct::DirectFile::open(path, create) {
+                Ok(file) => return Ok(WriterFile::Direct(Box::new(file))),
+                Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                    warn!(
+                        path = %path.display(),
+                        "Filesystem does not support direct IO, using standard IO",
+                    );
+                },
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(create)
+            .open(path)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(WriterFile::Std(SyncOnFlushFile::from(file)))
+    }
+
+    /// Reads the whole file into memory if using direct IO.
+    ///
+    /// Returns `None` if the file should be read via standard IO instead,
+    /// either because it is the selected backend or the filesystem does not
+    /// support direct IO.
+    pub fn read_direct(self, path: &Path) -> io::Result<Option<AlignedBuffer>> {
+        #[cfg(target_os = "linux")]
+        if self == Self::Direct {
+            return match crate::direct::read_to_buffer(path) {
+                Ok(buffer) => Ok(Some(buffer)),
+                Err(e) if e.kind() == io::ErrorKind::InvalidInput => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+
+        let _ = path;
+        Ok(None)
+    }
+}
+
+/// A file opened for writing by an [IoBackend].
+pub enum WriterFile {
+    /// A standard file which syncs on flush.
+    Std(SyncOnFlushFile),
+    #[cfg(target_os = "linux")]
+    /// A direct IO file.
+    Direct(Box<crate::direct::DirectFile>),
+}
+
+impl WriterFile {
+    /// Reserves disk space for the file to grow up to `len` bytes.
+    ///
+    /// See [preallocate](crate::space::preallocate) for more info.
+    pub fn preallocate(&mut self, len: u64) -> io::Result<()> {
+        match self {
+            Self::Std(file) => crate::space::preallocate(file.get_ref(), 0, len),
+            #[cfg(target_os = "linux")]
+            Self::Direct(file) => file.preallocate(len),
+        }
+    }
+
+    /// Writes any data buffered by the file and releases any disk
+    /// space reserved beyond the end of the file.
+    ///
+    /// This should be called once no more data will be written to the file.
+    pub fn release_preallocation(&mut self) -> io::Result<()> {
+        match self {
+            Self::Std(file) => {
+                let len = file.stream_position()?;
+                crate::space::release_preallocation(file.get_ref(), len)
+            },
+            #[cfg(target_os = "linux")]
+            Self::Direct(file) => file.release_preallocation(),
+        }
+    }
+
+    /// Writes any data buffered by the file without syncing it.
+    ///
+    /// Standard files write straight to the page cache, so this is a no-op.
+    pub fn write_out(&mut self) -> io::Result<()> {
+        match self {
+            Self::Std(_) => Ok(()),
+            #[cfg(target_os = "linux")]
+            Self::Direct(file) => file.write_out(),
+        }
+    }
+}
+
+impl From<fs::File> for WriterFile {
+    fn from(value: fs::File) -> Self {
+        Self::Std(SyncOnFlushFile::from(value))
+    }
+}
+
+impl Write for WriterFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Std(file) => file.write(buf),
+            #[cfg(target_os = "linux")]
+            Self::Direct(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Std(file) => file.flush(),
+            #[cfg(target_os = "linux")]
+            Self::Direct(file) => file.flush(),
+        }
+    }
+}
+
+impl Seek for WriterFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Std(file) => file.seek(pos),
+            #[cfg(target_os = "linux")]
+            Self::Direct(file) => file.seek(pos),
+        }
+    }
+}
@@ -1 +1,9 @@
+#[macro_use]
+extern crate tracing;
+
+pub mod aligned;
+pub mod async_file;
+#[cfg(target_os = "linux")]
+pub mod direct;
 pub mod file;
+pub mod space;
@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Reserves disk space for `len` bytes of the file starting at `offset`.
+///
+/// The length of the file is not changed, so appending to the file
+/// behaves as normal while using the reserved space. This is a no-op
+/// if the platform or filesystem does not support preallocation.
+pub fn preallocate(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    if len == 0 {
+        return Ok(());
+    }
+
+    sys::preallocate(file, offset, len)
+}
+
+/// Releases any disk space reserved beyond the first `len` bytes of the file.
+///
+/// The file is truncated to `len` bytes, which must be the current
+/// length of the file.
+pub fn release_preallocation(file: &File, len: u64) -> io::Result<()> {
+    file.set_len(len)
+}
+
+/// Returns the number of bytes available for writing on the filesystem
+/// containing the given path.
+///
+/// Returns `None` if the platform does not support checking the free space.
+pub fn available_space(path: &Path) -> io::Result<Option<u64>> {
+    sys::available_space(path)
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io::{self, ErrorKind};
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    pub fn preallocate(file: &File, offset: u64, len: u64) -> io::Result<()> {
+        // SAFETY: The file descriptor is valid for the lifetime of the file.
+        let res = unsafe {
+            libc::fallocate(
+                file.as_raw_fd(),
+                libc::FALLOC_FL_KEEP_SIZE,
+                offset as libc::off_t,
+                len as libc::off_t,
+            )
+        };
+
+        if res == 0 {
+            return Ok(());
+        }
+
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => {
+                debug!("Filesystem does not support preallocation");
+                Ok(())
+            },
+            _ => Err(err),
+        }
+    }
+
+    #[allow(clippy::unnecessary_cast)] // The field types differ between targets.
+    pub fn available_space(path: &Path) -> io::Result<Option<u64>> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+
+        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: The path is a valid C string and the stat is only read on success.
+        let stat = unsafe {
+            if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            stat.assume_init()
+        };
+
+        Ok(Some(
+            (stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64),
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    pub fn preallocate(_file: &File, _offset: u64, _len: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn available_space(_path: &Path) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Seek, SeekFrom, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_preallocate_keeps_size() {
+        let path = std::env::temp_dir()
+            .join(format!("lnx-io-{}-preallocate", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"hello").unwrap();
+
+        preallocate(&file, 0, 1 << 20).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 5);
+        assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), 5);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_release_preallocation() {
+        use std::os::unix::fs::MetadataExt;
+
+        let path = std::env::temp_dir().join(format!(
+            "lnx-io-{}-release-preallocation",
+            std::process::id()
+        ));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"hello").unwrap();
+
+        preallocate(&file, 0, 1 << 20).unwrap();
+        let reserved_blocks = file.metadata().unwrap().blocks();
+
+        release_preallocation(&file, 5).unwrap();
+        let metadata = file.metadata().unwrap();
+        assert_eq!(metadata.len(), 5);
+        assert!(metadata.blocks() <= reserved_blocks);
+        if reserved_blocks >= (1 << 20) / 512 {
+            assert!(
+                metadata.blocks() < reserved_blocks,
+                "Reservation should be released"
+            );
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_available_space() {
+        if let Some(available) = available_space(&std::env::temp_dir()).unwrap() {
+            assert!(available > 0);
+        }
+    }
+}
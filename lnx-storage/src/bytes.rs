@@ -1,7 +1,9 @@
 use std::ops::{Deref, Range};
 use std::sync::Arc;
 
+use lnx_io::aligned::AlignedBuffer;
 use memmap2::Mmap;
+use rkyv::AlignedVec;
 
 #[derive(Clone)]
 /// A cheaply clone slice.
@@ -76,6 +78,26 @@ impl From<Vec<u8>> for SharedSlice {
     }
 }
 
+impl From<AlignedVec> for SharedSlice {
+    fn from(value: AlignedVec) -> Self {
+        Self {
+            start: 0,
+            end: value.len(),
+            data: Arc::new(MmapOrBytes::Aligned(value)),
+        }
+    }
+}
+
+impl From<AlignedBuffer> for SharedSlice {
+    fn from(value: AlignedBuffer) -> Self {
+        Self {
+            start: 0,
+            end: value.len(),
+            data: Arc::new(MmapOrBytes::Direct(value)),
+        }
+    }
+}
+
 impl<T> PartialEq<T> for SharedSlice
 where
     T: AsRef<[u8]>,
@@ -92,6 +114,10 @@ enum MmapOrBytes {
     Mmap(Mmap),
     /// In memory buffer.
     Bytes(Vec<u8>),
+    /// In memory buffer aligned for zero-copy reads.
+    Aligned(AlignedVec),
+    /// In memory buffer read via direct IO.
+    Direct(AlignedBuffer),
 }
 
 impl MmapOrBytes {
@@ -100,6 +126,8 @@ impl MmapOrBytes {
         match self {
             MmapOrBytes::Mmap(map) => map.as_ref(),
             MmapOrBytes::Bytes(bytes) => bytes.as_slice(),
+            MmapOrBytes::Aligned(bytes) => bytes.as_slice(),
+            MmapOrBytes::Direct(bytes) => bytes,
         }
     }
 }
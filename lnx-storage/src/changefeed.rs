@@ -0,0 +1,527 @@
+use std::ops::Bound;
+use std::sync::Arc;
+
+use datacake::crdt::{HLCTimestamp, Key};
+use datacake::eventual_consistency::Document;
+use datacake_lmdb::heed::byteorder::{BigEndian, LittleEndian};
+use datacake_lmdb::heed::types::{ByteSlice, Str, Unit, U64};
+use datacake_lmdb::heed::{Database, Env};
+use datacake_lmdb::Error;
+use futures::{Stream, StreamExt};
+use hashbrown::{HashMap, HashSet};
+use parking_lot::RwLock;
+
+use crate::listeners::{EventFilter, ListenerManager, DEFAULT_EVENT_BUFFER_SIZE};
+use crate::store::{INDEX_FRAGMENTS, INDEX_METADATA};
+
+/// The maximum number of changes read from the feed at once when tailing.
+const TAIL_BATCH_SIZE: usize = 500;
+
+const OP_PUT: u8 = 0;
+const OP_DELETE: u8 = 1;
+
+/// The size of the header of an encoded change, the op, doc ID and timestamp.
+const CHANGE_HEADER_SIZE: usize = 17;
+
+/// The database of the keyspaces known to the KV store.
+///
+/// This and the per-keyspace databases must match the layout of
+/// [LmdbStorage](datacake_lmdb::LmdbStorage).
+const KV_KEYSPACE_LIST: &str = "datacake-keyspace";
+
+type KvDB = Database<U64<LittleEndian>, ByteSlice>;
+type MetaDB = Database<U64<LittleEndian>, U64<LittleEndian>>;
+type KeyspaceDB = Database<Str, Unit>;
+
+#[derive(Debug, Clone, Default)]
+/// Options for the durable change feed.
+pub struct ChangeFeedOptions {
+    /// Should changes be recorded to the change feed.
+    pub enabled: bool,
+    /// The keyspaces which should be recorded.
+    ///
+    /// If this is `None` all keyspaces are recorded.
+    pub keyspaces: Option<HashSet<String>>,
+}
+
+impl ChangeFeedOptions {
+    /// Is the given keyspace being recorded.
+    ///
+    /// The internal fragment and index metadata keyspaces are never recorded.
+    pub fn is_recorded(&self, keyspace: &str) -> bool {
+        self.enabled
+            && keyspace != INDEX_FRAGMENTS
+            && keyspace != INDEX_METADATA
+            && self
+                .keyspaces
+                .as_ref()
+                .map(|keyspaces| keyspaces.contains(keyspace))
+                .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A position within the change feed of a keyspace.
+///
+/// Reading from a cursor returns all changes which occurred after it.
+pub struct ChangeCursor {
+    /// The local sequence number of the change.
+    ///
+    /// Sequence numbers increase with each change recorded by the node,
+    /// they are not comparable between nodes.
+    pub sequence: u64,
+}
+
+impl ChangeCursor {
+    /// The size of a serialized cursor.
+    pub const SIZE: usize = 8;
+
+    /// Serializes the cursor as bytes.
+    ///
+    /// The cursor is big endian encoded so it sorts by sequence number.
+    pub fn as_bytes(&self) -> [u8; Self::SIZE] {
+        self.sequence.to_be_bytes()
+    }
+
+    /// Deserializes the cursor from bytes.
+    pub fn from_bytes(slice: [u8; Self::SIZE]) -> Self {
+        Self {
+            sequence: u64::from_be_bytes(slice),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The type of change which occurred.
+pub enum ChangeKind {
+    /// The document was inserted or updated with the given data.
+    Put(Vec<u8>),
+    /// The document was deleted.
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single change recorded in the change feed.
+pub struct ChangeEntry {
+    /// The position of the change in the feed.
+    pub cursor: ChangeCursor,
+    /// The ID of the document which was changed.
+    pub doc_id: Key,
+    /// The HLC timestamp of the change.
+    pub timestamp: HLCTimestamp,
+    /// The change which occurred.
+    pub kind: ChangeKind,
+}
+
+#[derive(Clone)]
+/// A durable and ordered log of changes for each keyspace.
+///
+/// Changes are keyed by a local sequence number allowing consumers to resume
+/// reading from a [ChangeCursor] after a restart. Consumers can persist their
+/// own position via [ChangeFeed::commit_cursor].
+///
+/// Writes to recorded keyspaces are applied to the KV store's databases by the
+/// change feed, so each document and its change are written in a single transaction.
+/// Documents are always read from the KV store.
+///
+/// The fragment keyspace is never recorded.
+pub struct ChangeFeed {
+    env: Env,
+    changes: Database<ByteSlice, ByteSlice>,
+    sequences: Database<ByteSlice, U64<BigEndian>>,
+    cursors: Database<ByteSlice, ByteSlice>,
+    kv_keyspaces: KeyspaceDB,
+    kv_databases: Arc<RwLock<HashMap<String, (KvDB, MetaDB)>>>,
+    options: ChangeFeedOptions,
+    listeners: ListenerManager,
+}
+
+impl ChangeFeed {
+    /// Open the change feed for a given LMDB environment.
+    pub fn from_env(
+        env: Env,
+        options: ChangeFeedOptions,
+        listeners: ListenerManager,
+    ) -> Result<Self, Error> {
+        let mut txn = env.write_txn()?;
+        let changes = env.create_database(&mut txn, Some("lnx-change-feed"))?;
+        let sequences =
+            env.create_database(&mut txn, Some("lnx-change-feed-sequences"))?;
+        let cursors = env.create_database(&mut txn, Some("lnx-change-feed-cursors"))?;
+        let kv_keyspaces = env.create_database(&mut txn, Some(KV_KEYSPACE_LIST))?;
+        txn.commit()?;
+
+        Ok(Self {
+            env,
+            changes,
+            sequences,
+            cursors,
+            kv_keyspaces,
+            kv_databases: Arc::default(),
+            options,
+            listeners,
+        })
+    }
+
+    /// Is the given keyspace being recorded.
+    pub fn is_recorded(&self, keyspace: &str) -> bool {
+        self.options.is_recorded(keyspace)
+    }
+
+    /// Gets the KV store databases of the given keyspace, creating them if
+    /// they don't exist.
+    fn kv_databases(&self, keyspace: &str) -> Result<(KvDB, MetaDB), Error> {
+        if let Some(dbs) = self.kv_databases.read().get(keyspace) {
+            return Ok(*dbs);
+        }
+
+        // The databases are created in their own transaction as the handles
+        // would be closed if a write transaction was aborted.
+        let mut txn = self.env.write_txn()?;
+        self.kv_keyspaces.put(&mut txn, keyspace, &())?;
+        let kv = self
+            .env
+            .create_database(&mut txn, Some(&format!("datacake-{keyspace}-kv")))?;
+        let meta = self
+            .env
+            .create_database(&mut txn, Some(&format!("datacake-{keyspace}-meta")))?;
+        txn.commit()?;
+
+        self.kv_databases
+            .write()
+            .insert(keyspace.to_owned(), (kv, meta));
+
+        Ok((kv, meta))
+    }
+
+    /// Writes a set of documents to a recorded keyspace.
+    ///
+    /// Each change is recorded in the same transaction as the document, so a
+    /// document is never stored without its change being recorded.
+    pub(crate) fn put_documents(
+        &self,
+        keyspace: &str,
+        docs: &[Document],
+    ) -> Result<(), Error> {
+        let (kv, meta) = self.kv_databases(keyspace)?;
+
+        let mut txn = self.env.write_txn()?;
+        let mut sequence = self.sequences.get(&txn, keyspace.as_bytes())?.unwrap_or(0);
+
+        let mut value = Vec::new();
+        for doc in docs {
+            kv.put(&mut txn, &doc.id(), doc.data())?;
+            meta.put(&mut txn, &doc.id(), &doc.last_updated().as_u64())?;
+
+            sequence += 1;
+            encode_change(&mut value, OP_PUT, doc.id(), doc.last_updated(), doc.data());
+            self.changes
+                .put(&mut txn, &change_key(keyspace, sequence), &value)?;
+        }
+
+        self.sequences
+            .put(&mut txn, keyspace.as_bytes(), &sequence)?;
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Marks a set of documents in a recorded keyspace as deleted.
+    ///
+    /// Each change is recorded in the same transaction as the tombstone.
+    pub(crate) fn delete_documents(
+        &self,
+        keyspace: &str,
+        docs: impl IntoIterator<Item = (Key, HLCTimestamp)>,
+    ) -> Result<(), Error> {
+        let (kv, meta) = self.kv_databases(keyspace)?;
+
+        let mut txn = self.env.write_txn()?;
+        let mut sequence = self.sequences.get(&txn, keyspace.as_bytes())?.unwrap_or(0);
+
+        let mut value = Vec::new();
+        for (doc_id, ts) in docs {
+            kv.delete(&mut txn, &doc_id)?;
+            meta.put(&mut txn, &doc_id, &ts.as_u64())?;
+
+            sequence += 1;
+            encode_change(&mut value, OP_DELETE, doc_id, ts, &[]);
+            self.changes
+                .put(&mut txn, &change_key(keyspace, sequence), &value)?;
+        }
+
+        self.sequences
+            .put(&mut txn, keyspace.as_bytes(), &sequence)?;
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Reads up to `limit` changes from the keyspace which occurred after the
+    /// given cursor.
+    ///
+    /// If no cursor is provided, changes are read from the start of the feed.
+    pub fn read(
+        &self,
+        keyspace: &str,
+        after: Option<ChangeCursor>,
+        limit: usize,
+    ) -> Result<Vec<ChangeEntry>, Error> {
+        let prefix = keyspace_prefix(keyspace);
+        let start = match after {
+            Some(cursor) => Bound::Excluded(change_key(keyspace, cursor.sequence)),
+            None => Bound::Included(prefix.clone()),
+        };
+        let end = Bound::Included(change_key(keyspace, u64::MAX));
+        let range = (as_slice_bound(&start), as_slice_bound(&end));
+
+        let txn = self.env.read_txn()?;
+        let mut entries = Vec::new();
+        for row in self.changes.range(&txn, &range)?.take(limit) {
+            let (key, value) = row?;
+            let cursor = ChangeCursor::from_bytes(
+                key[prefix.len()..]
+                    .try_into()
+                    .expect("Corrupted change key, this is a bug"),
+            );
+
+            match decode_change(cursor, value) {
+                Some(entry) => entries.push(entry),
+                None => {
+                    warn!(
+                        keyspace = keyspace,
+                        cursor = ?cursor,
+                        "Skipping corrupted change entry",
+                    );
+                },
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Removes all changes from the keyspace up to and including the given cursor.
+    ///
+    /// This should be called once all consumers have processed the changes.
+    pub fn truncate(&self, keyspace: &str, up_to: ChangeCursor) -> Result<usize, Error> {
+        let start = Bound::Included(keyspace_prefix(keyspace));
+        let end = Bound::Included(change_key(keyspace, up_to.sequence));
+        let range = (as_slice_bound(&start), as_slice_bound(&end));
+
+        let mut txn = self.env.write_txn()?;
+        let num_removed = self.changes.delete_range(&mut txn, &range)?;
+        txn.commit()?;
+
+        Ok(num_removed)
+    }
+
+    /// Persists the position of a named consumer in the keyspace's feed.
+    pub fn commit_cursor(
+        &self,
+        consumer: &str,
+        keyspace: &str,
+        cursor: ChangeCursor,
+    ) -> Result<(), Error> {
+        let key = consumer_key(consumer, keyspace);
+
+        let mut txn = self.env.write_txn()?;
+        self.cursors.put(&mut txn, &key, &cursor.as_bytes())?;
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Gets the last committed position of a named consumer in the keyspace's feed.
+    pub fn get_cursor(
+        &self,
+        consumer: &str,
+        keyspace: &str,
+    ) -> Result<Option<ChangeCursor>, Error> {
+        let key = consumer_key(consumer, keyspace);
+
+        let txn = self.env.read_txn()?;
+        let cursor = self.cursors.get(&txn, &key)?.map(|bytes| {
+            ChangeCursor::from_bytes(
+                bytes
+                    .try_into()
+                    .expect("Corrupted change cursor, this is a bug"),
+            )
+        });
+
+        Ok(cursor)
+    }
+
+    /// Tails the changes of a given keyspace starting after the given cursor.
+    ///
+    /// The stream yields the existing changes before waiting for new changes
+    /// to be recorded, it never ends unless an error occurs.
+    pub fn tail(
+        &self,
+        keyspace: &str,
+        after: Option<ChangeCursor>,
+    ) -> impl Stream<Item = Result<ChangeEntry, Error>> + Send + 'static {
+        // We subscribe before reading so no changes are missed between the
+        // read and waiting for new events.
+        let events = self.listeners.subscribe(
+            EventFilter::default().keyspace(keyspace),
+            DEFAULT_EVENT_BUFFER_SIZE,
+        );
+
+        let state = TailState {
+            feed: self.clone(),
+            keyspace: keyspace.to_owned(),
+            cursor: after,
+            pending: Vec::new().into_iter(),
+            events,
+        };
+
+        futures::stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            loop {
+                if let Some(entry) = state.pending.next() {
+                    state.cursor = Some(entry.cursor);
+                    return Some((Ok(entry), Some(state)));
+                }
+
+                let feed = state.feed.clone();
+                let keyspace = state.keyspace.clone();
+                let cursor = state.cursor;
+                let result = state
+                    .feed
+                    .listeners
+                    .executor()
+                    .spawn_task(
+                        async move { feed.read(&keyspace, cursor, TAIL_BATCH_SIZE) },
+                    )
+                    .await
+                    .expect("Join task");
+
+                match result {
+                    Ok(entries) if entries.is_empty() => {
+                        // Any event on the keyspace means we should check
+                        // for changes again.
+                        state.events.next().await?;
+                        while state.events.try_next_event().is_some() {}
+                    },
+                    Ok(entries) => state.pending = entries.into_iter(),
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+        })
+    }
+}
+
+struct TailState {
+    feed: ChangeFeed,
+    keyspace: String,
+    cursor: Option<ChangeCursor>,
+    pending: std::vec::IntoIter<ChangeEntry>,
+    events: crate::listeners::EventStream,
+}
+
+/// The prefix of all change keys for the given keyspace.
+///
+/// The keyspace is length prefixed so keyspaces sharing a common
+/// prefix do not overlap.
+fn keyspace_prefix(keyspace: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(keyspace.len() + 4 + ChangeCursor::SIZE);
+    key.extend_from_slice(&(keyspace.len() as u32).to_be_bytes());
+    key.extend_from_slice(keyspace.as_bytes());
+    key
+}
+
+fn change_key(keyspace: &str, sequence: u64) -> Vec<u8> {
+    let mut key = keyspace_prefix(keyspace);
+    key.extend_from_slice(&ChangeCursor { sequence }.as_bytes());
+    key
+}
+
+/// Encodes a change as its op, doc ID, timestamp and the document data.
+fn encode_change(
+    buffer: &mut Vec<u8>,
+    op: u8,
+    doc_id: Key,
+    ts: HLCTimestamp,
+    data: &[u8],
+) {
+    buffer.clear();
+    buffer.push(op);
+    buffer.extend_from_slice(&doc_id.to_be_bytes());
+    buffer.extend_from_slice(&ts.as_u64().to_be_bytes());
+    buffer.extend_from_slice(data);
+}
+
+fn decode_change(cursor: ChangeCursor, value: &[u8]) -> Option<ChangeEntry> {
+    if value.len() < CHANGE_HEADER_SIZE {
+        return None;
+    }
+
+    let doc_id = u64::from_be_bytes(value[1..9].try_into().unwrap());
+    let ts = u64::from_be_bytes(value[9..17].try_into().unwrap());
+    let kind = match value[0] {
+        OP_PUT => ChangeKind::Put(value[CHANGE_HEADER_SIZE..].to_vec()),
+        OP_DELETE => ChangeKind::Delete,
+        _ => return None,
+    };
+
+    Some(ChangeEntry {
+        cursor,
+        doc_id,
+        timestamp: HLCTimestamp::from_u64(ts),
+        kind,
+    })
+}
+
+fn consumer_key(consumer: &str, keyspace: &str) -> Vec<u8> {
+    let mut key = keyspace_prefix(keyspace);
+    key.extend_from_slice(consumer.as_bytes());
+    key
+}
+
+fn as_slice_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.as_slice()),
+        Bound::Excluded(key) => Bound::Excluded(key.as_slice()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_key_ordering() {
+        let a = change_key("a", 2);
+        let b = change_key("a", 256);
+        let c = change_key("ab", 0);
+        assert!(a < b, "Changes should be ordered by sequence");
+        assert!(
+            !c.starts_with(&keyspace_prefix("a")),
+            "Keyspaces should not overlap"
+        );
+
+        let cursor = ChangeCursor { sequence: 12 };
+        assert_eq!(ChangeCursor::from_bytes(cursor.as_bytes()), cursor);
+    }
+
+    #[test]
+    fn test_change_encoding() {
+        let ts = HLCTimestamp::from_u64(1 << 20);
+        let cursor = ChangeCursor { sequence: 3 };
+        let mut buffer = Vec::new();
+
+        encode_change(&mut buffer, OP_PUT, 4, ts, b"Hello, world");
+        let entry = decode_change(cursor, &buffer).expect("Decode change");
+        assert_eq!(entry.cursor, cursor);
+        assert_eq!(entry.doc_id, 4);
+        assert_eq!(entry.timestamp.as_u64(), ts.as_u64());
+        assert_eq!(entry.kind, ChangeKind::Put(b"Hello, world".to_vec()));
+
+        encode_change(&mut buffer, OP_DELETE, 4, ts, &[]);
+        let entry = decode_change(cursor, &buffer).expect("Decode change");
+        assert_eq!(entry.kind, ChangeKind::Delete);
+
+        assert!(decode_change(cursor, &buffer[..8]).is_none());
+    }
+}
@@ -124,10 +124,7 @@ impl TaskDistributor {
             fragment_id,
             blocks: blocks
                 .into_iter()
-                .map(|block| {
-                    memory_usage += block.block.data().len();
-                    block
-                })
+                .inspect(|block| memory_usage += block.block.data().len())
                 .collect(),
         };
 
@@ -1,12 +1,27 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::ErrorKind;
 use std::ops::Range;
 
 use bytecheck::CheckBytes;
-use rkyv::{Archive, Deserialize, Serialize};
+use hashbrown::HashMap;
+use rkyv::vec::ArchivedVec;
+use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
+
+use crate::SharedSlice;
 
 // pub const BLOCK_CAPACITY: usize = 512 << 10;
 // pub const COMPRESSION_LEVEL: i32 = 1;
 pub type BlockId = u64;
 pub type BlockLocations = Vec<(BlockId, BlockInfo)>;
+/// The sorted block index written to sealed fragments.
+pub type BlockIndex = Vec<BlockIndexEntry>;
+
+/// The alignment the block index is written with in the fragment.
+///
+/// This allows the archived index to be accessed in place from
+/// the memory map without copying it into an aligned buffer.
+pub const BLOCK_INDEX_ALIGNMENT: usize = 16;
 
 #[repr(C)]
 #[derive(Serialize, Deserialize, Archive, Debug, Clone, Eq, PartialEq)]
@@ -30,6 +45,116 @@ impl BlockInfo {
     }
 }
 
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug, Clone, Eq, PartialEq)]
+#[archive_attr(derive(CheckBytes, Debug))]
+/// A single entry of the sorted block index.
+pub struct BlockIndexEntry {
+    /// The ID of the block.
+    pub block_id: BlockId,
+    /// The location and checksum of the block.
+    pub info: BlockInfo,
+}
+
+/// Creates the sorted block index from the given block locations.
+///
+/// If a block is written multiple times, the last location is used.
+pub fn build_block_index(locations: &[(BlockId, BlockInfo)]) -> BlockIndex {
+    let sorted: BTreeMap<BlockId, BlockInfo> = locations.iter().cloned().collect();
+    sorted
+        .into_iter()
+        .map(|(block_id, info)| BlockIndexEntry { block_id, info })
+        .collect()
+}
+
+/// The block location table of a sealed fragment.
+pub enum BlockTable {
+    /// A sorted archived index which is validated once and then
+    /// searched in place.
+    Archived(SharedSlice),
+    /// A table deserialized from the legacy unsorted block locations.
+    Owned(HashMap<BlockId, BlockInfo>),
+}
+
+impl BlockTable {
+    /// Validates the archived block index and creates a new table
+    /// which reads directly from the buffer.
+    pub fn from_archived(mut bytes: SharedSlice) -> io::Result<Self> {
+        if bytes.as_ptr() as usize & (BLOCK_INDEX_ALIGNMENT - 1) != 0 {
+            warn!("Block index is not aligned, copying index into an aligned buffer");
+            let mut aligned = AlignedVec::with_capacity(bytes.len());
+            aligned.extend_from_slice(&bytes);
+            bytes = SharedSlice::from(aligned);
+        }
+
+        rkyv::check_archived_root::<BlockIndex>(&bytes).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "Failed to read block index, fragment is corrupted and must be repaired",
+            )
+        })?;
+
+        Ok(Self::Archived(bytes))
+    }
+
+    /// The number of blocks in the table.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Archived(_) => self.archived_entries().len(),
+            Self::Owned(blocks) => blocks.len(),
+        }
+    }
+
+    /// Returns if the table contains no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the info of a given block.
+    pub fn get(&self, block_id: BlockId) -> Option<BlockInfo> {
+        match self {
+            Self::Archived(_) => {
+                let entries = self.archived_entries();
+                let idx = entries
+                    .binary_search_by_key(&block_id, |entry| entry.block_id)
+                    .ok()?;
+                Some(to_block_info(&entries[idx].info))
+            },
+            Self::Owned(blocks) => blocks.get(&block_id).cloned(),
+        }
+    }
+
+    /// Get an iterator over all blocks in the table.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (BlockId, BlockInfo)> + '_> {
+        match self {
+            Self::Archived(_) => Box::new(
+                self.archived_entries()
+                    .iter()
+                    .map(|entry| (entry.block_id, to_block_info(&entry.info))),
+            ),
+            Self::Owned(blocks) => {
+                Box::new(blocks.iter().map(|(id, info)| (*id, info.clone())))
+            },
+        }
+    }
+
+    fn archived_entries(&self) -> &ArchivedVec<ArchivedBlockIndexEntry> {
+        match self {
+            // SAFETY:
+            //      The buffer is validated when the table is created and is immutable.
+            Self::Archived(bytes) => unsafe { rkyv::archived_root::<BlockIndex>(bytes) },
+            Self::Owned(_) => unreachable!("Table is not archived"),
+        }
+    }
+}
+
+fn to_block_info(info: &ArchivedBlockInfo) -> BlockInfo {
+    BlockInfo {
+        location: info.location.start..info.location.end,
+        checksum: info.checksum,
+    }
+}
+
 // /// The document block encoder
 // ///
 // /// The encoder serializes documents into bytes and records
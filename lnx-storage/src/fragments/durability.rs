@@ -0,0 +1,49 @@
+use bytecheck::CheckBytes;
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[repr(u8)]
+#[derive(
+    Serialize,
+    Deserialize,
+    Archive,
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[archive_attr(derive(CheckBytes, Debug))]
+/// The durability guarantee of blocks written to a fragment.
+///
+/// Fragments are always synced to disk when they are sealed, the mode
+/// only affects the blocks of fragments which are still being written.
+pub enum DurabilityMode {
+    #[default]
+    /// The fragment is synced to disk before each write is acknowledged.
+    Sync,
+    /// The fragment is synced to disk periodically, writes are acknowledged
+    /// before they are synced.
+    ///
+    /// Blocks written since the last sync may be lost if the node crashes.
+    GroupSync,
+    /// The fragment is never explicitly synced until it is sealed, relying
+    /// on the other replicas for durability.
+    ///
+    /// Any blocks not yet written back by the OS may be lost if the node crashes.
+    ReplicationOnly,
+}
+
+impl DurabilityMode {
+    /// Get the mode from its `u8` representation.
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Sync),
+            1 => Some(Self::GroupSync),
+            2 => Some(Self::ReplicationOnly),
+            _ => None,
+        }
+    }
+}
@@ -1,26 +1,35 @@
 use std::collections::BTreeMap;
-use std::fs::OpenOptions;
 use std::io;
-use std::io::ErrorKind;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use bytecheck::CheckBytes;
+use datacake_lmdb::heed;
 use exponential_backoff::Backoff;
 use hashbrown::HashMap;
 use parking_lot::RwLock;
 use puppet::ActorMailbox;
 use rkyv::{Archive, Deserialize, Serialize};
-
-use crate::fragments::writer::{Flush, GetCurrentState, RemoveOnDrop, Seal, WriteFile};
+use tokio::time::MissedTickBehavior;
+
+use crate::fragments::writer::{
+    Flush,
+    GetCurrentState,
+    RemoveOnDrop,
+    Seal,
+    SyncPending,
+    WriteFile,
+};
 use crate::listeners::{FragmentListener, ListenerManager};
 use crate::{EnvCtx, SharedSlice};
 
 mod block;
+mod durability;
 mod reader;
 mod writer;
 
 pub use self::block::{BlockId, BlockInfo};
+pub use self::durability::DurabilityMode;
 pub use self::reader::FragmentReader;
 pub use self::writer::{
     FragmentStream,
@@ -70,6 +79,21 @@ pub struct FragmentInfo {
     pub child_of_fragments: Vec<u64>,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Insufficient disk space, {available} bytes are available but {min_free} bytes \
+     must be kept free"
+)]
+/// The free disk space is below the configured watermark so new data cannot be written.
+///
+/// This is returned within an [io::Error] and can be retrieved via [io::Error::get_ref].
+pub struct InsufficientDiskSpace {
+    /// The number of bytes available on disk.
+    pub available: u64,
+    /// The configured minimum number of free bytes.
+    pub min_free: u64,
+}
+
 #[derive(Clone)]
 /// The segment writer manager.
 ///
@@ -87,6 +111,10 @@ pub struct IndexFragmentsWriters {
     metastore: Metastore,
     /// Event listeners and notifications.
     listeners: ListenerManager,
+    /// The durability blocks are written with if none is specified.
+    ///
+    /// This is persisted in the index metadata.
+    durability: Arc<RwLock<DurabilityMode>>,
 }
 
 impl IndexFragmentsWriters {
@@ -96,25 +124,82 @@ impl IndexFragmentsWriters {
         metastore: Metastore,
         writers: HashMap<u64, ActorMailbox<FragmentWriter>>,
         listeners: ListenerManager,
+        durability: DurabilityMode,
     ) -> Self {
         let slf = Self {
             env,
             active_writers: Arc::new(RwLock::new(writers)),
             metastore,
             listeners,
+            durability: Arc::new(RwLock::new(durability)),
         };
 
         slf.listeners.register_fragment_listener(slf.clone());
+        slf.start_group_sync();
 
         slf
     }
 
+    /// The durability blocks are written with if none is specified.
+    pub fn default_durability(&self) -> DurabilityMode {
+        *self.durability.read()
+    }
+
+    /// Set the durability blocks are written with if none is specified.
+    ///
+    /// The durability is persisted in the index metadata and is kept
+    /// across restarts.
+    pub fn set_default_durability(
+        &self,
+        durability: DurabilityMode,
+    ) -> Result<(), heed::Error> {
+        let mut current = self.durability.write();
+        self.metastore.put_durability(durability)?;
+        *current = durability;
+        Ok(())
+    }
+
+    /// Starts the background task which periodically syncs any writers
+    /// using [DurabilityMode::GroupSync].
+    ///
+    /// The task stops once the writers are dropped.
+    fn start_group_sync(&self) {
+        let active_writers = Arc::downgrade(&self.active_writers);
+        let period = self.env.group_sync_interval;
+
+        self.env.executor.spawn_task(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                let writers = match active_writers.upgrade() {
+                    Some(writers) => {
+                        writers.read().values().cloned().collect::<Vec<_>>()
+                    },
+                    None => break,
+                };
+
+                for writer in writers {
+                    if let Err(e) = writer.send(SyncPending).await {
+                        error!(error = ?e, "Failed to sync fragment writer");
+                    }
+                }
+            }
+        });
+    }
+
     #[instrument(name = "open-fragment-writer", skip_all)]
     /// This will use the live writer if it already exists otherwise, a writer
     /// will be opened.
+    ///
+    /// New fragment files reserve `size_hint` bytes on disk if provided,
+    /// otherwise the configured default preallocation.
     async fn get_writer(
         &self,
         fragment_id: u64,
+        size_hint: Option<u64>,
     ) -> io::Result<ActorMailbox<FragmentWriter>> {
         if let Some(writer) = self.active_writers.read().get(&fragment_id).cloned() {
             trace!("Using cached writer");
@@ -125,15 +210,18 @@ impl IndexFragmentsWriters {
             crate::resolvers::get_fragment_location(&self.env.root_path, fragment_id);
         info!(path = %path.display(), "Opening new fragment writer");
 
-        let mut options = OpenOptions::new();
-        options.write(true);
-        options.create(true);
+        let io_backend = self.env.io_backend;
+        let preallocate = size_hint.unwrap_or(self.env.fragment_preallocation);
 
         self.sync_directory().await?;
 
-        let file = tokio::task::spawn_blocking(move || options.open(path))
-            .await
-            .expect("Join thread")?;
+        let file = tokio::task::spawn_blocking(move || {
+            let mut file = io_backend.open_writer(&path, true)?;
+            file.preallocate(preallocate)?;
+            Ok::<_, io::Error>(file)
+        })
+        .await
+        .expect("Join thread")?;
 
         let writer = FragmentWriter::new(
             self.env.clone(),
@@ -145,7 +233,7 @@ impl IndexFragmentsWriters {
         info!("Creating new blank fragment");
         self.metastore
             .create_new_fragment(fragment_id)
-            .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
 
         self.active_writers
             .write()
@@ -177,7 +265,8 @@ impl IndexFragmentsWriters {
         file: String,
         bytes: SharedSlice,
     ) -> io::Result<()> {
-        let writer = self.get_writer(fragment_id).await?;
+        self.ensure_disk_space().await?;
+        let writer = self.get_writer(fragment_id, None).await?;
         writer
             .send(WriteFile {
                 file: file.clone(),
@@ -194,8 +283,10 @@ impl IndexFragmentsWriters {
         &self,
         fragment_id: u64,
         block_data: WriteDocBlock,
+        durability: DurabilityMode,
     ) -> io::Result<()> {
-        let writer = self.get_writer(fragment_id).await?;
+        self.ensure_disk_space().await?;
+        let writer = self.get_writer(fragment_id, None).await?;
         let doc = block_data.block.clone();
         let num_bytes = doc.data().len();
         let start = Instant::now();
@@ -204,7 +295,7 @@ impl IndexFragmentsWriters {
         //       This can help cut out duplicates early on.
         writer.send(block_data).await?;
 
-        writer.send(Flush).await?;
+        writer.send(Flush(durability)).await?;
 
         // TODO: Optimise with smallvec
         self.listeners
@@ -221,8 +312,10 @@ impl IndexFragmentsWriters {
         &self,
         fragment_id: u64,
         blocks: &[WriteDocBlock],
+        durability: DurabilityMode,
     ) -> io::Result<()> {
-        let writer = self.get_writer(fragment_id).await?;
+        self.ensure_disk_space().await?;
+        let writer = self.get_writer(fragment_id, None).await?;
         let mut num_bytes = 0;
         let mut docs = Vec::with_capacity(blocks.len());
         let start = Instant::now();
@@ -235,7 +328,7 @@ impl IndexFragmentsWriters {
             writer.send(block_data.clone()).await?;
         }
 
-        writer.send(Flush).await?;
+        writer.send(Flush(durability)).await?;
 
         // TODO: Optimise with smallvec
         self.listeners
@@ -248,12 +341,16 @@ impl IndexFragmentsWriters {
 
     #[instrument(name = "fragment-write-stream", skip(self, stream))]
     /// Writes the incoming fragment stream to given fragment.
+    ///
+    /// The `size_hint` is the expected total size of the fragment in bytes.
     pub async fn write_stream(
         &self,
         fragment_id: u64,
         stream: FragmentStream,
+        size_hint: Option<u64>,
     ) -> Result<(), StreamError> {
-        let writer = self.get_writer(fragment_id).await?;
+        self.ensure_disk_space().await?;
+        let writer = self.get_writer(fragment_id, size_hint).await?;
 
         let start = Instant::now();
         writer.send(stream).await?;
@@ -265,7 +362,7 @@ impl IndexFragmentsWriters {
     #[instrument(name = "fragment-seal", skip(self))]
     /// Seal written fragment
     pub async fn seal(&self, fragment_id: u64, info: FragmentInfo) -> io::Result<()> {
-        let writer = self.get_writer(fragment_id).await?;
+        let writer = self.get_writer(fragment_id, None).await?;
 
         let start = Instant::now();
         writer.send(Seal(info)).await?;
@@ -277,6 +374,40 @@ impl IndexFragmentsWriters {
         Ok(())
     }
 
+    /// Rejects new writes if the free disk space is below the configured watermark.
+    ///
+    /// Sealing fragments is still allowed as it only writes a small amount of metadata.
+    async fn ensure_disk_space(&self) -> io::Result<()> {
+        let min_free = self.env.min_free_disk_space;
+        if min_free == 0 {
+            return Ok(());
+        }
+
+        let path = self.env.root_path.clone();
+        let available =
+            tokio::task::spawn_blocking(move || lnx_io::space::available_space(&path))
+                .await
+                .expect("Join thread")?;
+        let available = match available {
+            Some(available) => available,
+            None => return Ok(()),
+        };
+
+        if available < min_free {
+            warn!(
+                available = available,
+                min_free = min_free,
+                "Rejecting write due to low disk space"
+            );
+            return Err(io::Error::other(InsufficientDiskSpace {
+                available,
+                min_free,
+            }));
+        }
+
+        Ok(())
+    }
+
     /// Sync the fragments directory to ensure fragments are correctly persisted.
     ///
     /// This is a no-op on windows.
@@ -284,15 +415,17 @@ impl IndexFragmentsWriters {
         #[cfg(unix)]
         {
             let path = self.env.root_path.clone();
-            lnx_executor::spawn_task(async move {
-                std::fs::OpenOptions::new()
-                    .read(true)
-                    .open(&crate::resolvers::fragments_folder(&path))?
-                    .sync_data()?;
-                Ok::<_, io::Error>(())
-            })
-            .await
-            .expect("Join task")?;
+            self.env
+                .executor
+                .spawn_task(async move {
+                    std::fs::OpenOptions::new()
+                        .read(true)
+                        .open(crate::resolvers::fragments_folder(&path))?
+                        .sync_data()?;
+                    Ok::<_, io::Error>(())
+                })
+                .await
+                .expect("Join task")?;
         }
 
         Ok(())
@@ -302,7 +435,7 @@ impl IndexFragmentsWriters {
 impl FragmentListener for IndexFragmentsWriters {
     fn on_delete(&self, fragment_id: u64) {
         if let Some(writer) = self.active_writers.write().remove(&fragment_id) {
-            lnx_executor::spawn_task(async move {
+            self.env.executor.spawn_task(async move {
                 writer.send(RemoveOnDrop).await;
             });
         }
@@ -350,11 +483,12 @@ impl IndexFragmentsReaders {
         let mut last_error = None;
         for wait_for in backoff.iter() {
             let slf = self.clone();
-            let result = lnx_executor::spawn_task(async move {
-                slf.try_add_new_reader_blocking(fragment_id)
-            })
-            .await
-            .map_err(|_| io::Error::new(ErrorKind::Other, "Executor task panicked"))?;
+            let result = self
+                .env
+                .executor
+                .spawn_task(async move { slf.try_add_new_reader_blocking(fragment_id) })
+                .await
+                .map_err(|_| io::Error::other("Executor task panicked"))?;
 
             match result {
                 Ok(()) => return Ok(()),
@@ -372,7 +506,7 @@ impl IndexFragmentsReaders {
         info!(fragment_id = fragment_id, "Opening fragment");
         let path =
             crate::resolvers::get_fragment_location(&self.env.root_path, fragment_id);
-        let reader = FragmentReader::open_mmap_blocking(path)?;
+        let reader = FragmentReader::open_blocking(self.env.io_backend, path)?;
         self.sealed_fragments.write().insert(fragment_id, reader);
 
         self.listeners.trigger_fragment_read_ready(fragment_id);
@@ -9,13 +9,22 @@ use std::{io, mem};
 use bytecheck::CheckBytes;
 use hashbrown::HashMap;
 use jocky::metadata::{get_metadata_offsets, SegmentMetadata, METADATA_HEADER_SIZE};
+use lnx_executor::Executor;
+use lnx_io::file::IoBackend;
 use memmap2::Mmap;
+use once_cell::sync::OnceCell;
 use rkyv::de::deserializers::SharedDeserializeMap;
 use rkyv::validation::validators::DefaultValidator;
 use rkyv::{AlignedVec, Archive, Deserialize};
 
-use crate::fragments::block::{BlockId, BlockInfo, BlockLocations};
-use crate::resolvers::{BLOCK_LOCATIONS_PATH, FRAGMENT_INFO_PATH};
+use crate::fragments::block::{BlockId, BlockInfo, BlockLocations, BlockTable};
+use crate::resolvers::{
+    fragment_vector_index_path,
+    BLOCK_INDEX_PATH,
+    BLOCK_LOCATIONS_PATH,
+    FRAGMENT_INFO_PATH,
+    FRAGMENT_SCHEMA_VERSION_PATH,
+};
 use crate::{FragmentInfo, SharedSlice, BLOCK_HEADER_SIZE};
 
 #[derive(Clone)]
@@ -26,7 +35,7 @@ pub struct FragmentReader {
     should_remove_on_drop: Arc<AtomicBool>,
     file_contents: SharedSlice,
     metadata: Arc<SegmentMetadata>,
-    blocks: Arc<HashMap<BlockId, BlockInfo>>,
+    blocks: Arc<OnceCell<BlockTable>>,
 }
 
 impl FragmentReader {
@@ -36,11 +45,31 @@ impl FragmentReader {
     pub fn new(
         bytes: SharedSlice,
         should_remove_on_drop: Arc<AtomicBool>,
+    ) -> io::Result<Self> {
+        let slf = Self::new_lazy(bytes, should_remove_on_drop)?;
+        slf.load_blocks()?;
+        Ok(slf)
+    }
+
+    /// Creates a new fragment reader from [OwnedBytes] only validating
+    /// the fragment footer.
+    ///
+    /// The block location table is loaded when it is first accessed.
+    pub fn new_lazy(
+        bytes: SharedSlice,
+        should_remove_on_drop: Arc<AtomicBool>,
     ) -> io::Result<Self> {
         let len = bytes.len();
+        if len < METADATA_HEADER_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Fragment is too small to contain a valid footer",
+            ));
+        }
+
         let offsets_slice = &bytes[len - METADATA_HEADER_SIZE..];
-        let (start, len) = get_metadata_offsets(offsets_slice)
-            .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+        let (start, len) =
+            get_metadata_offsets(offsets_slice).map_err(io::Error::other)?;
 
         let mut aligned_metadata = AlignedVec::with_capacity(len as usize);
         aligned_metadata
@@ -48,19 +77,59 @@ impl FragmentReader {
         let metadata = SegmentMetadata::from_buffer(&aligned_metadata)?;
         let fragment_info =
             deserialize_file::<FragmentInfo>(FRAGMENT_INFO_PATH, &metadata, &bytes)?;
-        let block_locations_iter =
-            deserialize_file::<BlockLocations>(BLOCK_LOCATIONS_PATH, &metadata, &bytes)?;
-        let block_locations = HashMap::from_iter(block_locations_iter);
 
         Ok(Self {
             info: Arc::new(fragment_info),
             should_remove_on_drop,
             file_contents: bytes,
             metadata: Arc::new(metadata),
-            blocks: Arc::new(block_locations),
+            blocks: Arc::new(OnceCell::new()),
+        })
+    }
+
+    /// Loads the block location table if it is not already loaded.
+    ///
+    /// Fragments containing a sorted block index are validated and read in place,
+    /// older fragments fall back to deserializing the block locations.
+    pub fn load_blocks(&self) -> io::Result<&BlockTable> {
+        self.blocks.get_or_try_init(|| {
+            if let Some(range) = self.metadata.get_location(BLOCK_INDEX_PATH) {
+                let range = range.start as usize..range.end as usize;
+                if range.end > self.file_contents.len() || range.start >= range.end {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Block index is outside of the fragment bounds",
+                    ));
+                }
+
+                return BlockTable::from_archived(self.file_contents.slice(range));
+            }
+
+            let block_locations_iter = deserialize_file::<BlockLocations>(
+                BLOCK_LOCATIONS_PATH,
+                &self.metadata,
+                &self.file_contents,
+            )?;
+            Ok(BlockTable::Owned(HashMap::from_iter(block_locations_iter)))
         })
     }
 
+    /// Gets the block location table, logging any errors which
+    /// occur while lazily loading it.
+    fn blocks(&self) -> Option<&BlockTable> {
+        match self.load_blocks() {
+            Ok(blocks) => Some(blocks),
+            Err(e) => {
+                error!(
+                    error = ?e,
+                    fragment_id = self.id(),
+                    "Failed to load fragment block locations",
+                );
+                None
+            },
+        }
+    }
+
     /// Get the fragment ID.
     pub fn id(&self) -> u64 {
         self.info.fragment_id
@@ -71,9 +140,36 @@ impl FragmentReader {
         &self.info
     }
 
+    /// Get the version of the index schema the fragment's documents were written with.
+    ///
+    /// Fragments without a schema version were written with the first version.
+    pub fn schema_version(&self) -> io::Result<u32> {
+        let bytes = match self.read_file(FRAGMENT_SCHEMA_VERSION_PATH) {
+            Some(bytes) => bytes,
+            None => return Ok(0),
+        };
+
+        let bytes: [u8; 4] = bytes.as_ref().try_into().map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "Fragment schema version is corrupted",
+            )
+        })?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Get the serialized vector index of a vector field of the fragment.
+    ///
+    /// Returns `None` if the fragment has no index for the field.
+    pub fn vector_index(&self, field: &str) -> Option<SharedSlice> {
+        self.read_file(&fragment_vector_index_path(field))
+    }
+
     /// Get an iterator over all blocks in the fragment.
-    pub fn get_fragment_blocks(&self) -> impl Iterator<Item = (&BlockId, &BlockInfo)> {
-        self.blocks.iter()
+    pub fn get_fragment_blocks(
+        &self,
+    ) -> io::Result<impl Iterator<Item = (BlockId, BlockInfo)> + '_> {
+        self.load_blocks().map(|blocks| blocks.iter())
     }
 
     /// Get an iterator over all files in the fragment.
@@ -81,10 +177,65 @@ impl FragmentReader {
         self.metadata.files().iter()
     }
 
+    /// Open a fragment reader using the given IO backend.
+    ///
+    /// Direct IO reads the whole fragment into memory, bypassing the page cache,
+    /// otherwise the fragment is memory mapped.
+    pub async fn open(
+        executor: &Executor,
+        io_backend: IoBackend,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        executor
+            .spawn_task(async move { Self::open_blocking(io_backend, path) })
+            .await
+            .expect("Spawn background thread")
+    }
+
+    /// Open a fragment reader using the given IO backend.
+    pub fn open_blocking(io_backend: IoBackend, path: PathBuf) -> io::Result<Self> {
+        let should_remove_on_drop = Arc::new(AtomicBool::new(false));
+        match io_backend.read_direct(&path)? {
+            Some(buffer) => Self::new(SharedSlice::from(buffer), should_remove_on_drop),
+            None => Self::open_mmap_blocking(path),
+        }
+    }
+
+    /// Open a fragment reader using the given IO backend only validating
+    /// the fragment footer.
+    ///
+    /// The block location table is loaded on first access.
+    pub async fn open_lazy(
+        executor: &Executor,
+        io_backend: IoBackend,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        executor
+            .spawn_task(async move {
+                let should_remove_on_drop = Arc::new(AtomicBool::new(false));
+                let bytes = match io_backend.read_direct(&path)? {
+                    Some(buffer) => SharedSlice::from(buffer),
+                    None => {
+                        let file = std::fs::File::open(path)?;
+                        SharedSlice::from(unsafe { Mmap::map(&file)? })
+                    },
+                };
+                Self::new_lazy(bytes, should_remove_on_drop)
+            })
+            .await
+            .expect("Spawn background thread")
+    }
+
     /// Open a fragment read
-    pub async fn open_mmap(path: impl AsRef<Path>) -> io::Result<Self> {
+    pub async fn open_mmap(
+        executor: &Executor,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
         let path = path.as_ref().to_path_buf();
-        lnx_executor::spawn_task(async move { Self::open_mmap_blocking(path) })
+        executor
+            .spawn_task(async move { Self::open_mmap_blocking(path) })
             .await
             .expect("Spawn background thread")
     }
@@ -97,6 +248,25 @@ impl FragmentReader {
         Self::new(SharedSlice::from(map), should_remove_on_drop)
     }
 
+    /// Open a fragment reader only validating the fragment footer.
+    ///
+    /// The block location table is loaded on first access.
+    pub async fn open_mmap_lazy(
+        executor: &Executor,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        executor
+            .spawn_task(async move {
+                let file = std::fs::File::open(path)?;
+                let map = unsafe { Mmap::map(&file)? };
+                let should_remove_on_drop = Arc::new(AtomicBool::new(false));
+                Self::new_lazy(SharedSlice::from(map), should_remove_on_drop)
+            })
+            .await
+            .expect("Spawn background thread")
+    }
+
     /// Read a virtual file from the fragment.
     pub fn read_file(&self, path: &str) -> Option<SharedSlice> {
         let range = self.metadata.get_location(path)?;
@@ -108,14 +278,14 @@ impl FragmentReader {
 
     /// Reads a block from the fragment but leaves it in it's compressed form.
     pub fn read_block(&self, id: u64) -> Option<SharedSlice> {
-        let info = self.blocks.get(&id)?.clone();
+        let info = self.blocks()?.get(id)?;
         Some(self.file_contents.slice(info.location_usize()))
     }
 
     /// Reads a block from the fragment but leaves it in it's compressed form with
     /// the metadata header attached.
     pub fn read_block_raw(&self, id: u64) -> Option<SharedSlice> {
-        let info = self.blocks.get(&id)?.clone();
+        let info = self.blocks()?.get(id)?;
         let range = info.location_usize();
         Some(
             self.file_contents
@@ -153,13 +323,23 @@ where
         ));
     }
 
-    let mut aligned = AlignedVec::with_capacity(range.len());
-    aligned.extend_from_slice(&data[range]);
+    // Files which are already suitably aligned can be read in place
+    // rather than being copied into a new buffer.
+    let file = &data[range];
+    let aligned;
+    let file = if file.as_ptr() as usize & (AlignedVec::ALIGNMENT - 1) == 0 {
+        file
+    } else {
+        let mut buffer = AlignedVec::with_capacity(file.len());
+        buffer.extend_from_slice(file);
+        aligned = buffer;
+        aligned.as_slice()
+    };
 
     // SAFETY:
     //      We ensure the target `T` is `'static` and contains only owned data so it's safe to
     //      temporarily extend the lifetime so we can allocate the type entirely.
-    let slice = unsafe { mem::transmute::<&[u8], &'static [u8]>(aligned.as_slice()) };
+    let slice = unsafe { mem::transmute::<&[u8], &'static [u8]>(file) };
     rkyv::from_bytes::<T>(slice).map_err(|_| {
         io::Error::new(
             ErrorKind::InvalidData,
@@ -170,7 +350,183 @@ where
 
 #[cfg(test)]
 mod tests {
+    use jocky::metadata::write_metadata_offsets;
+
     use super::*;
+    use crate::fragments::block::{build_block_index, BLOCK_INDEX_ALIGNMENT};
+
+    /// Builds a sealed fragment in memory containing the given blocks.
+    ///
+    /// If `indexed` is `false` the legacy block locations file is written
+    /// instead of the sorted block index.
+    fn build_fragment(
+        blocks: &[(BlockId, &[u8])],
+        indexed: bool,
+        corrupt_block_table: bool,
+    ) -> SharedSlice {
+        let mut buffer = AlignedVec::new();
+        let mut metadata = SegmentMetadata::default();
+        let mut block_locations = BlockLocations::new();
+
+        for (block_id, data) in blocks {
+            let start = buffer.len() as u32;
+            buffer.extend_from_slice(data);
+            block_locations.push((
+                *block_id,
+                BlockInfo {
+                    location: start..buffer.len() as u32,
+                    checksum: 0,
+                },
+            ));
+        }
+
+        let (path, mut block_table_bytes) = if indexed {
+            while buffer.len() & (BLOCK_INDEX_ALIGNMENT - 1) != 0 {
+                buffer.push(0);
+            }
+
+            let index = build_block_index(&block_locations);
+            let bytes = rkyv::to_bytes::<_, 4096>(&index).expect("Serialize index");
+            (BLOCK_INDEX_PATH, bytes.into_vec())
+        } else {
+            let bytes = rkyv::to_bytes::<_, 4096>(&block_locations)
+                .expect("Serialize block locations");
+            (BLOCK_LOCATIONS_PATH, bytes.into_vec())
+        };
+        if corrupt_block_table {
+            block_table_bytes.truncate(block_table_bytes.len() / 2);
+        }
+        let start = buffer.len() as u64;
+        buffer.extend_from_slice(&block_table_bytes);
+        metadata.add_file(path.to_string(), start..buffer.len() as u64);
+
+        let info = FragmentInfo {
+            fragment_id: 1,
+            orphaned_id: None,
+            num_blocks: blocks.len() as u32,
+            num_bytes_total: 0,
+            num_docs: 0,
+            child_of_fragments: vec![],
+        };
+        let info_bytes = rkyv::to_bytes::<_, 4096>(&info).expect("Serialize info");
+        let start = buffer.len() as u64;
+        buffer.extend_from_slice(&info_bytes);
+        metadata.add_file(FRAGMENT_INFO_PATH.to_string(), start..buffer.len() as u64);
+
+        let metadata_bytes = metadata.to_bytes().expect("Serialize metadata");
+        let start = buffer.len() as u64;
+        buffer.extend_from_slice(&metadata_bytes);
+
+        let mut offsets = Vec::new();
+        write_metadata_offsets(&mut offsets, start, metadata_bytes.len() as u64)
+            .expect("Write offsets");
+        buffer.extend_from_slice(&offsets);
+
+        SharedSlice::from(buffer)
+    }
+
+    #[test]
+    fn test_reader_open() {
+        for indexed in [false, true] {
+            let fragment =
+                build_fragment(&[(2, b"world"), (1, b"hello")], indexed, false);
+            let reader = FragmentReader::new(fragment, Arc::new(AtomicBool::new(false)))
+                .expect("Open reader");
+
+            assert_eq!(reader.id(), 1);
+            assert_eq!(reader.read_block(1).as_deref(), Some(b"hello".as_ref()));
+            assert_eq!(reader.read_block(2).as_deref(), Some(b"world".as_ref()));
+            assert!(reader.read_block(3).is_none());
+            assert_eq!(reader.schema_version().unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn test_block_index_is_archived() {
+        let blocks = (0..1_000u64)
+            .rev()
+            .map(|id| (id, b"data".as_ref()))
+            .collect::<Vec<_>>();
+        let fragment = build_fragment(&blocks, true, false);
+        let reader = FragmentReader::new(fragment, Arc::new(AtomicBool::new(false)))
+            .expect("Open reader");
+
+        let table = reader.load_blocks().expect("Load blocks");
+        assert!(
+            matches!(table, BlockTable::Archived(_)),
+            "Table should be read in place"
+        );
+        assert_eq!(table.len(), 1_000);
+
+        let ids = reader
+            .get_fragment_blocks()
+            .expect("Load blocks")
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            (0..1_000).collect::<Vec<_>>(),
+            "Index should be sorted"
+        );
+
+        for id in [0, 500, 999] {
+            assert_eq!(reader.read_block(id).as_deref(), Some(b"data".as_ref()));
+        }
+        assert!(reader.read_block(1_000).is_none());
+    }
+
+    #[test]
+    fn test_lazy_reader_defers_block_table() {
+        for indexed in [false, true] {
+            let fragment = build_fragment(&[(1, b"hello")], indexed, true);
+
+            let result =
+                FragmentReader::new(fragment.clone(), Arc::new(AtomicBool::new(false)));
+            assert!(
+                result.is_err(),
+                "Eager open should validate the block table"
+            );
+
+            let reader =
+                FragmentReader::new_lazy(fragment, Arc::new(AtomicBool::new(false)))
+                    .expect("Lazy open should only validate the footer");
+            assert_eq!(reader.id(), 1);
+
+            let error = reader
+                .load_blocks()
+                .err()
+                .expect("Block table is corrupted");
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            assert!(reader.read_block(1).is_none());
+        }
+    }
+
+    #[test]
+    fn test_reader_rejects_truncated_footer() {
+        let error = FragmentReader::new_lazy(
+            SharedSlice::from(vec![0; 4]),
+            Arc::new(AtomicBool::new(false)),
+        )
+        .err()
+        .expect("Footer is missing");
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_reader_open_backends() {
+        let fragment = build_fragment(&[(2, b"world"), (1, b"hello")], true, false);
+        let path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::write(&path, &fragment[..]).expect("Write fragment");
+
+        for io_backend in [IoBackend::Std, IoBackend::detect()] {
+            let reader = FragmentReader::open_blocking(io_backend, path.clone())
+                .expect("Open reader");
+            assert_eq!(reader.read_block(1).as_deref(), Some(b"hello".as_ref()));
+            assert_eq!(reader.read_block(2).as_deref(), Some(b"world".as_ref()));
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
 
     #[test]
     fn test_file_deserializer() {
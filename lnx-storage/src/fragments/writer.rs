@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
-use std::io::{BufWriter, ErrorKind, Seek, SeekFrom, Write};
+use std::io::{ErrorKind, Seek};
 use std::ops::Range;
 use std::time::Instant;
 use std::{io, mem};
@@ -12,15 +12,16 @@ use datacake::eventual_consistency::Document;
 use datacake::rpc::{Body, Status};
 use hyper::body::HttpBody;
 use jocky::metadata::{write_metadata_offsets, SegmentMetadata};
-use lnx_io::file::SyncOnFlushFile;
+use lnx_io::async_file::AsyncWriter;
+use lnx_io::file::WriterFile;
 use puppet::{derive_message, puppet_actor, ActorMailbox};
 use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
-use tokio::task::yield_now;
 
-use super::block::BlockLocations;
+use super::block::{build_block_index, BlockLocations, BLOCK_INDEX_ALIGNMENT};
+use super::durability::DurabilityMode;
 use crate::fragments::block::{BlockId, BlockInfo};
 use crate::metastore::{BlockMetadata, Metastore};
-use crate::resolvers::{BLOCK_LOCATIONS_PATH, FRAGMENT_INFO_PATH};
+use crate::resolvers::{BLOCK_INDEX_PATH, FRAGMENT_INFO_PATH};
 use crate::{EnvCtx, FragmentInfo, SharedSlice};
 
 /// The number of bytes that prefix a given block
@@ -34,10 +35,12 @@ pub struct FragmentWriter {
     cursor: usize,
     metadata: SegmentMetadata,
     block_locations: BlockLocations,
-    writer: BufWriter<SyncOnFlushFile>,
+    writer: AsyncWriter,
     block_metadata_changes: Vec<(BlockId, BlockMetadata)>,
     metastore: Metastore,
     should_remove_file_on_drop: bool,
+    /// If the writer has data which should be synced by the next group sync.
+    pending_group_sync: bool,
 }
 
 #[puppet_actor]
@@ -46,7 +49,7 @@ impl FragmentWriter {
     pub fn new(
         env: EnvCtx,
         id: u64,
-        file: impl Into<SyncOnFlushFile>,
+        file: impl Into<WriterFile>,
         metastore: Metastore,
     ) -> ActorMailbox<Self> {
         Self::from_existing_state(env, id, file, metastore, Vec::new())
@@ -56,25 +59,42 @@ impl FragmentWriter {
     pub fn from_existing_state(
         env: EnvCtx,
         id: u64,
-        file: impl Into<SyncOnFlushFile>,
+        file: impl Into<WriterFile>,
         metastore: Metastore,
         block_locations: BlockLocations,
     ) -> ActorMailbox<Self> {
         let (tx, rx) = flume::bounded(25);
 
+        // Recovered files are opened at the end of the existing data, so the
+        // cursor must start from there for block locations to be correct.
+        let mut file: WriterFile = file.into();
+        let cursor = match file.stream_position() {
+            Ok(pos) => pos as usize,
+            Err(e) => {
+                warn!(
+                    error = ?e,
+                    fragment_id = id,
+                    "Failed to get the writer file position",
+                );
+                0
+            },
+        };
+
+        let executor = env.executor.clone();
         let actor = Self {
             env,
             id,
-            cursor: 0,
+            cursor,
             metadata: SegmentMetadata::default(),
             block_locations,
-            writer: BufWriter::new(file.into()),
+            writer: AsyncWriter::new(file, cursor as u64),
             block_metadata_changes: Vec::new(),
             metastore,
             should_remove_file_on_drop: false,
+            pending_group_sync: false,
         };
 
-        lnx_executor::spawn_task(actor.run_actor(rx));
+        executor.spawn_task(actor.run_actor(rx));
 
         ActorMailbox::new(tx, Cow::Borrowed("block-writer-actor"))
     }
@@ -93,22 +113,23 @@ impl FragmentWriter {
     }
 
     #[instrument("fragment-io-write", skip_all)]
-    async fn write_all(&mut self, mut buffer: &[u8]) -> io::Result<usize> {
-        let mut yields = 0;
+    async fn write_all(&mut self, buffer: &[u8]) -> io::Result<usize> {
         let start = Instant::now();
-        loop {
-            let n = self.writer.write(buffer)?;
-            self.cursor += n;
+        self.writer.write_all(buffer).await?;
+        self.cursor += buffer.len();
 
-            if n == buffer.len() {
-                debug!(num_yields = yields, elapsed = ?start.elapsed(), "Write bytes");
-                return Ok(self.cursor);
-            }
+        debug!(elapsed = ?start.elapsed(), "Write bytes");
+        Ok(self.cursor)
+    }
 
-            buffer = &buffer[n..];
-            yield_now().await;
-            yields += 1;
+    /// Pads the file with zeros until the cursor is a multiple of `alignment`.
+    async fn write_padding(&mut self, alignment: usize) -> io::Result<()> {
+        let remainder = self.cursor % alignment;
+        if remainder != 0 {
+            let padding = vec![0u8; alignment - remainder];
+            self.write_all(&padding).await?;
         }
+        Ok(())
     }
 
     #[instrument("fragment-io-copy-stream", skip_all)]
@@ -157,25 +178,19 @@ impl FragmentWriter {
     }
 
     #[instrument("fragment-io-write-len", skip_all)]
-    fn write_block_header(
+    async fn write_block_header(
         &mut self,
         len: u32,
         id: u64,
         checksum: u32,
     ) -> io::Result<u32> {
-        let bytes = checksum.to_le_bytes();
-        self.writer.write_all(&bytes)?;
-        self.cursor += bytes.len();
-
-        let bytes = id.to_le_bytes();
-        self.writer.write_all(&bytes)?;
-        self.cursor += bytes.len();
-
-        let bytes = len.to_le_bytes();
-        self.writer.write_all(&bytes)?;
-        self.cursor += bytes.len();
+        let mut header = [0u8; BLOCK_HEADER_SIZE];
+        header[0..4].copy_from_slice(&checksum.to_le_bytes());
+        header[4..12].copy_from_slice(&id.to_le_bytes());
+        header[12..16].copy_from_slice(&len.to_le_bytes());
 
-        Ok(self.cursor as u32)
+        let cursor = self.write_all(&header).await?;
+        Ok(cursor as u32)
     }
 
     #[puppet]
@@ -187,10 +202,16 @@ impl FragmentWriter {
 
         // Write the length of the block as the prefix.
         // This lets us walk through the block to recover data.
-        let start = self.write_block_header(len as u32, msg.block.id(), msg.checksum)?;
-        let res = self.write_all(buffer).await;
+        let res = match self
+            .write_block_header(len as u32, msg.block.id(), msg.checksum)
+            .await
+        {
+            Ok(_) => self.write_all(buffer).await,
+            Err(e) => Err(e),
+        };
 
         if res.is_ok() {
+            let start = (cursor_start + BLOCK_HEADER_SIZE) as u32;
             let info = BlockInfo {
                 location: start..self.cursor as u32,
                 checksum: msg.checksum,
@@ -207,11 +228,7 @@ impl FragmentWriter {
         } else {
             // We attempt to reset the cursor here to prevent us having to do
             // more work in the recovery state and cut down on wasted space.
-            if let Err(e) = self
-                .writer
-                .get_mut()
-                .seek(SeekFrom::Start(cursor_start as u64))
-            {
+            if let Err(e) = self.writer.seek_to(cursor_start as u64).await {
                 warn!(error = ?e, "Failed to reset writer cursor, this may lead to write amplification");
             } else {
                 trace!(
@@ -231,7 +248,7 @@ impl FragmentWriter {
         let start = self.cursor;
 
         if let Err(e) = self.write_all(&msg.bytes).await {
-            if let Err(e) = self.writer.get_mut().seek(SeekFrom::Start(start as u64)) {
+            if let Err(e) = self.writer.seek_to(start as u64).await {
                 warn!(error = ?e, "Failed to reset writer cursor, this may lead to write amplification");
             } else {
                 trace!(
@@ -262,11 +279,7 @@ impl FragmentWriter {
         let res = self.copy_stream(msg).await;
 
         if res.is_err() {
-            if let Err(e) = self
-                .writer
-                .get_mut()
-                .seek(SeekFrom::Start(start_cursor as u64))
-            {
+            if let Err(e) = self.writer.seek_to(start_cursor as u64).await {
                 warn!(error = ?e, "Failed to reset writer cursor, this may lead to write amplification");
             } else {
                 trace!(
@@ -284,22 +297,48 @@ impl FragmentWriter {
 
     #[puppet]
     /// Attempt to flush the buffer contents to disk.
-    async fn flush(&mut self, _msg: Flush) -> io::Result<()> {
+    async fn flush(&mut self, msg: Flush) -> io::Result<()> {
         let start = Instant::now();
-        self.writer.flush()?;
+        match msg.0 {
+            DurabilityMode::Sync => self.sync().await?,
+            DurabilityMode::GroupSync => {
+                self.writer.write_out().await?;
+                self.pending_group_sync = true;
+            },
+            DurabilityMode::ReplicationOnly => self.writer.write_out().await?,
+        }
 
-        // We only persist the metadata of the blocks once we know it's safely on disk.
+        // With the weaker durability modes the metadata may be persisted before the
+        // blocks are on disk, the loader validates the blocks when recovering.
         for (block_id, metadata) in mem::take(&mut self.block_metadata_changes) {
             self.metastore
                 .insert_block(block_id, metadata)
-                .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+                .map_err(io::Error::other)?;
         }
 
-        debug!(elapsed = ?start.elapsed(), "Flush complete");
+        debug!(elapsed = ?start.elapsed(), durability = ?msg.0, "Flush complete");
 
         Ok(())
     }
 
+    #[puppet]
+    /// Sync the fragment to disk if it has any writes pending a group sync.
+    async fn sync_pending(&mut self, _msg: SyncPending) -> io::Result<()> {
+        if self.pending_group_sync {
+            let start = Instant::now();
+            self.sync().await?;
+            debug!(elapsed = ?start.elapsed(), "Group sync complete");
+        }
+        Ok(())
+    }
+
+    /// Flushes the buffer contents and syncs the file to disk.
+    async fn sync(&mut self) -> io::Result<()> {
+        self.writer.sync().await?;
+        self.pending_group_sync = false;
+        Ok(())
+    }
+
     #[instrument(name = "fragment-sealer", skip_all, fields(fragment_id = self.id))]
     #[puppet]
     /// Seal the fragment and write all metadata.
@@ -309,16 +348,19 @@ impl FragmentWriter {
     async fn seal(&mut self, msg: Seal) -> io::Result<()> {
         let start_time = Instant::now();
 
-        let block_locations_bytes = rkyv::to_bytes::<_, 4096>(&self.block_locations)
-            .map_err(|e| io::Error::new(ErrorKind::Other, e.to_string()))?;
+        let block_index = build_block_index(&self.block_locations);
+        let block_index_bytes = rkyv::to_bytes::<_, 4096>(&block_index)
+            .map_err(|e| io::Error::other(e.to_string()))?;
         let fragment_info = rkyv::to_bytes::<_, 4096>(&msg.0)
-            .map_err(|e| io::Error::new(ErrorKind::Other, e.to_string()))?;
+            .map_err(|e| io::Error::other(e.to_string()))?;
 
+        // The block index is aligned so readers can access it directly from the mmap.
+        self.write_padding(BLOCK_INDEX_ALIGNMENT).await?;
         let start = self.cursor;
-        self.write_all(&block_locations_bytes).await?;
+        self.write_all(&block_index_bytes).await?;
         let end = self.cursor;
         self.metadata
-            .add_file(BLOCK_LOCATIONS_PATH.to_string(), start as u64..end as u64);
+            .add_file(BLOCK_INDEX_PATH.to_string(), start as u64..end as u64);
 
         let start = self.cursor;
         self.write_all(&fragment_info).await?;
@@ -331,12 +373,17 @@ impl FragmentWriter {
         let len = metadata_bytes.len();
 
         self.write_all(&metadata_bytes).await?;
-        write_metadata_offsets(&mut self.writer, start as u64, len as u64)?;
-        self.flush(Flush).await?;
+        let mut offsets = Vec::new();
+        write_metadata_offsets(&mut offsets, start as u64, len as u64)?;
+        self.writer.write_all(&offsets).await?;
+
+        // The fragment is complete, so any space reserved beyond it is released.
+        self.writer.release_preallocation().await?;
+        self.flush(Flush(DurabilityMode::Sync)).await?;
 
         self.metastore
             .seal_fragment(self.id)
-            .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
         info!(elapsed = ?start_time.elapsed(), "Fragment is sealed");
 
         // Remove the blocks that are now in the sealed segments.
@@ -344,7 +391,7 @@ impl FragmentWriter {
         // recovery unnecessarily.
         self.metastore
             .remove_blocks(self.block_locations.iter().map(|(k, _)| k).copied())
-            .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
 
         Ok(())
     }
@@ -360,12 +407,6 @@ impl Drop for FragmentWriter {
     }
 }
 
-/// Write some bytes to the file.
-///
-/// Returns the current position of the cursor.
-pub struct WriteBytes(pub Bytes);
-derive_message!(WriteBytes, io::Result<usize>);
-
 #[repr(C)]
 #[derive(Serialize, Deserialize, Archive, Clone)]
 #[archive_attr(derive(CheckBytes))]
@@ -547,12 +588,16 @@ async fn copy_to_upstream(
     let _ = upstream.send_async(None).await;
 }
 
-/// Flush the current writer buffer.
+/// Flush the current writer buffer with the given durability.
 ///
-/// This internally calls `fdatasync`.
-pub struct Flush;
+/// This internally calls `fdatasync` when using [DurabilityMode::Sync].
+pub struct Flush(pub DurabilityMode);
 derive_message!(Flush, io::Result<()>);
 
+/// Sync the writer if it has writes pending a group sync.
+pub struct SyncPending;
+derive_message!(SyncPending, io::Result<()>);
+
 /// Seals the segment, writing the metadata and footer
 /// to the file.
 pub struct Seal(pub FragmentInfo);
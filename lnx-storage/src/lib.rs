@@ -2,10 +2,10 @@
 extern crate tracing;
 
 use std::io;
-use std::io::ErrorKind;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use datacake::eventual_consistency::{
     Document,
@@ -22,23 +22,30 @@ use datacake::node::{
     DatacakeNode,
 };
 use datacake_lmdb::{heed, LmdbStorage};
+use lnx_executor::Executor;
+use lnx_io::file::IoBackend;
 use tokio::time::Instant;
 
 pub use self::distributor::HEARTBEAT;
-pub use self::fragments::{BlockId, FragmentInfo, BLOCK_HEADER_SIZE};
-use crate::distributor::TaskDistributor;
-use crate::fragments::{
+pub use self::fragments::{
+    BlockId,
+    DurabilityMode,
+    FragmentInfo,
     FragmentReader,
-    IndexFragmentsReaders,
-    IndexFragmentsWriters,
-    WriteDocBlock,
+    InsufficientDiskSpace,
+    BLOCK_HEADER_SIZE,
 };
+pub use self::loader::{FragmentLoadOptions, LoadProgress};
+use crate::distributor::TaskDistributor;
+use crate::fragments::{IndexFragmentsReaders, IndexFragmentsWriters, WriteDocBlock};
 use crate::listeners::ListenerManager;
 use crate::metastore::Metastore;
+use crate::resolvers::{fragment_vector_index_path, FRAGMENT_SCHEMA_VERSION_PATH};
 use crate::rpc::StorageService;
-use crate::store::{LnxStorage, StorageError, INDEX_FRAGMENTS};
+use crate::store::{LnxStorage, StorageError, INDEX_FRAGMENTS, INDEX_METADATA};
 
 mod bytes;
+mod changefeed;
 mod distributor;
 mod fragments;
 pub mod listeners;
@@ -51,6 +58,13 @@ mod store;
 mod tests;
 
 pub use self::bytes::SharedSlice;
+pub use self::changefeed::{
+    ChangeCursor,
+    ChangeEntry,
+    ChangeFeed,
+    ChangeFeedOptions,
+    ChangeKind,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum CreateStorageError {
@@ -97,7 +111,12 @@ impl ClusterExtension for LnxStorageExtension {
         let lmdb_store =
             LmdbStorage::open(&resolvers::metastore_folder(&self.env.root_path)).await?;
         let metastore = Metastore::from_env(lmdb_store.handle().env().clone())?;
-        let listeners = ListenerManager::default();
+        let listeners = ListenerManager::new(self.env.executor.clone());
+        let change_feed = ChangeFeed::from_env(
+            lmdb_store.handle().env().clone(),
+            self.env.change_feed.clone(),
+            listeners.clone(),
+        )?;
 
         info!("Loading existing fragment readers");
         let readers =
@@ -120,8 +139,15 @@ impl ClusterExtension for LnxStorageExtension {
             writers.clone(),
             readers.clone(),
             listeners.clone(),
+            change_feed.clone(),
+            self.env.executor.clone(),
         );
-        node.add_rpc_service(StorageService::new(writers.clone(), readers.clone()));
+
+        node.add_rpc_service(StorageService::new(
+            writers.clone(),
+            readers.clone(),
+            self.env.executor.clone(),
+        ));
 
         let replication = EventuallyConsistentStoreExtension::new(store);
         let replication_handle = node.add_extension(replication).await?;
@@ -138,6 +164,7 @@ impl ClusterExtension for LnxStorageExtension {
             readers,
             distributor,
             listeners,
+            change_feed,
         };
 
         Ok((guard, handle))
@@ -168,6 +195,7 @@ pub struct LnxStorageHandle {
     readers: IndexFragmentsReaders,
     distributor: TaskDistributor,
     listeners: ListenerManager,
+    change_feed: ChangeFeed,
 }
 
 impl Deref for LnxStorageHandle {
@@ -184,12 +212,68 @@ impl LnxStorageHandle {
         &self.listeners
     }
 
+    /// Get access to the durable change feed.
+    pub fn change_feed(&self) -> &ChangeFeed {
+        &self.change_feed
+    }
+
+    /// Persist a metadata entry of the index, such as its schema.
+    ///
+    /// Entries are replicated to every node in the cluster and are kept
+    /// across restarts, concurrent writes to the same key resolve to the
+    /// latest write.
+    pub async fn set_index_metadata(
+        &self,
+        key: &str,
+        value: impl Into<Vec<u8>>,
+    ) -> Result<(), StoreError<StorageError>> {
+        self.store_handle
+            .put(
+                INDEX_METADATA,
+                index_metadata_id(key),
+                value,
+                Consistency::All,
+            )
+            .await
+    }
+
+    /// Get a metadata entry of the index previously set with
+    /// [LnxStorageHandle::set_index_metadata].
+    pub async fn get_index_metadata(
+        &self,
+        key: &str,
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        let doc = self
+            .store_handle
+            .get(INDEX_METADATA, index_metadata_id(key))
+            .await?;
+        Ok(doc.map(|doc| doc.data().to_vec()))
+    }
+
+    /// Get the durability of blocks written to the fragments of the index.
+    ///
+    /// This applies to blocks replicated from other nodes and any writes which
+    /// don't specify a durability. Defaults to [DurabilityMode::Sync].
+    pub fn durability(&self) -> DurabilityMode {
+        self.writers.default_durability()
+    }
+
+    /// Set the durability of blocks written to the fragments of the index.
+    ///
+    /// The durability is stored with the index's metadata and is kept
+    /// across restarts, it only applies to blocks written after it is set.
+    pub fn set_durability(&self, durability: DurabilityMode) -> Result<(), heed::Error> {
+        self.writers.set_default_durability(durability)
+    }
+
     /// Get a given fragment reader.
     pub fn get_reader(&self, fragment_id: u64) -> Option<FragmentReader> {
         self.readers.get_reader(fragment_id)
     }
 
     /// Add a new block to the given fragment.
+    ///
+    /// The block is written with the durability of the index.
     pub async fn add_block<D>(
         &self,
         fragment_id: u64,
@@ -197,6 +281,31 @@ impl LnxStorageHandle {
         data: D,
         checksum: u32,
     ) -> Result<(), AddBlockError>
+    where
+        D: Into<Vec<u8>>,
+    {
+        self.add_block_with_durability(
+            fragment_id,
+            block_id,
+            data,
+            checksum,
+            self.writers.default_durability(),
+        )
+        .await
+    }
+
+    /// Add a new block to the given fragment with the given durability.
+    ///
+    /// The durability only applies to the local write, replicas write the
+    /// block with their own durability for the index.
+    pub async fn add_block_with_durability<D>(
+        &self,
+        fragment_id: u64,
+        block_id: BlockId,
+        data: D,
+        checksum: u32,
+        durability: DurabilityMode,
+    ) -> Result<(), AddBlockError>
     where
         D: Into<Vec<u8>>,
     {
@@ -206,7 +315,9 @@ impl LnxStorageHandle {
             checksum,
         };
 
-        self.writers.write_block(fragment_id, msg.clone()).await?;
+        self.writers
+            .write_block(fragment_id, msg.clone(), durability)
+            .await?;
 
         self.distributor.send_block(fragment_id, msg).await?;
 
@@ -214,11 +325,34 @@ impl LnxStorageHandle {
     }
 
     /// Add a many new blocks to the given fragment.
+    ///
+    /// The blocks are written with the durability of the index.
     pub async fn add_many_blocks<D>(
         &self,
         fragment_id: u64,
         blocks_iter: impl IntoIterator<Item = (BlockId, D, u32)>,
     ) -> Result<(), AddBlockError>
+    where
+        D: Into<Vec<u8>>,
+    {
+        self.add_many_blocks_with_durability(
+            fragment_id,
+            blocks_iter,
+            self.writers.default_durability(),
+        )
+        .await
+    }
+
+    /// Add a many new blocks to the given fragment with the given durability.
+    ///
+    /// The durability only applies to the local writes, replicas write the
+    /// blocks with their own durability for the index.
+    pub async fn add_many_blocks_with_durability<D>(
+        &self,
+        fragment_id: u64,
+        blocks_iter: impl IntoIterator<Item = (BlockId, D, u32)>,
+        durability: DurabilityMode,
+    ) -> Result<(), AddBlockError>
     where
         D: Into<Vec<u8>>,
     {
@@ -232,7 +366,9 @@ impl LnxStorageHandle {
             });
         }
 
-        self.writers.write_many_blocks(fragment_id, &blocks).await?;
+        self.writers
+            .write_many_blocks(fragment_id, &blocks, durability)
+            .await?;
 
         self.distributor
             .send_many_blocks(fragment_id, blocks.into_iter())
@@ -256,6 +392,42 @@ impl LnxStorageHandle {
             .await
     }
 
+    /// Set the version of the index schema the documents of the given
+    /// fragment were written with.
+    ///
+    /// This must be set before the fragment is committed, fragments without
+    /// a version are assumed to be written with the first version of the schema.
+    pub async fn set_fragment_schema_version(
+        &self,
+        fragment_id: u64,
+        schema_version: u32,
+    ) -> io::Result<()> {
+        self.add_file(
+            fragment_id,
+            FRAGMENT_SCHEMA_VERSION_PATH,
+            SharedSlice::copy_from_slice(&schema_version.to_le_bytes()),
+        )
+        .await
+    }
+
+    /// Set the serialized vector index of a vector field of the given fragment.
+    ///
+    /// Like any fragment file this must be set before the fragment is committed,
+    /// and is replicated with the fragment once it is committed.
+    pub async fn set_fragment_vector_index(
+        &self,
+        fragment_id: u64,
+        field: &str,
+        index: Vec<u8>,
+    ) -> io::Result<()> {
+        self.add_file(
+            fragment_id,
+            fragment_vector_index_path(field),
+            SharedSlice::from(index),
+        )
+        .await
+    }
+
     #[instrument("commit-fragment", skip(self))]
     /// Seal a fragment and begin replicating it out to nodes.
     pub async fn commit_fragment(
@@ -265,9 +437,7 @@ impl LnxStorageHandle {
     ) -> Result<(), StoreError<StorageError>> {
         let start = Instant::now();
         let data = rkyv::to_bytes::<_, 1024>(&info)
-            .map_err(|_| {
-                io::Error::new(ErrorKind::Other, "Failed to serialize fragment info")
-            })
+            .map_err(|_| io::Error::other("Failed to serialize fragment info"))
             .map_err(|e| StoreError::StorageError(StorageError::IO(e)))?;
 
         self.writers
@@ -311,6 +481,35 @@ impl LnxStorageHandle {
 
         Ok(())
     }
+
+    #[instrument("delete-many-fragments", skip(self, fragment_ids))]
+    /// Delete many fragments from the system.
+    ///
+    /// This is typically used after a compaction or when an index is dropped.
+    pub async fn delete_many_fragments(
+        &self,
+        fragment_ids: impl IntoIterator<Item = u64>,
+    ) -> Result<(), StoreError<StorageError>> {
+        let start = Instant::now();
+        let fragment_ids = fragment_ids.into_iter().collect::<Vec<_>>();
+        let num_fragments = fragment_ids.len();
+        self.store_handle
+            .del_many(INDEX_FRAGMENTS, fragment_ids, Consistency::All)
+            .await?;
+
+        info!(
+            elapsed = ?start.elapsed(),
+            num_fragments = num_fragments,
+            "Fragments deleted across all live nodes",
+        );
+
+        Ok(())
+    }
+}
+
+/// The document ID an index metadata entry is stored under.
+fn index_metadata_id(key: &str) -> u64 {
+    cityhash_sys::city_hash_64(key.as_bytes())
 }
 
 #[derive(Clone)]
@@ -320,17 +519,23 @@ pub struct EnvCtx(Arc<EnvCtxInner>);
 impl EnvCtx {
     /// Create a new environment for the storage system.
     pub fn new(root_path: PathBuf) -> Self {
-        let inner = EnvCtxInner { root_path };
-        Self(Arc::new(inner))
+        Self::from(EnvCtxInner::new(root_path))
     }
 
     #[cfg(test)]
     /// Create a environment context for testing.
     pub fn for_test() -> Self {
-        let inner = EnvCtxInner {
-            root_path: std::env::temp_dir().join(uuid::Uuid::new_v4().to_string()),
-        };
+        Self::from(EnvCtxInner::for_test())
+    }
+
+    /// Get the progress of loading existing fragments on startup.
+    pub fn fragment_load_progress(&self) -> &LoadProgress {
+        &self.load_progress
+    }
+}
 
+impl From<EnvCtxInner> for EnvCtx {
+    fn from(inner: EnvCtxInner) -> Self {
         Self(Arc::new(inner))
     }
 }
@@ -343,6 +548,68 @@ impl Deref for EnvCtx {
     }
 }
 
+/// The default number of bytes reserved for new fragment files.
+const DEFAULT_FRAGMENT_PREALLOCATION: u64 = 64 << 20;
+/// The default minimum free disk space before writes are rejected.
+///
+/// The check is disabled by default.
+const DEFAULT_MIN_FREE_DISK_SPACE: u64 = 0;
+
 pub struct EnvCtxInner {
     pub root_path: PathBuf,
+    /// Options for loading existing fragments on startup.
+    pub fragment_loading: FragmentLoadOptions,
+    /// The progress of loading existing fragments on startup.
+    pub load_progress: LoadProgress,
+    /// Options for the durable keyspace change feed.
+    pub change_feed: ChangeFeedOptions,
+    /// The executor storage tasks are run on.
+    ///
+    /// Defaults to the global executor.
+    pub executor: Executor,
+    /// The IO implementation used for fragment files.
+    ///
+    /// Defaults to direct IO if the kernel supports it.
+    pub io_backend: IoBackend,
+    /// How often fragments written with [DurabilityMode::GroupSync] are synced to disk.
+    pub group_sync_interval: Duration,
+    /// The number of bytes reserved on disk when a new fragment file is created.
+    ///
+    /// Fragments downloaded from other nodes reserve their known size instead.
+    /// Set to `0` to disable preallocation.
+    pub fragment_preallocation: u64,
+    /// The minimum free disk space in bytes before new writes and
+    /// downloads are rejected.
+    ///
+    /// Reads are unaffected. Defaults to `0` which disables the check.
+    pub min_free_disk_space: u64,
+}
+
+impl EnvCtxInner {
+    /// Create a new environment with the default options.
+    pub fn new(root_path: PathBuf) -> Self {
+        Self {
+            root_path,
+            fragment_loading: FragmentLoadOptions::default(),
+            load_progress: LoadProgress::default(),
+            change_feed: ChangeFeedOptions::default(),
+            executor: Executor::global(),
+            io_backend: IoBackend::detect(),
+            group_sync_interval: Duration::from_secs(1),
+            fragment_preallocation: DEFAULT_FRAGMENT_PREALLOCATION,
+            min_free_disk_space: DEFAULT_MIN_FREE_DISK_SPACE,
+        }
+    }
+
+    #[cfg(test)]
+    /// Create a new environment for testing with its own executor.
+    ///
+    /// Tests share a process, so using the global executor would let
+    /// one test's setup interfere with the tasks of another.
+    pub fn for_test() -> Self {
+        let root_path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let mut inner = Self::new(root_path);
+        inner.executor = Executor::with_threads(2).expect("Create test executor");
+        inner
+    }
 }
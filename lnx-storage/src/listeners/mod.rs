@@ -3,9 +3,22 @@ use std::sync::Arc;
 use datacake::crdt::Key;
 use datacake::eventual_consistency::Document;
 use hashbrown::HashMap;
+use lnx_executor::Executor;
 use parking_lot::RwLock;
 use smallvec::SmallVec;
 
+mod stream;
+
+use self::stream::Subscriptions;
+pub use self::stream::{
+    EventFilter,
+    EventKind,
+    EventStream,
+    StorageEvent,
+    SubscriptionEvent,
+    DEFAULT_EVENT_BUFFER_SIZE,
+};
+
 type BoxedStorageListener = Arc<dyn StorageListener>;
 type BoxedFragmentListener = Arc<dyn FragmentListener>;
 type Listeners<L> = SmallVec<[L; 10]>;
@@ -16,7 +29,7 @@ type FragmentsListeners = Listeners<BoxedFragmentListener>;
 macro_rules! derive_fragment_triggers {
     ($slf:expr, $cb:ident => $($arg:expr $(,)?)*) => {{
         let slf = $slf.clone();
-        lnx_executor::spawn_task(async move {
+        slf.executor.clone().spawn_task(async move {
             let listeners = slf.fragments_listeners.read().clone();
             for listener in listeners.iter() {
                 listener.$cb($($arg),*);
@@ -28,12 +41,41 @@ macro_rules! derive_fragment_triggers {
 #[derive(Default, Clone)]
 /// The listener manager that triggers callbacks when
 /// events occur within the storage system.
+///
+/// Events can either be observed via the synchronous listener callbacks
+/// or consumed as an async stream via [ListenerManager::subscribe].
 pub struct ListenerManager {
     storage_listeners: Arc<RwLock<StorageListeners>>,
     fragments_listeners: Arc<RwLock<FragmentsListeners>>,
+    subscriptions: Subscriptions,
+    executor: Executor,
 }
 
 impl ListenerManager {
+    /// Create a new listener manager which runs listeners on the given executor.
+    pub fn new(executor: Executor) -> Self {
+        Self {
+            executor,
+            ..Self::default()
+        }
+    }
+
+    /// Subscribe to a stream of events matching the given filter.
+    ///
+    /// Up to `capacity` events are buffered for the subscriber before the
+    /// oldest events are dropped and a [SubscriptionEvent::Lagged] is yielded.
+    ///
+    /// Unlike the listener callbacks, fragment keyspace changes are included
+    /// as fragment events.
+    pub fn subscribe(&self, filter: EventFilter, capacity: usize) -> EventStream {
+        self.subscriptions.subscribe(filter, capacity)
+    }
+
+    /// The executor listeners are run on.
+    pub(crate) fn executor(&self) -> &Executor {
+        &self.executor
+    }
+
     /// Register a listener for storage events.
     ///
     /// No events are triggered when working on the fragment keyspace.
@@ -58,9 +100,14 @@ impl ListenerManager {
     ///
     /// This runs listeners on the blocking runtime.
     pub(crate) fn trigger_on_put(&self, keyspace: &str, doc: Document) {
+        self.subscriptions.publish(StorageEvent::Put {
+            keyspace: keyspace.to_owned(),
+            doc: doc.clone(),
+        });
+
         let slf = self.clone();
         let keyspace = keyspace.to_owned();
-        lnx_executor::spawn_task(async move {
+        self.executor.spawn_task(async move {
             let listeners_map = slf.storage_listeners.read();
             if let Some(listeners) = listeners_map.get(&keyspace).cloned() {
                 for listener in listeners {
@@ -74,9 +121,14 @@ impl ListenerManager {
     ///
     /// This runs listeners on the blocking runtime.
     pub(crate) fn trigger_on_del(&self, keyspace: &str, doc_id: Key) {
+        self.subscriptions.publish(StorageEvent::Delete {
+            keyspace: keyspace.to_owned(),
+            doc_id,
+        });
+
         let slf = self.clone();
         let keyspace = keyspace.to_owned();
-        lnx_executor::spawn_task(async move {
+        self.executor.spawn_task(async move {
             let listeners_map = slf.storage_listeners.read();
             if let Some(listeners) = listeners_map.get(&keyspace).cloned() {
                 for listener in listeners {
@@ -92,26 +144,41 @@ impl ListenerManager {
         fragment_id: u64,
         blocks: Vec<Document>,
     ) {
+        let blocks = Arc::new(blocks);
+        self.subscriptions.publish(StorageEvent::BlockFlush {
+            fragment_id,
+            blocks: blocks.clone(),
+        });
         derive_fragment_triggers!(self, on_block_flush => fragment_id, &blocks);
     }
 
     /// Trigger the fragment delete event.
     pub(crate) fn trigger_fragment_file_add(&self, fragment_id: u64, file_path: String) {
+        self.subscriptions.publish(StorageEvent::FileAdd {
+            fragment_id,
+            file_path: file_path.clone(),
+        });
         derive_fragment_triggers!(self, on_file_add => fragment_id, &file_path);
     }
 
     /// Trigger the fragment seal event.
     pub(crate) fn trigger_fragment_seal(&self, fragment_id: u64) {
+        self.subscriptions
+            .publish(StorageEvent::Seal { fragment_id });
         derive_fragment_triggers!(self, on_seal => fragment_id);
     }
 
     /// Trigger the fragment ready ready event.
     pub(crate) fn trigger_fragment_read_ready(&self, fragment_id: u64) {
+        self.subscriptions
+            .publish(StorageEvent::ReadReady { fragment_id });
         derive_fragment_triggers!(self, on_read_ready => fragment_id);
     }
 
     /// Trigger the fragment delete event.
     pub(crate) fn trigger_fragment_delete(&self, fragment_id: u64) {
+        self.subscriptions
+            .publish(StorageEvent::FragmentDelete { fragment_id });
         derive_fragment_triggers!(self, on_delete => fragment_id);
     }
 }
@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+
+use datacake::crdt::Key;
+use datacake::eventual_consistency::Document;
+use futures::Stream;
+use hashbrown::HashSet;
+use parking_lot::{Mutex, RwLock};
+
+/// The default number of events buffered per subscription.
+pub const DEFAULT_EVENT_BUFFER_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+/// The kind of a given [StorageEvent].
+pub enum EventKind {
+    /// A document was inserted or updated in a keyspace.
+    Put,
+    /// A document was deleted from a keyspace.
+    Delete,
+    /// A set of blocks were added to a fragment and flushed.
+    BlockFlush,
+    /// A file was added to a fragment.
+    FileAdd,
+    /// A fragment was sealed.
+    Seal,
+    /// A fragment is available to be read.
+    ReadReady,
+    /// A fragment was marked for deletion.
+    FragmentDelete,
+}
+
+#[derive(Debug, Clone)]
+/// An event that occurred within the storage system.
+pub enum StorageEvent {
+    /// A document was inserted or updated in a keyspace.
+    Put { keyspace: String, doc: Document },
+    /// A document was deleted from a keyspace.
+    Delete { keyspace: String, doc_id: Key },
+    /// A set of blocks were added to a fragment and flushed.
+    BlockFlush {
+        fragment_id: u64,
+        blocks: Arc<Vec<Document>>,
+    },
+    /// A file was added to a fragment.
+    FileAdd { fragment_id: u64, file_path: String },
+    /// A fragment was sealed.
+    Seal { fragment_id: u64 },
+    /// A fragment is available to be read.
+    ReadReady { fragment_id: u64 },
+    /// A fragment was marked for deletion.
+    FragmentDelete { fragment_id: u64 },
+}
+
+impl StorageEvent {
+    /// The kind of event.
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Put { .. } => EventKind::Put,
+            Self::Delete { .. } => EventKind::Delete,
+            Self::BlockFlush { .. } => EventKind::BlockFlush,
+            Self::FileAdd { .. } => EventKind::FileAdd,
+            Self::Seal { .. } => EventKind::Seal,
+            Self::ReadReady { .. } => EventKind::ReadReady,
+            Self::FragmentDelete { .. } => EventKind::FragmentDelete,
+        }
+    }
+
+    /// The keyspace the event occurred in if it is a keyspace event.
+    pub fn keyspace(&self) -> Option<&str> {
+        match self {
+            Self::Put { keyspace, .. } | Self::Delete { keyspace, .. } => {
+                Some(keyspace.as_str())
+            },
+            _ => None,
+        }
+    }
+
+    /// The fragment the event occurred on if it is a fragment event.
+    pub fn fragment_id(&self) -> Option<u64> {
+        match self {
+            Self::BlockFlush { fragment_id, .. }
+            | Self::FileAdd { fragment_id, .. }
+            | Self::Seal { fragment_id }
+            | Self::ReadReady { fragment_id }
+            | Self::FragmentDelete { fragment_id } => Some(*fragment_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A filter selecting which events a subscription receives.
+///
+/// Each set filter must match for the event to be delivered, an
+/// empty filter matches all events.
+pub struct EventFilter {
+    keyspaces: Option<HashSet<String>>,
+    fragment_ids: Option<HashSet<u64>>,
+    kinds: Option<HashSet<EventKind>>,
+}
+
+impl EventFilter {
+    /// Only receive keyspace events from the given keyspace.
+    ///
+    /// This can be called multiple times to select multiple keyspaces.
+    pub fn keyspace(mut self, keyspace: impl Into<String>) -> Self {
+        self.keyspaces
+            .get_or_insert_with(HashSet::new)
+            .insert(keyspace.into());
+        self
+    }
+
+    /// Only receive fragment events from the given fragment.
+    ///
+    /// This can be called multiple times to select multiple fragments.
+    pub fn fragment(mut self, fragment_id: u64) -> Self {
+        self.fragment_ids
+            .get_or_insert_with(HashSet::new)
+            .insert(fragment_id);
+        self
+    }
+
+    /// Only receive events of the given kind.
+    ///
+    /// This can be called multiple times to select multiple kinds.
+    pub fn kind(mut self, kind: EventKind) -> Self {
+        self.kinds.get_or_insert_with(HashSet::new).insert(kind);
+        self
+    }
+
+    /// Checks if the given event should be delivered.
+    pub fn matches(&self, event: &StorageEvent) -> bool {
+        if let Some(kinds) = self.kinds.as_ref() {
+            if !kinds.contains(&event.kind()) {
+                return false;
+            }
+        }
+
+        if let Some(keyspaces) = self.keyspaces.as_ref() {
+            match event.keyspace() {
+                Some(keyspace) if keyspaces.contains(keyspace) => {},
+                _ => return false,
+            }
+        }
+
+        if let Some(fragment_ids) = self.fragment_ids.as_ref() {
+            match event.fragment_id() {
+                Some(fragment_id) if fragment_ids.contains(&fragment_id) => {},
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An item yielded by an [EventStream].
+pub enum SubscriptionEvent {
+    /// An event which matched the subscription filter.
+    Event(StorageEvent),
+    /// The subscriber fell behind and the given number of the oldest
+    /// buffered events were dropped.
+    Lagged(u64),
+}
+
+/// The shared state between the publisher and a single subscriber.
+pub(crate) struct Subscription {
+    filter: EventFilter,
+    capacity: usize,
+    state: Mutex<SubscriptionState>,
+}
+
+#[derive(Default)]
+struct SubscriptionState {
+    buffer: VecDeque<StorageEvent>,
+    lagged: u64,
+    waker: Option<Waker>,
+}
+
+impl Subscription {
+    /// Pushes the event into the subscription buffer if it matches the filter.
+    ///
+    /// If the buffer is full the oldest event is dropped and the
+    /// subscriber is notified that it has lagged.
+    fn publish(&self, event: &StorageEvent) {
+        if !self.filter.matches(event) {
+            return;
+        }
+
+        let mut state = self.state.lock();
+        if state.buffer.len() >= self.capacity {
+            state.buffer.pop_front();
+            state.lagged += 1;
+        }
+        state.buffer.push_back(event.clone());
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+/// The set of active subscriptions.
+pub(crate) struct Subscriptions(Arc<RwLock<Vec<Weak<Subscription>>>>);
+
+impl Subscriptions {
+    /// Creates a new subscription with the given filter and buffer capacity.
+    pub(crate) fn subscribe(&self, filter: EventFilter, capacity: usize) -> EventStream {
+        let subscription = Arc::new(Subscription {
+            filter,
+            capacity: capacity.max(1),
+            state: Mutex::new(SubscriptionState::default()),
+        });
+
+        self.0.write().push(Arc::downgrade(&subscription));
+
+        EventStream { subscription }
+    }
+
+    /// Publishes an event to all matching subscriptions.
+    ///
+    /// Events are pushed to the subscribers before this call returns, so
+    /// events published in order are received in the same order.
+    pub(crate) fn publish(&self, event: StorageEvent) {
+        let mut has_closed = false;
+        for subscription in self.0.read().iter() {
+            match subscription.upgrade() {
+                Some(subscription) => subscription.publish(&event),
+                None => has_closed = true,
+            }
+        }
+
+        if has_closed {
+            self.0
+                .write()
+                .retain(|subscription| subscription.strong_count() > 0);
+        }
+    }
+}
+
+/// An async stream of events matching a given [EventFilter].
+///
+/// Events are buffered up to the subscription's capacity, once full the
+/// oldest events are dropped and a [SubscriptionEvent::Lagged] is yielded
+/// before the remaining events.
+///
+/// Events for the same keyspace key or fragment are yielded in the order
+/// they were applied on this node.
+///
+/// The subscription is removed once the stream is dropped.
+pub struct EventStream {
+    subscription: Arc<Subscription>,
+}
+
+impl EventStream {
+    /// Attempts to get the next event without waiting.
+    pub fn try_next_event(&self) -> Option<SubscriptionEvent> {
+        let mut state = self.subscription.state.lock();
+        Self::take_next(&mut state)
+    }
+
+    fn take_next(state: &mut SubscriptionState) -> Option<SubscriptionEvent> {
+        if state.lagged > 0 {
+            let lagged = std::mem::take(&mut state.lagged);
+            return Some(SubscriptionEvent::Lagged(lagged));
+        }
+
+        state.buffer.pop_front().map(SubscriptionEvent::Event)
+    }
+}
+
+impl Stream for EventStream {
+    type Item = SubscriptionEvent;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut state = self.subscription.state.lock();
+
+        match Self::take_next(&mut state) {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_filter() {
+        let put = StorageEvent::Delete {
+            keyspace: "a".to_string(),
+            doc_id: 1,
+        };
+        let seal = StorageEvent::Seal { fragment_id: 1 };
+
+        assert!(EventFilter::default().matches(&put));
+        assert!(EventFilter::default().matches(&seal));
+
+        let filter = EventFilter::default().keyspace("a");
+        assert!(filter.matches(&put));
+        assert!(!filter.matches(&seal));
+        assert!(!EventFilter::default().keyspace("b").matches(&put));
+
+        let filter = EventFilter::default().fragment(1);
+        assert!(!filter.matches(&put));
+        assert!(filter.matches(&seal));
+        assert!(!EventFilter::default().fragment(2).matches(&seal));
+
+        let filter = EventFilter::default().kind(EventKind::Seal);
+        assert!(!filter.matches(&put));
+        assert!(filter.matches(&seal));
+    }
+
+    #[test]
+    fn test_subscription_lag() {
+        let subscriptions = Subscriptions::default();
+        let stream = subscriptions.subscribe(EventFilter::default(), 2);
+
+        for fragment_id in 0..5 {
+            subscriptions.publish(StorageEvent::Seal { fragment_id });
+        }
+
+        assert!(matches!(
+            stream.try_next_event(),
+            Some(SubscriptionEvent::Lagged(3))
+        ));
+        assert!(matches!(
+            stream.try_next_event(),
+            Some(SubscriptionEvent::Event(StorageEvent::Seal {
+                fragment_id: 3
+            }))
+        ));
+        assert!(matches!(
+            stream.try_next_event(),
+            Some(SubscriptionEvent::Event(StorageEvent::Seal {
+                fragment_id: 4
+            }))
+        ));
+        assert!(stream.try_next_event().is_none());
+
+        drop(stream);
+        subscriptions.publish(StorageEvent::Seal { fragment_id: 5 });
+        assert!(
+            subscriptions.0.read().is_empty(),
+            "Dropped subscriptions should be removed"
+        );
+    }
+}
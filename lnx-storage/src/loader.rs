@@ -1,6 +1,12 @@
 use std::collections::BTreeMap;
-use std::io::{self, ErrorKind, Seek, SeekFrom};
+use std::fs::OpenOptions;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use futures::StreamExt;
 use hashbrown::HashMap;
 
 use crate::fragments::{
@@ -10,31 +16,150 @@ use crate::fragments::{
     FragmentWriter,
     IndexFragmentsReaders,
     IndexFragmentsWriters,
+    BLOCK_HEADER_SIZE,
 };
 use crate::listeners::ListenerManager;
 use crate::{EnvCtx, Metastore};
 
+/// The default number of fragments opened concurrently on startup.
+const DEFAULT_LOAD_CONCURRENCY: usize = 16;
+/// The interval between startup progress reports.
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+/// Options for loading the existing sealed fragments on startup.
+pub struct FragmentLoadOptions {
+    /// The maximum number of fragments being opened at any one time.
+    ///
+    /// Each open is run on the default executor pool.
+    pub concurrency: usize,
+    /// Only validate the fragment footer on startup and defer loading
+    /// the block location table until the fragment is first accessed.
+    pub lazy: bool,
+}
+
+impl Default for FragmentLoadOptions {
+    fn default() -> Self {
+        let concurrency = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(DEFAULT_LOAD_CONCURRENCY);
+
+        Self {
+            concurrency,
+            lazy: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// The progress of loading the existing fragments on startup.
+///
+/// This can be polled while the storage extension is initialising.
+pub struct LoadProgress {
+    total: Arc<AtomicUsize>,
+    loaded: Arc<AtomicUsize>,
+    done: Arc<AtomicBool>,
+}
+
+impl LoadProgress {
+    /// The total number of fragments which need to be loaded.
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// The number of fragments which have been loaded so far.
+    pub fn loaded(&self) -> usize {
+        self.loaded.load(Ordering::Relaxed)
+    }
+
+    /// Has the loading process completed.
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    fn start(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+        self.loaded.store(0, Ordering::Relaxed);
+        self.done.store(false, Ordering::Relaxed);
+    }
+
+    fn inc_loaded(&self) -> usize {
+        self.loaded.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn finish(&self) {
+        self.done.store(true, Ordering::Relaxed);
+    }
+}
+
 /// Loads all sealed fragments stored within the metastore.
+///
+/// Fragments are opened in parallel across the default pool with
+/// the concurrency bounded by the [FragmentLoadOptions] of the environment.
 pub async fn load_readers(
     env: EnvCtx,
     metastore: &Metastore,
     listeners: ListenerManager,
 ) -> io::Result<IndexFragmentsReaders> {
-    let fragment_ids = metastore
-        .get_sealed_fragments()
-        .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+    let fragment_ids = metastore.get_sealed_fragments().map_err(io::Error::other)?;
+
+    let options = env.fragment_loading.clone();
+    let progress = env.load_progress.clone();
+    let total = fragment_ids.len();
+    progress.start(total);
+
+    info!(
+        num_fragments = total,
+        concurrency = options.concurrency,
+        lazy = options.lazy,
+        "Loading sealed fragments",
+    );
+
+    let root_path = env.root_path.clone();
+    let executor = env.executor.clone();
+    let io_backend = env.io_backend;
+    let start = Instant::now();
+    let mut last_report = Instant::now();
+    let mut pending = futures::stream::iter(fragment_ids)
+        .map(|fragment_id| {
+            debug!(fragment_id = fragment_id, "Loading fragment");
+            // We do this same process in the IndexFragmentsReaders::load_reader method
+            // but it is very heavy on locking and is slower, so we use this method to
+            // prevent thousands of fragments slowing the startup time.
+            let path = crate::resolvers::get_fragment_location(&root_path, fragment_id);
+            let lazy = options.lazy;
+            let executor = executor.clone();
+            async move {
+                let reader = if lazy {
+                    FragmentReader::open_lazy(&executor, io_backend, path).await?
+                } else {
+                    FragmentReader::open(&executor, io_backend, path).await?
+                };
+                Ok::<_, io::Error>((fragment_id, reader))
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1));
 
     let mut readers = BTreeMap::new();
-    for fragment_id in fragment_ids {
-        debug!(fragment_id = fragment_id, "Loading fragment");
-        // We do this same process in the IndexFragmentsReaders::load_reader method
-        // but it is very heavy on locking and is slower, so we use this method to
-        // prevent thousands of fragments slowing the startup time.
-        let path = crate::resolvers::get_fragment_location(&env.root_path, fragment_id);
-        let reader = FragmentReader::open_mmap(path).await?;
+    while let Some(result) = pending.next().await {
+        let (fragment_id, reader) = result?;
         readers.insert(fragment_id, reader);
+
+        let loaded = progress.inc_loaded();
+        if last_report.elapsed() >= PROGRESS_LOG_INTERVAL {
+            info!(
+                loaded = loaded,
+                total = total,
+                elapsed = ?start.elapsed(),
+                "Fragment loading in progress",
+            );
+            last_report = Instant::now();
+        }
     }
 
+    progress.finish();
+    info!(num_fragments = total, elapsed = ?start.elapsed(), "Loaded sealed fragments");
+
     Ok(IndexFragmentsReaders::from_existing_state(
         env, readers, listeners,
     ))
@@ -50,11 +175,13 @@ pub async fn load_partial_writers(
     listeners: ListenerManager,
 ) -> io::Result<IndexFragmentsWriters> {
     let metastore = metastore.clone();
-    lnx_executor::spawn_task(async move {
-        load_partial_writers_inner(env, &metastore, listeners)
-    })
-    .await
-    .expect("Join task")
+    let executor = env.executor.clone();
+    executor
+        .spawn_task(
+            async move { load_partial_writers_inner(env, &metastore, listeners) },
+        )
+        .await
+        .expect("Join task")
 }
 
 fn load_partial_writers_inner(
@@ -64,11 +191,9 @@ fn load_partial_writers_inner(
 ) -> io::Result<IndexFragmentsWriters> {
     let fragment_ids = metastore
         .get_unsealed_fragments()
-        .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+        .map_err(io::Error::other)?;
 
-    let unsealed_blocks = metastore
-        .get_blocks()
-        .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+    let unsealed_blocks = metastore.get_blocks().map_err(io::Error::other)?;
     let mut fragment_blocks = HashMap::<u64, Vec<(BlockId, BlockInfo)>>::new();
     for (block_id, metadata) in unsealed_blocks {
         let fragment_id = metadata.fragment_id;
@@ -86,20 +211,28 @@ fn load_partial_writers_inner(
     let mut writers = HashMap::new();
     for fragment_id in fragment_ids {
         let path = crate::resolvers::get_fragment_location(&env.root_path, fragment_id);
+        let block_locations = fragment_blocks.remove(&fragment_id).unwrap_or_default();
 
-        let res = std::fs::OpenOptions::new()
-            .create(false)
-            .write(true)
-            .read(true)
-            .open(path);
-
-        match res {
-            Ok(mut file) => {
-                file.seek(SeekFrom::End(0))?;
+        let (block_locations, discarded) = match recover_blocks(&path, block_locations) {
+            Ok(recovered) => recovered,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
 
-                let block_locations =
-                    fragment_blocks.remove(&fragment_id).unwrap_or_default();
+        if !discarded.is_empty() {
+            warn!(
+                fragment_id = fragment_id,
+                num_discarded = discarded.len(),
+                "Discarded blocks which were not persisted before shutdown, \
+                 they must be re-replicated",
+            );
+            metastore
+                .remove_blocks(discarded.into_iter())
+                .map_err(io::Error::other)?;
+        }
 
+        match env.io_backend.open_writer(&path, false) {
+            Ok(file) => {
                 let writer = FragmentWriter::from_existing_state(
                     env.clone(),
                     fragment_id,
@@ -115,10 +248,72 @@ fn load_partial_writers_inner(
         }
     }
 
+    let durability = metastore
+        .get_durability()
+        .map_err(io::Error::other)?
+        .unwrap_or_default();
+
     Ok(IndexFragmentsWriters::from_existing_state(
         env,
         metastore.clone(),
         writers,
         listeners,
+        durability,
     ))
 }
+
+/// The valid blocks of a fragment and the IDs of the discarded blocks.
+type RecoveredBlocks = (Vec<(BlockId, BlockInfo)>, Vec<BlockId>);
+
+/// Validates the blocks of a partially written fragment against its file.
+///
+/// Blocks written with a weaker [DurabilityMode](crate::DurabilityMode) can have
+/// their metadata persisted before the data reaches the disk, so any block whose
+/// header does not match its metadata is discarded. If any blocks are discarded,
+/// the data after the last valid block is truncated so new writes continue from it.
+///
+/// Returns the valid blocks and the IDs of the discarded blocks.
+fn recover_blocks(
+    path: &Path,
+    blocks: Vec<(BlockId, BlockInfo)>,
+) -> io::Result<RecoveredBlocks> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let mut valid = Vec::with_capacity(blocks.len());
+    let mut discarded = Vec::new();
+    let mut header = [0u8; BLOCK_HEADER_SIZE];
+    for (block_id, info) in blocks {
+        let start = info.location.start as u64;
+        let end = info.location.end as u64;
+
+        let is_valid =
+            start >= BLOCK_HEADER_SIZE as u64 && start <= end && end <= file_len && {
+                file.seek(SeekFrom::Start(start - BLOCK_HEADER_SIZE as u64))?;
+                file.read_exact(&mut header)?;
+
+                let checksum = u32::from_le_bytes(header[0..4].try_into().unwrap());
+                let id = u64::from_le_bytes(header[4..12].try_into().unwrap());
+                let len = u32::from_le_bytes(header[12..16].try_into().unwrap());
+                checksum == info.checksum && id == block_id && len == info.len()
+            };
+
+        if is_valid {
+            valid.push((block_id, info));
+        } else {
+            discarded.push(block_id);
+        }
+    }
+
+    let valid_len = valid
+        .iter()
+        .map(|(_, info)| info.location.end as u64)
+        .max()
+        .unwrap_or(0);
+    if !discarded.is_empty() && valid_len < file_len {
+        file.set_len(valid_len)?;
+        file.sync_data()?;
+    }
+
+    Ok((valid, discarded))
+}
@@ -2,12 +2,15 @@ use std::mem::size_of;
 use std::sync::Arc;
 
 use datacake_lmdb::heed::byteorder::LittleEndian;
-use datacake_lmdb::heed::types::{ByteSlice, U64, U8};
+use datacake_lmdb::heed::types::{ByteSlice, Str, U64, U8};
 use datacake_lmdb::heed::{Database, Env};
 use datacake_lmdb::Error;
 use parking_lot::Mutex;
 
-use crate::fragments::BlockId;
+use crate::fragments::{BlockId, DurabilityMode};
+
+/// The index metadata key the durability of the index is stored under.
+pub const DURABILITY_KEY: &str = "durability";
 
 /// Fragment state flags.
 pub mod flags {
@@ -29,6 +32,7 @@ pub struct Metastore {
     env: Arc<Mutex<Env>>,
     block_locations: Database<U64<LittleEndian>, ByteSlice>,
     fragments_info: Database<U64<LittleEndian>, U8>,
+    index_metadata: Database<Str, ByteSlice>,
 }
 
 impl Metastore {
@@ -38,12 +42,15 @@ impl Metastore {
         let block_locations = env.create_database(&mut txn, Some("datacake-blocks"))?;
         let fragments_info =
             env.create_database(&mut txn, Some("datacake-fragments-info"))?;
+        let index_metadata =
+            env.create_database(&mut txn, Some("lnx-index-metadata"))?;
         txn.commit()?;
 
         Ok(Self {
             env: Arc::new(Mutex::new(env)),
             block_locations,
             fragments_info,
+            index_metadata,
         })
     }
 
@@ -127,6 +134,19 @@ impl Metastore {
         Ok(())
     }
 
+    /// Removes many fragments from the metastore.
+    ///
+    /// This is all-or-nothing, if an error occurs no fragments are removed.
+    pub fn remove_fragments(&self, ids: impl Iterator<Item = u64>) -> Result<(), Error> {
+        let lock = self.env.lock();
+        let mut txn = lock.write_txn()?;
+        for id in ids {
+            self.fragments_info.delete(&mut txn, &id)?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
     /// Get fragments which are sealed.
     pub fn get_sealed_fragments(&self) -> Result<Vec<u64>, Error> {
         let mut fragment_ids = Vec::new();
@@ -142,6 +162,39 @@ impl Metastore {
         Ok(fragment_ids)
     }
 
+    /// Set the value of an index metadata entry, replacing any existing value.
+    ///
+    /// These entries are local to the node and are not replicated.
+    pub fn put_index_metadata(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        let lock = self.env.lock();
+        let mut txn = lock.write_txn()?;
+        self.index_metadata.put(&mut txn, key, value)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Get the value of an index metadata entry.
+    pub fn get_index_metadata(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let lock = self.env.lock();
+        let txn = lock.read_txn()?;
+        let value = self.index_metadata.get(&txn, key)?.map(|v| v.to_vec());
+        Ok(value)
+    }
+
+    /// Set the durability blocks written to the index's fragments default to.
+    pub fn put_durability(&self, durability: DurabilityMode) -> Result<(), Error> {
+        self.put_index_metadata(DURABILITY_KEY, &[durability as u8])
+    }
+
+    /// Get the durability of the index if one has been set.
+    pub fn get_durability(&self) -> Result<Option<DurabilityMode>, Error> {
+        let value = self.get_index_metadata(DURABILITY_KEY)?;
+        Ok(match value.as_deref() {
+            Some(&[value]) => DurabilityMode::from_u8(value),
+            _ => None,
+        })
+    }
+
     /// Get fragments which are unsealed.
     pub fn get_unsealed_fragments(&self) -> Result<Vec<u64>, Error> {
         let mut fragment_ids = Vec::new();
@@ -2,8 +2,25 @@ use std::io;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
+/// The legacy unsorted block locations of a fragment.
+///
+/// This is only read for fragments which do not contain a [BLOCK_INDEX_PATH] file.
 pub static BLOCK_LOCATIONS_PATH: &str = "lnx/internal/fragment-blocks";
+/// The sorted and aligned block index of a fragment.
+pub static BLOCK_INDEX_PATH: &str = "lnx/internal/fragment-block-index";
 pub static FRAGMENT_INFO_PATH: &str = "lnx/internal/info";
+/// The version of the index schema the fragment's documents were written with.
+///
+/// Fragments without this file were written with the first version of the schema.
+pub static FRAGMENT_SCHEMA_VERSION_PATH: &str = "lnx/internal/schema-version";
+/// The folder of the vector indexes of a fragment, each vector field's index is
+/// stored under its field name, see [fragment_vector_index_path].
+pub static FRAGMENT_VECTOR_INDEX_PATH: &str = "lnx/internal/vector-index";
+
+/// Get the path of a vector field's index within a fragment.
+pub fn fragment_vector_index_path(field: &str) -> String {
+    format!("{FRAGMENT_VECTOR_INDEX_PATH}/{field}")
+}
 
 /// Get the path of the metastore database
 pub fn metastore_folder(root: &Path) -> PathBuf {
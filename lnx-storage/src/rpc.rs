@@ -6,6 +6,7 @@ use bytes::Bytes;
 use datacake::rpc::{Handler, Request, RpcService, ServiceRegistry, Status};
 use hashbrown::HashSet;
 use humansize::DECIMAL;
+use lnx_executor::{Executor, IoPoolRole};
 use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::fragments::{
@@ -19,11 +20,20 @@ use crate::fragments::{
 pub struct StorageService {
     writers: IndexFragmentsWriters,
     readers: IndexFragmentsReaders,
+    executor: Executor,
 }
 
 impl StorageService {
-    pub fn new(writers: IndexFragmentsWriters, readers: IndexFragmentsReaders) -> Self {
-        Self { writers, readers }
+    pub fn new(
+        writers: IndexFragmentsWriters,
+        readers: IndexFragmentsReaders,
+        executor: Executor,
+    ) -> Self {
+        Self {
+            writers,
+            readers,
+            executor,
+        }
     }
 }
 
@@ -61,17 +71,24 @@ impl Handler<GetFragment> for StorageService {
             .map(|(key, range)| (key.clone(), (range.end - range.start) as u32))
             .collect();
 
-        let blocks = reader
-            .get_fragment_blocks()
-            .filter(|(block_id, _)| !lookup.contains(&**block_id))
-            .map(|(block_id, info)| (*block_id, info.len(), info.checksum))
-            .collect();
+        let fragment_blocks = reader.get_fragment_blocks().map_err(Status::internal)?;
+
+        let mut blocks = fragment_blocks
+            .filter(|(block_id, _)| !lookup.contains(block_id))
+            .map(|(block_id, info)| (block_id, info.len(), info.checksum))
+            .collect::<Vec<_>>();
+        blocks.sort_unstable_by_key(|(block_id, _, _)| *block_id);
+
+        let block_ids = blocks
+            .iter()
+            .map(|(block_id, _, _)| *block_id)
+            .collect::<Vec<BlockId>>();
 
         info!(remote_addr = %remote, fragment_id = msg.fragment_id, "Starting data stream for node");
 
         let (tx, rx) = flume::bounded(10);
 
-        lnx_executor::spawn_task(async move {
+        self.executor.spawn_on(IoPoolRole::ReplicationIo, async move {
             let mut total_bytes = 0;
 
             let start = Instant::now();
@@ -88,13 +105,6 @@ impl Handler<GetFragment> for StorageService {
                 }
             }
 
-            let mut block_ids = reader
-                .get_fragment_blocks()
-                .map(|(id, _)| *id)
-                .filter(|block_id| !lookup.contains(block_id))
-                .collect::<Vec<BlockId>>();
-            block_ids.sort_unstable();
-
             let mut buffered = Vec::with_capacity(5 << 20);
             for block_id in block_ids {
                 let block = reader
@@ -152,8 +162,10 @@ impl Handler<AddDocBlock> for StorageService {
     ) -> Result<Self::Reply, Status> {
         let msg = msg.into_inner().to_owned().map_err(Status::internal)?;
 
+        // Replicas write blocks with their own default durability.
+        let durability = self.writers.default_durability();
         self.writers
-            .write_block(msg.fragment_id, msg.block)
+            .write_block(msg.fragment_id, msg.block, durability)
             .await
             .map_err(Status::internal)?;
 
@@ -171,8 +183,10 @@ impl Handler<AddManyDocBlocks> for StorageService {
     ) -> Result<Self::Reply, Status> {
         let msg = msg.into_inner().to_owned().map_err(Status::internal)?;
 
+        // Replicas write blocks with their own default durability.
+        let durability = self.writers.default_durability();
         self.writers
-            .write_many_blocks(msg.fragment_id, &msg.blocks)
+            .write_many_blocks(msg.fragment_id, &msg.blocks, durability)
             .await
             .map_err(Status::internal)?;
 
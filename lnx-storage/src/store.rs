@@ -11,8 +11,10 @@ use datacake::eventual_consistency::{
 };
 use datacake::rpc::{async_trait, RpcClient, Status};
 use datacake_lmdb::LmdbStorage;
+use lnx_executor::Executor;
 use rkyv::AlignedVec;
 
+use crate::changefeed::ChangeFeed;
 use crate::fragments::{FragmentInfo, IndexFragmentsWriters, StreamError};
 use crate::listeners::ListenerManager;
 use crate::rpc::GetFragment;
@@ -20,6 +22,7 @@ pub use crate::rpc::StorageService;
 use crate::{IndexFragmentsReaders, Metastore};
 
 pub static INDEX_FRAGMENTS: &str = "lnx-fragments";
+pub static INDEX_METADATA: &str = "lnx-index-metadata";
 
 #[derive(Debug, thiserror::Error)]
 pub enum StorageError {
@@ -39,6 +42,8 @@ pub struct LnxStorage {
     fragment_writers: IndexFragmentsWriters,
     fragment_readers: IndexFragmentsReaders,
     listeners: ListenerManager,
+    change_feed: ChangeFeed,
+    executor: Executor,
 }
 
 impl LnxStorage {
@@ -49,6 +54,8 @@ impl LnxStorage {
         fragment_writers: IndexFragmentsWriters,
         fragment_readers: IndexFragmentsReaders,
         listeners: ListenerManager,
+        change_feed: ChangeFeed,
+        executor: Executor,
     ) -> Self {
         Self {
             lmdb_store,
@@ -56,8 +63,25 @@ impl LnxStorage {
             fragment_writers,
             fragment_readers,
             listeners,
+            change_feed,
+            executor,
         }
     }
+
+    /// Runs a change feed operation on the executor.
+    async fn run_change_feed<T, F>(&self, op: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ChangeFeed) -> Result<T, <LmdbStorage as Storage>::Error>
+            + Send
+            + 'static,
+    {
+        let change_feed = self.change_feed.clone();
+        self.executor
+            .spawn_task(async move { op(&change_feed).map_err(StorageError::Lmdb) })
+            .await
+            .expect("Join task")
+    }
 }
 
 #[async_trait]
@@ -180,7 +204,11 @@ impl Storage for LnxStorage {
             .map_err(StorageError::Rpc)?;
 
         self.fragment_writers
-            .write_stream(info.fragment_id, resp)
+            .write_stream(
+                info.fragment_id,
+                resp,
+                Some(info.num_bytes_total).filter(|&n| n > 0),
+            )
             .await
             .map_err(|e| match e {
                 StreamError::Hyper(e) => StorageError::Rpc(Status::internal(e)),
@@ -211,10 +239,19 @@ impl Storage for LnxStorage {
     ///
     /// Any changes made to keyspace will trigger the storage listener events.
     async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
-        self.lmdb_store
-            .put(keyspace, document.clone())
-            .await
-            .map_err(StorageError::Lmdb)?;
+        if self.change_feed.is_recorded(keyspace) {
+            let keyspace = keyspace.to_owned();
+            let doc = document.clone();
+            self.run_change_feed(move |change_feed| {
+                change_feed.put_documents(&keyspace, std::slice::from_ref(&doc))
+            })
+            .await?;
+        } else {
+            self.lmdb_store
+                .put(keyspace, document.clone())
+                .await
+                .map_err(StorageError::Lmdb)?;
+        }
 
         self.listeners.trigger_on_put(keyspace, document);
 
@@ -273,11 +310,24 @@ impl Storage for LnxStorage {
         keyspace: &str,
         documents: impl Iterator<Item = Document> + Send,
     ) -> Result<(), BulkMutationError<Self::Error>> {
+        if self.change_feed.is_recorded(keyspace) {
+            let keyspace_owned = keyspace.to_owned();
+            let docs = documents.collect::<Vec<_>>();
+            let written = docs.clone();
+            self.run_change_feed(move |change_feed| {
+                change_feed.put_documents(&keyspace_owned, &docs)
+            })
+            .await
+            .map_err(|e| BulkMutationError::new(e, Vec::new()))?;
+
+            for doc in written {
+                self.listeners.trigger_on_put(keyspace, doc);
+            }
+            return Ok(());
+        }
+
         let mut successful_docs = Vec::new();
-        let documents = documents.map(|doc| {
-            successful_docs.push(doc.clone());
-            doc
-        });
+        let documents = documents.inspect(|doc| successful_docs.push(doc.clone()));
 
         // We cheat here when trigger the listeners as we know
         // LMDB will do all-or-nothing in the transaction.
@@ -302,10 +352,18 @@ impl Storage for LnxStorage {
         doc_id: Key,
         timestamp: HLCTimestamp,
     ) -> Result<(), Self::Error> {
-        self.lmdb_store
-            .mark_as_tombstone(keyspace, doc_id, timestamp)
-            .await
-            .map_err(StorageError::Lmdb)?;
+        if self.change_feed.is_recorded(keyspace) {
+            let keyspace_owned = keyspace.to_owned();
+            self.run_change_feed(move |change_feed| {
+                change_feed.delete_documents(&keyspace_owned, [(doc_id, timestamp)])
+            })
+            .await?;
+        } else {
+            self.lmdb_store
+                .mark_as_tombstone(keyspace, doc_id, timestamp)
+                .await
+                .map_err(StorageError::Lmdb)?;
+        }
 
         if keyspace != INDEX_FRAGMENTS {
             self.listeners.trigger_on_del(keyspace, doc_id);
@@ -313,13 +371,14 @@ impl Storage for LnxStorage {
         }
 
         let metastore = self.metastore.clone();
-        lnx_executor::spawn_task(async move {
-            metastore
-                .remove_fragment(doc_id)
-                .map_err(StorageError::Lmdb)
-        })
-        .await
-        .expect("Join task")?;
+        self.executor
+            .spawn_task(async move {
+                metastore
+                    .remove_fragment(doc_id)
+                    .map_err(StorageError::Lmdb)
+            })
+            .await
+            .expect("Join task")?;
 
         self.listeners.trigger_fragment_delete(doc_id);
 
@@ -333,10 +392,25 @@ impl Storage for LnxStorage {
         documents: impl Iterator<Item = DocumentMetadata> + Send,
     ) -> Result<(), BulkMutationError<Self::Error>> {
         let mut doc_ids = Vec::new();
-        let documents = documents.map(|doc| {
-            doc_ids.push(doc.id);
-            doc
-        });
+        let documents =
+            documents.inspect(|doc| doc_ids.push((doc.id, doc.last_updated)));
+
+        if self.change_feed.is_recorded(keyspace) {
+            let keyspace_owned = keyspace.to_owned();
+            let docs = documents
+                .map(|doc| (doc.id, doc.last_updated))
+                .collect::<Vec<_>>();
+            self.run_change_feed(move |change_feed| {
+                change_feed.delete_documents(&keyspace_owned, docs)
+            })
+            .await
+            .map_err(|e| BulkMutationError::new(e, Vec::new()))?;
+
+            for (doc_id, _) in doc_ids {
+                self.listeners.trigger_on_del(keyspace, doc_id);
+            }
+            return Ok(());
+        }
 
         if keyspace != INDEX_FRAGMENTS {
             // We cheat here when trigger the listeners as we know
@@ -352,13 +426,58 @@ impl Storage for LnxStorage {
                     keys,
                 ))
             } else {
-                for doc_id in doc_ids {
+                for (doc_id, _) in doc_ids {
                     self.listeners.trigger_on_del(keyspace, doc_id);
                 }
                 Ok(())
             };
         }
 
+        // Only the fragments which were successfully tombstoned are removed,
+        // any failed fragments are left for the replication system to retry.
+        let (tombstoned, error) = match self
+            .lmdb_store
+            .mark_many_as_tombstone(keyspace, documents)
+            .await
+        {
+            Ok(()) => (doc_ids.into_iter().map(|(id, _)| id).collect(), None),
+            Err(e) => {
+                let keys = e.successful_doc_ids().to_vec();
+                warn!(
+                    num_successful = keys.len(),
+                    error = ?e,
+                    "Tombstoned fragments with partial failure",
+                );
+                (keys, Some(StorageError::Lmdb(e.into_inner())))
+            },
+        };
+
+        let metastore = self.metastore.clone();
+        let fragment_ids = tombstoned.clone();
+        let res = self
+            .executor
+            .spawn_task(async move {
+                metastore
+                    .remove_fragments(fragment_ids.into_iter())
+                    .map_err(StorageError::Lmdb)
+            })
+            .await
+            .expect("Join task");
+
+        // The metastore removal is all-or-nothing so if it fails, none of the
+        // fragments can be considered deleted.
+        if let Err(e) = res {
+            return Err(BulkMutationError::new(e, Vec::new()));
+        }
+
+        for &fragment_id in tombstoned.iter() {
+            self.listeners.trigger_fragment_delete(fragment_id);
+        }
+
+        if let Some(error) = error {
+            return Err(BulkMutationError::new(error, tombstoned));
+        }
+
         Ok(())
     }
 
@@ -393,21 +512,30 @@ mod tests {
     use uuid::Uuid;
 
     use super::*;
-    use crate::{loader, resolvers, EnvCtx};
+    use crate::changefeed::ChangeFeedOptions;
+    use crate::{loader, resolvers, EnvCtx, EnvCtxInner};
 
     #[tokio::test]
     async fn test_store_suite() -> anyhow::Result<()> {
-        lnx_executor::build_default_pools(1).expect("Build pools");
-
         let root = temp_dir()
             .join("lnx-tests")
             .join(Uuid::new_v4().to_string());
-        let env = EnvCtx::new(root);
+        let mut inner = EnvCtxInner::new(root);
+        inner.executor = Executor::with_threads(2)?;
+        let env = EnvCtx::from(inner);
 
         let lmdb_store =
             LmdbStorage::open(&resolvers::metastore_folder(&env.root_path)).await?;
         let metastore = Metastore::from_env(lmdb_store.handle().env().clone())?;
-        let listeners = ListenerManager::default();
+        let listeners = ListenerManager::new(env.executor.clone());
+        let change_feed = ChangeFeed::from_env(
+            lmdb_store.handle().env().clone(),
+            ChangeFeedOptions {
+                enabled: true,
+                keyspaces: None,
+            },
+            listeners.clone(),
+        )?;
 
         info!("Loading existing fragment readers");
         let readers = loader::load_readers(env.clone(), &metastore, listeners.clone())
@@ -426,6 +554,8 @@ mod tests {
             writers.clone(),
             readers.clone(),
             listeners.clone(),
+            change_feed,
+            env.executor.clone(),
         );
 
         test_suite::run_test_suite(store).await;
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use datacake::node::{
+    ConnectionConfig,
+    Consistency,
+    DCAwareSelector,
+    DatacakeNodeBuilder,
+};
+use futures::StreamExt;
+
+use crate::tests::OpsLogger;
+use crate::{
+    ChangeFeedOptions,
+    ChangeKind,
+    EnvCtx,
+    EnvCtxInner,
+    LnxStorageExtension,
+    LnxStorageHandle,
+    StorageGuard,
+};
+
+#[tokio::test]
+async fn test_change_feed_resume_from_cursor() -> anyhow::Result<()> {
+    let mut inner = EnvCtxInner::for_test();
+    inner.change_feed = ChangeFeedOptions {
+        enabled: true,
+        keyspaces: Some([OpsLogger::KEYSPACE.to_string()].into_iter().collect()),
+    };
+    let env = EnvCtx::from(inner);
+    crate::resolvers::init_folders(&env.root_path)?;
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (guard, store) = create_node_from_env(env.clone()).await?;
+
+    store
+        .put(
+            OpsLogger::KEYSPACE,
+            1,
+            b"Hello, world".to_vec(),
+            Consistency::None,
+        )
+        .await
+        .expect("Put basic kv");
+    store
+        .put(
+            "not-recorded",
+            1,
+            b"Hello, world".to_vec(),
+            Consistency::None,
+        )
+        .await
+        .expect("Put basic kv");
+    store
+        .del(OpsLogger::KEYSPACE, 1, Consistency::None)
+        .await
+        .expect("Delete basic kv");
+
+    let changes = store
+        .change_feed()
+        .read(OpsLogger::KEYSPACE, None, 10)
+        .expect("Read changes");
+    assert_eq!(changes.len(), 2, "Both changes should be recorded");
+    assert_eq!(changes[0].doc_id, 1);
+    assert_eq!(changes[0].kind, ChangeKind::Put(b"Hello, world".to_vec()));
+    assert_eq!(changes[1].doc_id, 1);
+    assert_eq!(changes[1].kind, ChangeKind::Delete);
+    assert!(
+        changes[0].cursor < changes[1].cursor,
+        "Changes should be ordered"
+    );
+
+    let unrecorded = store
+        .change_feed()
+        .read("not-recorded", None, 10)
+        .expect("Read changes");
+    assert!(unrecorded.is_empty(), "Keyspace should not be recorded");
+
+    store
+        .change_feed()
+        .commit_cursor("consumer", OpsLogger::KEYSPACE, changes[0].cursor)
+        .expect("Commit cursor");
+
+    // Drop the node simulating a shutdown.
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Re-create the node
+    let (_guard, store) = create_node_from_env(env.clone()).await?;
+
+    let cursor = store
+        .change_feed()
+        .get_cursor("consumer", OpsLogger::KEYSPACE)
+        .expect("Get cursor");
+    assert_eq!(
+        cursor,
+        Some(changes[0].cursor),
+        "Cursor should be persisted"
+    );
+
+    let resumed = store
+        .change_feed()
+        .read(OpsLogger::KEYSPACE, cursor, 10)
+        .expect("Read changes");
+    assert_eq!(
+        resumed,
+        changes[1..],
+        "Only newer changes should be returned"
+    );
+
+    let mut tail = Box::pin(store.change_feed().tail(OpsLogger::KEYSPACE, cursor));
+    let entry = tokio::time::timeout(Duration::from_secs(1), tail.next())
+        .await
+        .expect("Existing change should be returned")
+        .expect("Stream should not end")
+        .expect("Read change");
+    assert_eq!(entry, changes[1], "Existing changes should be tailed first");
+
+    store
+        .put(
+            OpsLogger::KEYSPACE,
+            2,
+            b"Hello, world 2".to_vec(),
+            Consistency::None,
+        )
+        .await
+        .expect("Put basic kv");
+
+    let entry = tokio::time::timeout(Duration::from_secs(1), tail.next())
+        .await
+        .expect("New change should be returned")
+        .expect("Stream should not end")
+        .expect("Read change");
+    assert_eq!(entry.doc_id, 2, "New changes should be tailed");
+
+    let doc = store
+        .get(OpsLogger::KEYSPACE, 2)
+        .await
+        .expect("Get basic kv")
+        .expect("Document should exist");
+    assert_eq!(doc.data(), b"Hello, world 2", "Doc data should match");
+
+    let num_removed = store
+        .change_feed()
+        .truncate(OpsLogger::KEYSPACE, changes[1].cursor)
+        .expect("Truncate feed");
+    assert_eq!(num_removed, 2, "Processed changes should be removed");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_change_feed_keeps_existing_documents() -> anyhow::Result<()> {
+    let env = EnvCtx::for_test();
+    crate::resolvers::init_folders(&env.root_path)?;
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (guard, store) = create_node_from_env(env.clone()).await?;
+    store
+        .put_many(
+            OpsLogger::KEYSPACE,
+            [(1, b"Hello, world".to_vec()), (2, b"Deleted".to_vec())],
+            Consistency::None,
+        )
+        .await
+        .expect("Put basic kv");
+    store
+        .del(OpsLogger::KEYSPACE, 2, Consistency::None)
+        .await
+        .expect("Delete basic kv");
+
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Re-create the node with the keyspace now being recorded.
+    let (guard, store) = create_node_from_env(with_change_feed(&env, true)).await?;
+
+    let doc = store
+        .get(OpsLogger::KEYSPACE, 1)
+        .await
+        .expect("Get basic kv")
+        .expect("Existing document should exist");
+    assert_eq!(doc.data(), b"Hello, world", "Doc data should match");
+    let doc = store
+        .get(OpsLogger::KEYSPACE, 2)
+        .await
+        .expect("Get basic kv");
+    assert!(doc.is_none(), "Deleted document should not exist");
+
+    let changes = store
+        .change_feed()
+        .read(OpsLogger::KEYSPACE, None, 10)
+        .expect("Read changes");
+    assert!(
+        changes.is_empty(),
+        "Existing documents should not be recorded"
+    );
+
+    store
+        .put(
+            OpsLogger::KEYSPACE,
+            1,
+            b"Updated".to_vec(),
+            Consistency::None,
+        )
+        .await
+        .expect("Put basic kv");
+    let changes = store
+        .change_feed()
+        .read(OpsLogger::KEYSPACE, None, 10)
+        .expect("Read changes");
+    assert_eq!(changes.len(), 1, "New changes should be recorded");
+
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Recorded documents are kept in the KV store when recording is disabled.
+    let (_guard, store) = create_node_from_env(with_change_feed(&env, false)).await?;
+    let doc = store
+        .get(OpsLogger::KEYSPACE, 1)
+        .await
+        .expect("Get basic kv")
+        .expect("Document should exist");
+    assert_eq!(doc.data(), b"Updated", "Doc data should match");
+
+    Ok(())
+}
+
+/// Creates an environment sharing the data and executor of the given environment.
+fn with_change_feed(env: &EnvCtx, enabled: bool) -> EnvCtx {
+    let mut inner = EnvCtxInner::new(env.root_path.clone());
+    inner.executor = env.executor.clone();
+    inner.change_feed = ChangeFeedOptions {
+        enabled,
+        keyspaces: None,
+    };
+    EnvCtx::from(inner)
+}
+
+async fn create_node_from_env(
+    env: EnvCtx,
+) -> anyhow::Result<(StorageGuard, LnxStorageHandle)> {
+    let addr = test_helper::get_unused_addr();
+    let connection_cfg = ConnectionConfig::new(addr, addr, Vec::<String>::new());
+
+    let node = DatacakeNodeBuilder::<DCAwareSelector>::new(1, connection_cfg)
+        .connect()
+        .await?;
+
+    node.add_extension(LnxStorageExtension::new(env))
+        .await
+        .map_err(anyhow::Error::from)
+}
@@ -0,0 +1,87 @@
+use std::io;
+use std::time::Duration;
+
+use super::recovery::create_node_from_env;
+use crate::{
+    AddBlockError,
+    EnvCtx,
+    EnvCtxInner,
+    FragmentInfo,
+    InsufficientDiskSpace,
+    SharedSlice,
+};
+
+#[tokio::test]
+async fn test_writes_rejected_below_disk_space_watermark() -> anyhow::Result<()> {
+    let env = EnvCtx::for_test();
+    crate::resolvers::init_folders(&env.root_path)?;
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (guard, store) = create_node_from_env(env.clone()).await?;
+
+    let block_data = b"hello, world";
+    store
+        .add_block(1, 1, block_data.to_vec(), 1)
+        .await
+        .expect("Add block");
+    store
+        .commit_fragment(
+            1,
+            FragmentInfo {
+                // Not validated
+                fragment_id: 1,
+                orphaned_id: None,
+                num_blocks: 0,
+                num_bytes_total: 0,
+                num_docs: 0,
+                child_of_fragments: vec![],
+            },
+        )
+        .await
+        .expect("Commit fragment");
+
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Re-create the node with a watermark which can never be met.
+    let mut inner = EnvCtxInner::new(env.root_path.clone());
+    inner.executor = env.executor.clone();
+    inner.min_free_disk_space = u64::MAX;
+    let (_guard, store) = create_node_from_env(EnvCtx::from(inner)).await?;
+
+    let err = store
+        .add_block(2, 2, block_data.to_vec(), 1)
+        .await
+        .expect_err("Write should be rejected");
+    assert!(
+        matches!(
+            &err,
+            AddBlockError::LocalWriteError(e) if is_insufficient_disk_space(e),
+        ),
+        "Unexpected error: {err}",
+    );
+
+    let err = store
+        .add_file(2, "my-path.txt", SharedSlice::copy_from_slice(block_data))
+        .await
+        .expect_err("Write should be rejected");
+    assert!(is_insufficient_disk_space(&err), "Unexpected error: {err}");
+
+    let fragment = store
+        .get_reader(1)
+        .expect("Fragment should exist as a reader");
+    assert_eq!(
+        fragment.read_block(1).as_deref(),
+        Some(block_data.as_ref()),
+        "Reads should not be affected",
+    );
+
+    Ok(())
+}
+
+fn is_insufficient_disk_space(e: &io::Error) -> bool {
+    e.get_ref()
+        .map(|e| e.is::<InsufficientDiskSpace>())
+        .unwrap_or_default()
+}
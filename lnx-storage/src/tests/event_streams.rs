@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use datacake::node::Consistency;
+use futures::StreamExt;
+
+use crate::listeners::{EventFilter, EventKind, StorageEvent, SubscriptionEvent};
+use crate::tests::OpsLogger;
+use crate::{FragmentInfo, LnxStorageHandle};
+
+#[tokio::test]
+async fn test_keyspace_event_stream() -> anyhow::Result<()> {
+    super::single_node_test_harness(|store: LnxStorageHandle, _ops_logger| async move {
+        let mut events = store
+            .listeners()
+            .subscribe(EventFilter::default().keyspace(OpsLogger::KEYSPACE), 10);
+
+        store
+            .put("other-keyspace", 1, b"ignored".to_vec(), Consistency::None)
+            .await
+            .expect("Put basic kv");
+        store
+            .put(
+                OpsLogger::KEYSPACE,
+                1,
+                b"Hello, world".to_vec(),
+                Consistency::None,
+            )
+            .await
+            .expect("Put basic kv");
+        store
+            .del(OpsLogger::KEYSPACE, 1, Consistency::None)
+            .await
+            .expect("Delete basic kv");
+
+        let event = tokio::time::timeout(Duration::from_secs(1), events.next())
+            .await
+            .expect("Event should be received")
+            .expect("Stream should not end");
+        match event {
+            SubscriptionEvent::Event(StorageEvent::Put { keyspace, doc }) => {
+                assert_eq!(keyspace, OpsLogger::KEYSPACE, "Keyspace should match");
+                assert_eq!(doc.id(), 1, "Doc IDs should match");
+                assert_eq!(doc.data(), b"Hello, world", "Doc data should match");
+            },
+            other => panic!("Unexpected event {other:?}"),
+        }
+
+        let event = tokio::time::timeout(Duration::from_secs(1), events.next())
+            .await
+            .expect("Event should be received")
+            .expect("Stream should not end");
+        assert!(
+            matches!(
+                event,
+                SubscriptionEvent::Event(StorageEvent::Delete { doc_id: 1, .. })
+            ),
+            "Delete should be received after the put"
+        );
+        assert!(
+            events.try_next_event().is_none(),
+            "No more events should exist"
+        );
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_fragment_event_stream() -> anyhow::Result<()> {
+    super::single_node_test_harness(|store: LnxStorageHandle, _ops_logger| async move {
+        let mut events = store.listeners().subscribe(
+            EventFilter::default()
+                .fragment(1)
+                .kind(EventKind::Seal)
+                .kind(EventKind::ReadReady),
+            10,
+        );
+
+        for fragment_id in [2, 1] {
+            store
+                .add_block(fragment_id, fragment_id, b"Hello, world".to_vec(), 1)
+                .await
+                .expect("Add block locally");
+
+            store
+                .commit_fragment(
+                    fragment_id,
+                    FragmentInfo {
+                        // Not validated
+                        fragment_id,
+                        orphaned_id: None,
+                        num_blocks: 0,
+                        num_bytes_total: 0,
+                        num_docs: 0,
+                        child_of_fragments: vec![],
+                    },
+                )
+                .await
+                .expect("Commit fragment");
+        }
+
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(1), events.next())
+                .await
+                .expect("Event should be received")
+                .expect("Stream should not end");
+
+            match event {
+                SubscriptionEvent::Event(event) => {
+                    received.push((event.kind(), event.fragment_id()))
+                },
+                other => panic!("Unexpected event {other:?}"),
+            }
+        }
+
+        assert_eq!(
+            received,
+            [(EventKind::Seal, Some(1)), (EventKind::ReadReady, Some(1))],
+            "Events should be filtered and ordered",
+        );
+    })
+    .await
+}
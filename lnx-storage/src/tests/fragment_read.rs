@@ -20,6 +20,16 @@ async fn test_fragment_read_block() -> anyhow::Result<()> {
             .await
             .expect("Add file");
 
+        store
+            .set_fragment_schema_version(1, 3)
+            .await
+            .expect("Set schema version");
+
+        store
+            .set_fragment_vector_index(1, "embedding", b"vector-index".to_vec())
+            .await
+            .expect("Set vector index");
+
         store
             .commit_fragment(
                 1,
@@ -40,6 +50,17 @@ async fn test_fragment_read_block() -> anyhow::Result<()> {
         tokio::time::sleep(Duration::from_secs(1)).await;
 
         let reader = store.get_reader(1).expect("Reader should exist");
+        assert_eq!(
+            reader.schema_version().expect("Read schema version"),
+            3,
+            "Fragment should keep the schema version it was written with"
+        );
+        assert_eq!(
+            reader.vector_index("embedding").as_deref(),
+            Some(b"vector-index".as_ref()),
+            "Fragment should contain the vector index of the field",
+        );
+        assert!(reader.vector_index("other").is_none());
 
         let fetched = reader
             .read_block(1)
@@ -235,6 +235,47 @@ async fn test_local_fragment_delete() -> anyhow::Result<()> {
     .await
 }
 
+#[tokio::test]
+async fn test_local_delete_many_fragments() -> anyhow::Result<()> {
+    super::single_node_test_harness(|store, ops_logger| async move {
+        for fragment_id in 1..=3 {
+            store
+                .add_block(fragment_id, fragment_id, b"Hello, world".to_vec(), 1)
+                .await
+                .expect("Add block locally");
+
+            store
+                .commit_fragment(
+                    fragment_id,
+                    FragmentInfo {
+                        // Not validated
+                        fragment_id,
+                        orphaned_id: None,
+                        num_blocks: 0,
+                        num_bytes_total: 0,
+                        num_docs: 0,
+                        child_of_fragments: vec![],
+                    },
+                )
+                .await
+                .expect("Commit fragment");
+        }
+
+        store
+            .delete_many_fragments([1, 2])
+            .await
+            .expect("Delete fragments");
+
+        // Since notifications are executed asynchronously, we need to wait temporarily.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut deletes = ops_logger.deletes();
+        deletes.sort_unstable();
+        assert_eq!(deletes, [1, 2], "Deleted fragments should match");
+    })
+    .await
+}
+
 #[tokio::test]
 async fn test_cluster_commit() -> anyhow::Result<()> {
     super::multi_node_test_harness(
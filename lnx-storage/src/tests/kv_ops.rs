@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use datacake::node::Consistency;
 
 use crate::tests::OpsLogger;
@@ -97,3 +99,35 @@ async fn test_many_basic_kv_ops() -> anyhow::Result<()> {
     })
     .await
 }
+
+#[tokio::test]
+async fn test_index_metadata_replication() -> anyhow::Result<()> {
+    super::multi_node_test_harness(3, |nodes: Vec<LnxStorageHandle>, _| async move {
+        // Wait for the first node to discover the nodes which joined after it.
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        nodes[0]
+            .set_index_metadata("schema-history", b"version-0".to_vec())
+            .await
+            .expect("Set index metadata");
+
+        for node in nodes.iter() {
+            let value = node
+                .get_index_metadata("schema-history")
+                .await
+                .expect("Get index metadata");
+            assert_eq!(
+                value.as_deref(),
+                Some(b"version-0".as_ref()),
+                "Metadata should be replicated to every node",
+            );
+            assert_eq!(
+                node.get_index_metadata("other")
+                    .await
+                    .expect("Get metadata"),
+                None
+            );
+        }
+    })
+    .await
+}
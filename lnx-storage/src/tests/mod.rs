@@ -11,6 +11,9 @@ use crate::listeners::{FragmentListener, StorageListener};
 use crate::{DatacakeNode, EnvCtx, LnxStorageExtension, LnxStorageHandle, StorageGuard};
 
 mod block_replication;
+mod change_feed;
+mod disk_space;
+mod event_streams;
 mod fragment_read;
 mod fragment_replication;
 mod kv_ops;
@@ -24,7 +27,6 @@ where
 {
     let env = EnvCtx::for_test();
     crate::resolvers::init_folders(&env.root_path)?;
-    lnx_executor::build_default_pools(1)?;
     let _ = tracing_subscriber::fmt::try_init();
 
     let addr = test_helper::get_unused_addr();
@@ -52,12 +54,11 @@ where
 }
 
 /// A setup harness for a multi node cluster
-async fn multi_node_test_harness<'a, CB, F>(num_nodes: u8, cb: CB) -> anyhow::Result<()>
+async fn multi_node_test_harness<CB, F>(num_nodes: u8, cb: CB) -> anyhow::Result<()>
 where
     F: Future<Output = ()>,
     CB: FnOnce(Vec<LnxStorageHandle>, OpsLogger) -> F,
 {
-    lnx_executor::build_default_pools(1)?;
     let _ = tracing_subscriber::fmt::try_init();
 
     let (nodes, _guards) = connect_nodes(num_nodes).await?;
@@ -117,6 +118,9 @@ async fn connect_nodes(
 
 type BlockEvent = (u64, Vec<(u64, Vec<u8>)>);
 
+/// A document ID and its data.
+type KvPut = (u64, Vec<u8>);
+
 #[derive(Default, Clone)]
 /// A listener that tracks all the events that go
 /// through it and stores them.
@@ -129,7 +133,7 @@ struct OpsLogger {
     files: Arc<Mutex<Vec<(u64, String)>>>,
     sealed: Arc<Mutex<Vec<u64>>>,
     deletes: Arc<Mutex<Vec<u64>>>,
-    kv_puts: Arc<Mutex<Vec<(u64, Vec<u8>)>>>,
+    kv_puts: Arc<Mutex<Vec<KvPut>>>,
     kv_deletes: Arc<Mutex<Vec<u64>>>,
 }
 
@@ -152,7 +156,7 @@ impl OpsLogger {
         self.deletes.lock().clone()
     }
 
-    fn kv_puts(&self) -> Vec<(u64, Vec<u8>)> {
+    fn kv_puts(&self) -> Vec<KvPut> {
         self.kv_puts.lock().clone()
     }
 
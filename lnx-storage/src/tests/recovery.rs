@@ -4,7 +4,9 @@ use datacake::node::{ConnectionConfig, DCAwareSelector, DatacakeNodeBuilder};
 
 use crate::fragments::BlockInfo;
 use crate::{
+    DurabilityMode,
     EnvCtx,
+    EnvCtxInner,
     FragmentInfo,
     LnxStorageExtension,
     LnxStorageHandle,
@@ -16,7 +18,6 @@ use crate::{
 async fn test_partially_written_fragment_recovery() -> anyhow::Result<()> {
     let env = EnvCtx::for_test();
     crate::resolvers::init_folders(&env.root_path)?;
-    lnx_executor::build_default_pools(1)?;
     let _ = tracing_subscriber::fmt::try_init();
 
     let (guard, store) = create_node_from_env(env.clone()).await?;
@@ -75,11 +76,67 @@ async fn test_partially_written_fragment_recovery() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_unsynced_blocks_discarded_on_recovery() -> anyhow::Result<()> {
+    let env = EnvCtx::for_test();
+    crate::resolvers::init_folders(&env.root_path)?;
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (guard, store) = create_node_from_env(env.clone()).await?;
+
+    let block_data = b"hello, world".to_vec();
+    for block_id in 1..=2 {
+        store
+            .add_block_with_durability(
+                1,
+                block_id,
+                block_data.clone(),
+                1,
+                DurabilityMode::ReplicationOnly,
+            )
+            .await
+            .expect("Add block");
+    }
+
+    // Drop the node simulating a shutdown.
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Simulate the second block never reaching the disk.
+    let path = crate::resolvers::get_fragment_location(&env.root_path, 1);
+    let file = std::fs::OpenOptions::new().write(true).open(&path)?;
+    file.set_len(40)?;
+    drop(file);
+
+    // Re-create the node
+    let (_guard, store) = create_node_from_env(env.clone()).await?;
+
+    let state = store
+        .writers
+        .get_current_writer_state(1)
+        .await
+        .expect("Writer should exist after reload");
+
+    assert_eq!(
+        state.existing_blocks,
+        vec![(
+            1,
+            BlockInfo {
+                location: 16..28,
+                checksum: 1,
+            }
+        )],
+        "Only the persisted block should be recovered",
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_comitted_fragment_recovery() -> anyhow::Result<()> {
     let env = EnvCtx::for_test();
     crate::resolvers::init_folders(&env.root_path)?;
-    lnx_executor::build_default_pools(1)?;
     let _ = tracing_subscriber::fmt::try_init();
 
     let (guard, store) = create_node_from_env(env.clone()).await?;
@@ -149,7 +206,104 @@ async fn test_comitted_fragment_recovery() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn create_node_from_env(
+#[tokio::test]
+async fn test_comitted_fragment_lazy_recovery() -> anyhow::Result<()> {
+    let mut inner = EnvCtxInner::for_test();
+    inner.fragment_loading.lazy = true;
+    inner.fragment_loading.concurrency = 2;
+    let env = EnvCtx::from(inner);
+    crate::resolvers::init_folders(&env.root_path)?;
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (guard, store) = create_node_from_env(env.clone()).await?;
+
+    let block_data = b"hello, world";
+    for fragment_id in 1..=3 {
+        store
+            .add_block(fragment_id, fragment_id, block_data.to_vec(), 1)
+            .await
+            .expect("Add block");
+
+        store
+            .commit_fragment(
+                fragment_id,
+                FragmentInfo {
+                    // Not validated
+                    fragment_id,
+                    orphaned_id: None,
+                    num_blocks: 0,
+                    num_bytes_total: 0,
+                    num_docs: 0,
+                    child_of_fragments: vec![],
+                },
+            )
+            .await
+            .expect("Commit fragment");
+    }
+
+    // Drop the node simulating a shutdown.
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Re-create the node
+    let (_guard, store) = create_node_from_env(env.clone()).await?;
+
+    let progress = env.fragment_load_progress();
+    assert!(progress.is_done(), "Loading should be complete");
+    assert_eq!(progress.total(), 3, "All fragments should be tracked");
+    assert_eq!(progress.loaded(), 3, "All fragments should be loaded");
+
+    for fragment_id in 1..=3 {
+        let fragment = store
+            .readers
+            .get_reader(fragment_id)
+            .expect("Fragment should exist as a reader");
+
+        assert_eq!(
+            fragment.read_block(fragment_id).as_deref(),
+            Some(block_data.as_ref()),
+            "Lazily loaded blocks should match",
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_index_metadata_recovery() -> anyhow::Result<()> {
+    let env = EnvCtx::for_test();
+    crate::resolvers::init_folders(&env.root_path)?;
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (guard, store) = create_node_from_env(env.clone()).await?;
+
+    assert_eq!(store.get_index_metadata("schema-history").await?, None);
+    store
+        .set_index_metadata("schema-history", b"version-0".to_vec())
+        .await?;
+    store
+        .set_index_metadata("schema-history", b"version-1".to_vec())
+        .await?;
+
+    // Drop the node simulating a shutdown.
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Re-create the node
+    let (_guard, store) = create_node_from_env(env.clone()).await?;
+
+    assert_eq!(
+        store.get_index_metadata("schema-history").await?,
+        Some(b"version-1".to_vec()),
+        "Metadata should be kept across restarts",
+    );
+
+    Ok(())
+}
+
+pub(super) async fn create_node_from_env(
     env: EnvCtx,
 ) -> anyhow::Result<(StorageGuard, LnxStorageHandle)> {
     let addr = test_helper::get_unused_addr();
@@ -163,3 +317,32 @@ async fn create_node_from_env(
         .await
         .map_err(anyhow::Error::from)
 }
+
+#[tokio::test]
+async fn test_durability_recovery() -> anyhow::Result<()> {
+    let env = EnvCtx::for_test();
+    crate::resolvers::init_folders(&env.root_path)?;
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (guard, store) = create_node_from_env(env.clone()).await?;
+
+    assert_eq!(store.durability(), DurabilityMode::Sync);
+    store.set_durability(DurabilityMode::ReplicationOnly)?;
+    assert_eq!(store.durability(), DurabilityMode::ReplicationOnly);
+
+    // Drop the node simulating a shutdown.
+    drop(store);
+    drop(guard);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Re-create the node
+    let (_guard, store) = create_node_from_env(env.clone()).await?;
+
+    assert_eq!(
+        store.durability(),
+        DurabilityMode::ReplicationOnly,
+        "Durability should be kept across restarts",
+    );
+
+    Ok(())
+}
@@ -90,7 +90,6 @@ async fn run_local_batching(data: Vec<u8>) -> anyhow::Result<()> {
                 let mut hashing_time = Duration::default();
 
                 let blocks_chunks = (0..NUM_BLOCKS)
-                    .into_iter()
                     .map(|block_id| {
                         let checksum =
                             timeit!(&mut hashing_time, { crc32fast::hash(&data) });
@@ -44,7 +44,7 @@ where
 }
 
 /// A setup harness for a multi node cluster
-pub(super) async fn multi_node_test_harness<'a, CB, F>(
+pub(super) async fn multi_node_test_harness<CB, F>(
     num_nodes: u8,
     cb: CB,
 ) -> anyhow::Result<()>
@@ -130,7 +130,6 @@ async fn create_fragment_and_commit(
 
     timeit!(blocks_add_time, {
         let blocks_chunks = (0..num_blocks)
-            .into_iter()
             .map(|block_id| {
                 let checksum = crc32fast::hash(&data);
                 (block_id as u64, data.clone(), checksum)